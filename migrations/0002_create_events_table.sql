@@ -0,0 +1,24 @@
+create table if not exists events (
+    id uuid primary key,
+    delivery_id varchar(127),
+    pr_url text not null,
+    event_type varchar(63) not null,
+    actor varchar(255) not null,
+    received_at timestamptz not null
+);
+
+create index if not exists events_pr_url_idx on events(pr_url);
+
+create table if not exists slack_actions (
+    id uuid primary key,
+    event_id uuid not null references events(id) on delete cascade,
+    kind varchar(63) not null,
+    channel varchar(127) not null,
+    timestamp varchar(127) not null,
+    emoji varchar(127) not null,
+    succeeded boolean not null,
+    error text,
+    performed_at timestamptz not null
+);
+
+create index if not exists slack_actions_event_id_idx on slack_actions(event_id);
@@ -0,0 +1,7 @@
+-- Slack retries used to be tracked more than once, keep the earliest row of every message and url
+delete from pull_requests duplicate
+using pull_requests original
+where duplicate.url = original.url
+    and duplicate.channel = original.channel
+    and duplicate.timestamp = original.timestamp
+    and (duplicate.inserted_at, duplicate.id) > (original.inserted_at, original.id);
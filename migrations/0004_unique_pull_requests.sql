@@ -0,0 +1,2 @@
+alter table pull_requests
+    add constraint pull_requests_url_channel_timestamp_key unique (url, channel, timestamp);
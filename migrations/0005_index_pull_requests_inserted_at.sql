@@ -0,0 +1 @@
+create index if not exists pull_requests_inserted_at_idx on pull_requests(inserted_at);
@@ -0,0 +1,10 @@
+create table if not exists pull_request_metadata (
+    url text primary key,
+    repository varchar(255) not null,
+    number bigint not null,
+    title text not null,
+    author varchar(255),
+    updated_at timestamptz not null
+);
+
+create index if not exists pull_request_metadata_updated_at_idx on pull_request_metadata(updated_at);
@@ -0,0 +1,7 @@
+create table if not exists github_installations (
+    repository varchar(255) primary key,
+    installation_id bigint not null,
+    updated_at timestamptz not null
+);
+
+create index if not exists github_installations_installation_id_idx on github_installations(installation_id);
@@ -0,0 +1,8 @@
+create table if not exists channel_settings (
+    channel varchar(255) not null,
+    event_type varchar(255) not null,
+    emoji varchar(255),
+    enabled boolean,
+    updated_at timestamptz not null,
+    primary key (channel, event_type)
+);
@@ -0,0 +1,8 @@
+create table if not exists pull_request_comments (
+    url varchar(255) primary key,
+    comments bigint not null,
+    reacted_comments bigint not null default 0,
+    updated_at timestamptz not null
+);
+
+create index if not exists pull_request_comments_updated_at_idx on pull_request_comments(updated_at);
@@ -0,0 +1,2 @@
+-- The retention job purges the audit log by age
+create index if not exists events_received_at_idx on events(received_at);
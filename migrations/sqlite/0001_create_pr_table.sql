@@ -0,0 +1,11 @@
+create table if not exists pull_requests (
+    id blob primary key,
+    url text not null,
+    inserted_at text not null,
+    channel text not null,
+    timestamp text not null,
+    unique (url, channel, timestamp)
+);
+
+create index if not exists pull_requests_url_idx on pull_requests(url);
+create index if not exists pull_requests_inserted_at_idx on pull_requests(inserted_at);
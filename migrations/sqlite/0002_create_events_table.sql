@@ -0,0 +1,24 @@
+create table if not exists events (
+    id blob primary key,
+    delivery_id text,
+    pr_url text not null,
+    event_type text not null,
+    actor text not null,
+    received_at text not null
+);
+
+create index if not exists events_pr_url_idx on events(pr_url);
+
+create table if not exists slack_actions (
+    id blob primary key,
+    event_id blob not null references events(id) on delete cascade,
+    kind text not null,
+    channel text not null,
+    timestamp text not null,
+    emoji text not null,
+    succeeded boolean not null,
+    error text,
+    performed_at text not null
+);
+
+create index if not exists slack_actions_event_id_idx on slack_actions(event_id);
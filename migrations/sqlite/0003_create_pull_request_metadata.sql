@@ -0,0 +1,10 @@
+create table if not exists pull_request_metadata (
+    url text primary key,
+    repository text not null,
+    number integer not null,
+    title text not null,
+    author text,
+    updated_at text not null
+);
+
+create index if not exists pull_request_metadata_updated_at_idx on pull_request_metadata(updated_at);
@@ -0,0 +1,7 @@
+create table if not exists github_installations (
+    repository text primary key,
+    installation_id integer not null,
+    updated_at text not null
+);
+
+create index if not exists github_installations_installation_id_idx on github_installations(installation_id);
@@ -0,0 +1,8 @@
+create table if not exists channel_settings (
+    channel text not null,
+    event_type text not null,
+    emoji text,
+    enabled integer,
+    updated_at text not null,
+    primary key (channel, event_type)
+);
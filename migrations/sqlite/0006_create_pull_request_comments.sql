@@ -0,0 +1,8 @@
+create table if not exists pull_request_comments (
+    url text primary key,
+    comments integer not null,
+    reacted_comments integer not null default 0,
+    updated_at text not null
+);
+
+create index if not exists pull_request_comments_updated_at_idx on pull_request_comments(updated_at);
@@ -0,0 +1,2 @@
+-- The inline comments of a review are counted once, together with the review
+alter table pull_request_comments add column last_review_id integer;
@@ -0,0 +1,4 @@
+-- Deployments name the commit they deployed, which is matched against the merge commits of PRs
+alter table pull_request_metadata add column merge_commit_sha text;
+
+create index if not exists pull_request_metadata_merge_commit_sha_idx on pull_request_metadata(repository, merge_commit_sha);
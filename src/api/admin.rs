@@ -0,0 +1,229 @@
+use axum::{
+  extract::{Query, State},
+  Json,
+};
+use chrono::Duration;
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  app_state::AppState,
+  clock::Clock,
+  models::{
+    EventHistoryEntry, EventId, PrFilter, PrMetadata, PrState, PrUrl, RecordedEvent, SlackAction,
+    ToDelete, PR,
+  },
+  persistence::{event_repository::EventRepository, pr_repository::PrRepository},
+  reactions,
+  slack::models::{Channel, Timestamp},
+};
+
+use super::models::ApiError;
+
+#[derive(Deserialize, Debug)]
+pub struct PrUrlQuery {
+  pub pr_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ListQuery {
+  pub channel: Option<String>,
+  // `owner/name` of the repository
+  pub repo: Option<String>,
+  pub max_age_hours: Option<i64>,
+  pub min_age_hours: Option<i64>,
+  pub state: Option<PrState>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeleteQuery {
+  pub pr_url: String,
+  pub channel: Option<String>,
+  pub timestamp: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TrackedPr {
+  #[serde(flatten)]
+  pub pr: PR,
+  pub state: PrState,
+  pub metadata: Option<PrMetadata>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PrDetails {
+  pub url: PrUrl,
+  pub state: PrState,
+  pub metadata: Option<PrMetadata>,
+  pub messages: Vec<PR>,
+}
+
+pub async fn event_history<S: AppState>(
+  state: State<S>,
+  Query(query): Query<PrUrlQuery>,
+) -> Result<Json<Vec<EventHistoryEntry>>, ApiError> {
+  let history = state
+    .event_repository()
+    .history(PrUrl(query.pr_url))
+    .await?;
+  Ok(Json(history))
+}
+
+pub async fn list_prs<S: AppState>(
+  state: State<S>,
+  Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<TrackedPr>>, ApiError> {
+  let now = state.clock().now();
+
+  let filter = PrFilter {
+    channel: query.channel.map(Channel),
+    repository: query.repo,
+    inserted_after: query
+      .max_age_hours
+      .map(|hours| now - Duration::hours(hours)),
+    inserted_before: query
+      .min_age_hours
+      .map(|hours| now - Duration::hours(hours)),
+  };
+
+  let prs = state.pr_repository().list(filter).await?;
+
+  let urls = prs.iter().map(|pr| pr.url.clone()).collect::<Vec<_>>();
+
+  let states = state.event_repository().states(urls.clone()).await?;
+  let metadata = state.pr_repository().metadata(urls).await?;
+
+  let tracked = prs
+    .into_iter()
+    .map(|pr| TrackedPr {
+      state: states.get(&pr.url).copied().unwrap_or(PrState::Open),
+      metadata: metadata.get(&pr.url).cloned(),
+      pr,
+    })
+    .filter(|tracked| query.state.is_none_or(|state| state == tracked.state))
+    .collect();
+
+  Ok(Json(tracked))
+}
+
+pub async fn get_pr<S: AppState>(
+  state: State<S>,
+  Query(query): Query<PrUrlQuery>,
+) -> Result<Json<PrDetails>, ApiError> {
+  let url = PrUrl(query.pr_url);
+
+  let messages = state.pr_repository().get_by_url(url.clone()).await?;
+
+  if messages.is_empty() {
+    return Err(ApiError::new("Pull request is not tracked", 404));
+  }
+
+  let pr_state = state
+    .event_repository()
+    .states(vec![url.clone()])
+    .await?
+    .remove(&url)
+    .unwrap_or(PrState::Open);
+
+  let metadata = state
+    .pr_repository()
+    .metadata(vec![url.clone()])
+    .await?
+    .remove(&url);
+
+  Ok(Json(PrDetails {
+    url,
+    state: pr_state,
+    metadata,
+    messages,
+  }))
+}
+
+pub async fn delete_pr<S: AppState>(
+  state: State<S>,
+  Query(query): Query<DeleteQuery>,
+) -> Result<StatusCode, ApiError> {
+  let url = PrUrl(query.pr_url);
+  let repo = state.pr_repository();
+
+  match (query.channel, query.timestamp) {
+    (Some(channel), Some(timestamp)) => {
+      repo
+        .delete_all(ToDelete::new(
+          vec![url],
+          Channel(channel),
+          Timestamp(timestamp),
+        ))
+        .await?
+    }
+    (None, None) => {
+      repo.delete_by_url(url).await?;
+    }
+    _ => {
+      return Err(ApiError::new(
+        "channel and timestamp have to be given together",
+        400,
+      ))
+    }
+  }
+
+  Ok(StatusCode::NO_CONTENT)
+}
+
+// Re-applies every reaction the recorded events call for to all tracked messages
+pub async fn resync_pr<S: AppState>(
+  state: State<S>,
+  Query(query): Query<PrUrlQuery>,
+) -> Result<Json<Vec<SlackAction>>, ApiError> {
+  let url = PrUrl(query.pr_url);
+
+  let prs = state.pr_repository().get_by_url(url.clone()).await?;
+
+  if prs.is_empty() {
+    return Err(ApiError::new("Pull request is not tracked", 404));
+  }
+
+  let events = state.event_repository();
+
+  let pr_author = state
+    .pr_repository()
+    .metadata(vec![url.clone()])
+    .await?
+    .remove(&url)
+    .and_then(|metadata| metadata.author);
+
+  let history = events
+    .history(url.clone())
+    .await?
+    .into_iter()
+    .map(|entry| entry.event)
+    .filter(|event| {
+      state
+        .repository_rules()
+        .reacts_to_pr(&event.pr_url, &event.event_type)
+        && !state
+          .config()
+          .actors
+          .ignores(&event.actor, &event.event_type, pr_author.as_deref())
+        && state.config().reviews.reacts_to(&event.event_type)
+    })
+    .collect::<Vec<_>>();
+
+  let resync = RecordedEvent {
+    id: EventId(uuid::Uuid::new_v4()),
+    delivery_id: None,
+    pr_url: url,
+    event_type: "resync".to_string(),
+    actor: "admin".to_string(),
+    received_at: state.clock().now(),
+  };
+
+  events.record_event(resync.clone()).await?;
+
+  let mut actions = Vec::new();
+  for emoji in reactions::reactions_for_history(&history) {
+    actions.extend(reactions::add_reactions(&*state, &resync, prs.clone(), emoji).await);
+  }
+
+  Ok(Json(actions))
+}
@@ -9,22 +9,32 @@ use http_body_util::BodyExt;
 use sha2::Sha256;
 use tracing::error;
 
-use crate::{app_state::AppState, clock::Clock};
+use crate::{app_state::AppState, clock::Clock, metrics};
 
 use super::models::ApiError;
 
-pub fn hmac<'a>(secret: &[u8], message: &[u8]) -> Vec<u8> {
+pub fn hmac(secret: &[u8], message: &[u8]) -> Vec<u8> {
   let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can take key of any size");
   mac.update(message);
   let result = mac.finalize();
   result.into_bytes().to_vec()
 }
 
-pub fn verify_signature<'a>(secret: &[u8], message: &[u8], signature: &[u8]) -> bool {
+// Requests without the headers are rejected as unsigned
+fn missing_header(source: &str, message: &'static str) -> ApiError {
+  error!("{}", message);
+  metrics::SIGNATURE_FAILURES
+    .with_label_values(&[source, "missing"])
+    .inc();
+  ApiError::new(message, 401)
+}
+
+pub fn verify_signature(secret: &[u8], message: &[u8], signature: &[u8]) -> bool {
   let expected = hmac(secret, message);
   consistenttime::ct_u8_slice_eq(expected.as_slice(), signature)
 }
 
+#[tracing::instrument(name = "verify_github_signature", skip_all)]
 pub async fn authenticate_github_webhook<S: AppState>(
   State(state): State<S>,
   request: Request,
@@ -34,7 +44,7 @@ pub async fn authenticate_github_webhook<S: AppState>(
 
   let x_hub_signature = headers
     .get("x-hub-signature-256")
-    .ok_or(ApiError::new("Missing X-Hub-Signature-256 header", 401))?
+    .ok_or_else(|| missing_header("github", "Missing X-Hub-Signature-256 header"))?
     .to_str()
     .map_err(|_| ApiError::new("Invalid X-Hub-Signature-256 header", 400))?
     .strip_prefix("sha256=")
@@ -57,9 +67,9 @@ pub async fn authenticate_github_webhook<S: AppState>(
     .to_bytes();
 
   let signature = verify_signature(
-    &state.config().github.secret(),
-    &payload.to_vec().as_slice(),
-    &x_hub_signature.as_slice(),
+    state.config().github.secret(),
+    payload.to_vec().as_slice(),
+    x_hub_signature.as_slice(),
   );
 
   if signature {
@@ -69,10 +79,14 @@ pub async fn authenticate_github_webhook<S: AppState>(
     Ok(response)
   } else {
     error!("Signature mismatch");
-    return Err(ApiError::new("Invalid signature", 401));
+    metrics::SIGNATURE_FAILURES
+      .with_label_values(&["github", "mismatch"])
+      .inc();
+    Err(ApiError::new("Invalid signature", 401))
   }
 }
 
+#[tracing::instrument(name = "verify_slack_signature", skip_all)]
 pub async fn authenticate_slack_webhook<S: AppState>(
   State(state): State<S>,
   request: Request,
@@ -82,7 +96,7 @@ pub async fn authenticate_slack_webhook<S: AppState>(
 
   let x_slack_signature = headers
     .get("x-slack-signature")
-    .ok_or(ApiError::new("Missing X-Slack-Signature header", 401))?
+    .ok_or_else(|| missing_header("slack", "Missing X-Slack-Signature header"))?
     .to_str()
     .map_err(|_| ApiError::new("Invalid X-Slack-Signature header", 400))?
     .strip_prefix("v0=")
@@ -95,10 +109,7 @@ pub async fn authenticate_slack_webhook<S: AppState>(
 
   let x_slack_request_timestamp: i64 = headers
     .get("x-slack-request-timestamp")
-    .ok_or(ApiError::new(
-      "Missing X-Slack-Request-Timestamp header",
-      401,
-    ))?
+    .ok_or_else(|| missing_header("slack", "Missing X-Slack-Request-Timestamp header"))?
     .to_str()
     .map_err(|_| ApiError::new("Invalid X-Slack-Request-Timestamp header", 400))?
     .parse()
@@ -108,6 +119,9 @@ pub async fn authenticate_slack_webhook<S: AppState>(
 
   if now - x_slack_request_timestamp > 60 * 5 {
     error!("X-Slack-Request-Timestamp header is too old");
+    metrics::SIGNATURE_FAILURES
+      .with_label_values(&["slack", "expired"])
+      .inc();
     return Err(ApiError::new(
       "X-Slack-Request-Timestamp header is too old",
       401,
@@ -130,7 +144,7 @@ pub async fn authenticate_slack_webhook<S: AppState>(
   let body = format!("v0:{}:{}", x_slack_request_timestamp, message);
 
   let signature = verify_signature(
-    &state.config().slack.signing_secret(),
+    state.config().slack.signing_secret(),
     body.as_bytes(),
     x_slack_signature.as_slice(),
   );
@@ -141,7 +155,38 @@ pub async fn authenticate_slack_webhook<S: AppState>(
     Ok(response)
   } else {
     error!("Signature mismatch");
-    return Err(ApiError::new("Invalid signature", 401));
+    metrics::SIGNATURE_FAILURES
+      .with_label_values(&["slack", "mismatch"])
+      .inc();
+    Err(ApiError::new("Invalid signature", 401))
+  }
+}
+
+pub async fn authenticate_admin<S: AppState>(
+  State(state): State<S>,
+  request: Request,
+  next: Next,
+) -> Result<Response, ApiError> {
+  let token = state
+    .config()
+    .admin
+    .token()
+    .ok_or(ApiError::new("Not found", 404))?;
+
+  let bearer = request
+    .headers()
+    .get("authorization")
+    .ok_or(ApiError::new("Missing Authorization header", 401))?
+    .to_str()
+    .map_err(|_| ApiError::new("Invalid Authorization header", 400))?
+    .strip_prefix("Bearer ")
+    .ok_or(ApiError::new("Invalid Authorization header", 400))?;
+
+  if consistenttime::ct_u8_slice_eq(token, bearer.as_bytes()) {
+    Ok(next.run(request).await)
+  } else {
+    error!("Admin token mismatch");
+    Err(ApiError::new("Invalid token", 401))
   }
 }
 
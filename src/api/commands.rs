@@ -0,0 +1,273 @@
+use axum::{extract::State, Form, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  app_state::AppState,
+  channel_settings,
+  clock::Clock,
+  metrics,
+  models::ChannelSetting,
+  persistence::pr_repository::PrRepository,
+  slack::models::{Channel, Emoji, EVENT_TYPES},
+};
+
+use super::models::ApiError;
+
+const USAGE: &str = "Usage:
+`/prmoji config` - show the reactions of this channel
+`/prmoji config emoji <event> <emoji>` - use another emoji, or `default` to go back to the configured one
+`/prmoji config disable <event>` and `/prmoji config enable <event>` - turn reactions for an event off or on
+`/prmoji config reset` - forget every setting of this channel
+Events are `merged`, `closed`, `approved`, `changes_requested`, `commented`, `auto_merge_enabled`, `merge_queued`, `updated`, `conflicted` and `deployed`, or `deployed:<environment>` for the deployments to one environment.";
+
+// The form Slack posts for a slash command, only the fields we use
+#[derive(Deserialize, Debug)]
+pub struct SlashCommand {
+  pub text: String,
+  pub channel_id: String,
+}
+
+// Only the user who ran the command sees the reply
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct CommandResponse {
+  pub response_type: &'static str,
+  pub text: String,
+}
+
+impl CommandResponse {
+  fn ephemeral(text: impl Into<String>) -> Self {
+    Self {
+      response_type: "ephemeral",
+      text: text.into(),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ConfigCommand {
+  Show,
+  // None goes back to the configured emoji
+  Emoji(String, Option<String>),
+  Enable(String, bool),
+  Reset,
+}
+
+// Environment names are case insensitive on GitHub
+fn event_type(name: &str) -> Result<String, String> {
+  match name.split_once(':') {
+    Some(("deployed", environment)) if !environment.is_empty() => {
+      Ok(format!("deployed:{}", environment.to_lowercase()))
+    }
+    _ if EVENT_TYPES.contains(&name) => Ok(name.to_string()),
+    _ => Err(format!("Unknown event `{}`.\n{}", name, USAGE)),
+  }
+}
+
+fn parse(text: &str) -> Result<ConfigCommand, String> {
+  let words = text.split_whitespace().collect::<Vec<_>>();
+  match words.as_slice() {
+    ["config"] | ["config", "show"] => Ok(ConfigCommand::Show),
+    ["config", "emoji", event, "default"] => Ok(ConfigCommand::Emoji(event_type(event)?, None)),
+    ["config", "emoji", event, emoji] => Ok(ConfigCommand::Emoji(
+      event_type(event)?,
+      Some(emoji.trim_matches(':').to_string()),
+    )),
+    ["config", "disable", event] => Ok(ConfigCommand::Enable(event_type(event)?, false)),
+    ["config", "enable", event] => Ok(ConfigCommand::Enable(event_type(event)?, true)),
+    ["config", "reset"] => Ok(ConfigCommand::Reset),
+    _ => Err(USAGE.to_string()),
+  }
+}
+
+#[tracing::instrument(name = "slack_command", skip_all, fields(channel = %command.channel_id))]
+pub async fn handle_slack_command<S: AppState>(
+  state: State<S>,
+  Form(command): Form<SlashCommand>,
+) -> Result<Json<CommandResponse>, ApiError> {
+  metrics::WEBHOOKS_RECEIVED
+    .with_label_values(&["slack", "command"])
+    .inc();
+
+  let channel = Channel(command.channel_id);
+  let repo = state.pr_repository();
+
+  let command = match parse(&command.text) {
+    Ok(command) => command,
+    Err(usage) => return Ok(Json(CommandResponse::ephemeral(usage))),
+  };
+
+  let settings = repo.channel_settings(channel.clone()).await?;
+  let existing = |event_type: &str| {
+    settings
+      .iter()
+      .find(|setting| setting.event_type == event_type)
+      .cloned()
+      .unwrap_or(ChannelSetting {
+        channel: channel.clone(),
+        event_type: event_type.to_string(),
+        emoji: None,
+        enabled: None,
+        updated_at: state.clock().now(),
+      })
+  };
+
+  let setting = match command {
+    ConfigCommand::Show => {
+      let text = show(&*state, &settings, &channel);
+      return Ok(Json(CommandResponse::ephemeral(text)));
+    }
+    ConfigCommand::Reset => {
+      repo.delete_channel_settings(channel).await?;
+      return Ok(Json(CommandResponse::ephemeral(
+        "Forgot the settings of this channel, reactions follow the configuration again.",
+      )));
+    }
+    ConfigCommand::Emoji(event_type, emoji) => ChannelSetting {
+      emoji,
+      ..existing(&event_type)
+    },
+    ConfigCommand::Enable(event_type, enabled) => ChannelSetting {
+      enabled: Some(enabled),
+      ..existing(&event_type)
+    },
+  };
+
+  repo
+    .save_channel_setting(ChannelSetting {
+      updated_at: state.clock().now(),
+      ..setting
+    })
+    .await?;
+
+  let settings = repo.channel_settings(channel.clone()).await?;
+  let text = show(&*state, &settings, &channel);
+  Ok(Json(CommandResponse::ephemeral(text)))
+}
+
+// Repository rules can still pick other emojis for their pull requests
+fn show<S: AppState>(state: &S, settings: &[ChannelSetting], channel: &Channel) -> String {
+  let mut text = "Reactions in this channel:".to_string();
+  for event_type in EVENT_TYPES {
+    // Deployments are listed per configured environment
+    let emojis = match Emoji::for_event(event_type) {
+      Some(emoji) => vec![(format!("`{}`", event_type), emoji)],
+      None => {
+        let mut environments = state.config().emojis.deployed.keys().collect::<Vec<_>>();
+        environments.sort();
+        environments
+          .into_iter()
+          .map(|environment| {
+            (
+              format!("`{}` to {}", event_type, environment),
+              Emoji::Deployed(environment.clone()),
+            )
+          })
+          .collect()
+      }
+    };
+
+    for (label, emoji) in emojis {
+      let reaction = channel_settings::resolve(
+        state.config(),
+        state.repository_rules(),
+        settings,
+        channel,
+        None,
+        emoji,
+      );
+      match reaction {
+        Some(emoji) => text.push_str(&format!("\n{} :{}:", label, emoji)),
+        None => text.push_str(&format!("\n{} off", label)),
+      }
+    }
+  }
+  text
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app_state::TestState;
+
+  async fn run(state: &TestState, text: &str) -> String {
+    let command = SlashCommand {
+      text: text.to_string(),
+      channel_id: "C05UBF6AJH3".to_string(),
+    };
+    handle_slack_command(State(state.clone()), Form(command))
+      .await
+      .unwrap()
+      .0
+      .text
+  }
+
+  #[test]
+  fn parses_config_commands() {
+    assert_eq!(parse("config"), Ok(ConfigCommand::Show));
+    assert_eq!(
+      parse("config emoji approved :art:"),
+      Ok(ConfigCommand::Emoji(
+        "approved".to_string(),
+        Some("art".to_string())
+      ))
+    );
+    assert_eq!(
+      parse("config emoji merged default"),
+      Ok(ConfigCommand::Emoji("merged".to_string(), None))
+    );
+    assert_eq!(
+      parse(" config  disable commented "),
+      Ok(ConfigCommand::Enable("commented".to_string(), false))
+    );
+    assert_eq!(
+      parse("config disable deployed:Staging"),
+      Ok(ConfigCommand::Enable("deployed:staging".to_string(), false))
+    );
+    assert!(parse("config disable deployed:").is_err());
+    assert_eq!(parse("config reset"), Ok(ConfigCommand::Reset));
+    assert!(parse("config disable released")
+      .unwrap_err()
+      .starts_with("Unknown event `released`"));
+    assert_eq!(parse("help"), Err(USAGE.to_string()));
+  }
+
+  #[tokio::test]
+  async fn settings_are_saved_per_channel() {
+    let state = TestState::for_tests();
+
+    run(&state, "config emoji approved :art:").await;
+    let text = run(&state, "config disable commented").await;
+
+    assert_eq!(
+      text,
+      "Reactions in this channel:
+`merged` :shipit:
+`closed` :wastebasket:
+`approved` :art:
+`changes_requested` :construction:
+`commented` off
+`auto_merge_enabled` :hourglass_flowing_sand:
+`merge_queued` :steam_locomotive:
+`updated` :arrows_counterclockwise:
+`conflicted` :crossed_swords:
+`deployed` to production :rocket:
+`deployed` to staging :test_tube:"
+    );
+
+    let settings = state
+      .pr_repository()
+      .channel_settings(Channel("C05UBF6AJH3".to_string()))
+      .await
+      .unwrap();
+    assert_eq!(settings.len(), 2);
+
+    let text = run(&state, "config reset").await;
+    assert!(text.starts_with("Forgot the settings"));
+    assert!(state
+      .pr_repository()
+      .channel_settings(Channel("C05UBF6AJH3".to_string()))
+      .await
+      .unwrap()
+      .is_empty());
+  }
+}
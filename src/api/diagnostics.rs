@@ -0,0 +1,69 @@
+use axum::{extract::State, Json};
+use hyper::StatusCode;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{
+  app_state::AppState, config::Configuration, persistence::pr_repository::PrRepository,
+  slack::SlackClient,
+};
+
+#[derive(Serialize, Debug)]
+pub struct Readiness {
+  pub database: bool,
+  pub slack: bool,
+}
+
+impl Readiness {
+  pub fn is_ready(&self) -> bool {
+    self.database && self.slack
+  }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Version {
+  pub name: &'static str,
+  pub version: &'static str,
+  pub commit: Option<&'static str>,
+}
+
+// Liveness, the process is up and serving requests
+pub async fn healthz() -> StatusCode {
+  StatusCode::OK
+}
+
+// Readiness, the database and Slack can both be reached
+pub async fn readyz<S: AppState>(state: State<S>) -> (StatusCode, Json<Readiness>) {
+  let (database, slack) = futures::join!(
+    state.pr_repository().is_healthy(),
+    state.slack_client().auth_test()
+  );
+
+  let readiness = Readiness {
+    database,
+    slack: slack
+      .map_err(|err| warn!("Slack auth.test failed: {:?}", err))
+      .is_ok(),
+  };
+
+  let status = if readiness.is_ready() {
+    StatusCode::OK
+  } else {
+    StatusCode::SERVICE_UNAVAILABLE
+  };
+
+  (status, Json(readiness))
+}
+
+pub async fn version() -> Json<Version> {
+  Json(Version {
+    name: env!("CARGO_PKG_NAME"),
+    version: env!("CARGO_PKG_VERSION"),
+    commit: option_env!("PRMOJI_GIT_COMMIT"),
+  })
+}
+
+// The running configuration with every secret redacted
+pub async fn config<S: AppState>(state: State<S>) -> Json<Configuration> {
+  Json(state.config().clone())
+}
@@ -1,45 +1,121 @@
-use axum::{extract::State, response::IntoResponse, Json};
-use hyper::{HeaderMap, StatusCode};
-use tracing::{info, warn};
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use hyper::HeaderMap;
+use tracing::{field::Empty, info, warn, Span};
 
 use crate::{
   app_state::AppState,
+  backfill,
   clock::Clock,
-  github,
-  models::{ToDelete, ToInsert},
-  persistence::pr_repository::PrRepository,
-  slack::{
-    self,
-    models::{AddReactionRequest, Emoji},
-    SlackClient,
-  },
+  comments, conflicts, github, metrics,
+  models::{EventId, PrUrl, RecordedEvent, ToDelete, ToInsert},
+  persistence::{event_repository::EventRepository, pr_repository::PrRepository, RepositoryError},
+  reactions, reviews,
+  slack::{self, models::Emoji, queue::QueueSnapshot},
   url_extractor::extract_pr_urls,
 };
 
 use self::models::ApiError;
 
+pub mod admin;
 pub mod auth;
+pub mod commands;
+pub mod diagnostics;
 mod models;
 
+#[tracing::instrument(
+  name = "github_webhook",
+  skip_all,
+  fields(delivery_id = Empty, event_type = Empty, pr_url = Empty)
+)]
 pub async fn handle_github_webhook<S: AppState>(
   state: State<S>,
   headers: HeaderMap,
   Json(payload): Json<github::RawGitHubEvent>,
 ) -> Result<(), ApiError> {
+  let received_at = state.clock().now();
+
   let x_github_event = headers
     .get("X-GitHub-Event")
     .ok_or(ApiError::new("Missing X-GitHub-Event header", 400))?
     .to_str()
-    .map(|raw| github::EventTypeHeader::from_raw(raw))
+    .map(github::EventTypeHeader::from_raw)
     .map_err(|_| ApiError::new("Invalid X-GitHub-Event header", 400))?;
 
+  metrics::WEBHOOKS_RECEIVED
+    .with_label_values(&[
+      "github",
+      x_github_event.map_or("other", |event| event.name()),
+    ])
+    .inc();
+
   // If None we are not interested in this event
   let x_github_event = match x_github_event {
     Some(event) => event,
     None => return Ok(()),
   };
 
-  let github_event = github::GitHubEvent::from_raw(x_github_event, payload);
+  let delivery_id = headers
+    .get("X-GitHub-Delivery")
+    .and_then(|value| value.to_str().ok())
+    .map(|value| value.to_string());
+
+  if let Some(delivery_id) = &delivery_id {
+    Span::current().record("delivery_id", delivery_id.as_str());
+  }
+
+  if let Some(change) = payload.installation_change(x_github_event) {
+    info!("GitHub App installation changed: {:?}", change);
+    apply_installation_change(&*state, change, received_at).await?;
+    return Ok(());
+  }
+
+  let repository = payload
+    .repository()
+    .map(|repository| repository.to_string());
+
+  if let Some(repository) = &repository {
+    if !state.repository_rules().tracks(repository) {
+      info!("Ignoring event from excluded repository {}", repository);
+      return Ok(());
+    }
+  }
+
+  // Every event delivered to the app says which installation can read its repository
+  if let Some((installation_id, repository)) = payload.installation() {
+    state
+      .pr_repository()
+      .save_installation(installation_id, vec![repository], received_at)
+      .await?;
+  }
+
+  // Kept up to date even for events that don't change any reaction, e.g. a PR being renamed
+  let metadata = payload.metadata(received_at);
+  let pr_author = metadata
+    .as_ref()
+    .and_then(|metadata| metadata.author.clone());
+  if let Some(metadata) = metadata {
+    state.pr_repository().upsert_metadata(metadata).await?;
+  }
+
+  // Every pull_request event says whether the PR can be merged, only changes get recorded
+  if let Some((pr_url, conflicted)) = payload.merge_conflicts(x_github_event) {
+    conflicts::update(&*state, pr_url, conflicted, delivery_id.clone()).await?;
+  }
+
+  // Whether a dismissed review takes the approval back depends on the branch protection
+  let base_branch = payload.base_branch().map(|branch| branch.to_string());
+
+  // Deployments only name a commit, which is looked up among the merge commits of PRs
+  let deployed_pr = match (payload.deployed_commit(x_github_event), &repository) {
+    (Some(sha), Some(repository)) => state.pr_repository().merged_pr(repository, sha).await?,
+    _ => None,
+  };
+
+  let github_event = match deployed_pr {
+    Some(pr_url) => github::GitHubEvent::from_deployment(pr_url, payload),
+    None => github::GitHubEvent::from_raw(x_github_event, payload),
+  };
 
   // If None we are not interested in this event
   let github::GitHubEvent { pr_url, event_type } = match github_event {
@@ -47,120 +123,864 @@ pub async fn handle_github_webhook<S: AppState>(
     None => return Ok(()),
   };
 
+  Span::current()
+    .record("event_type", event_type.name())
+    .record("pr_url", pr_url.0.as_str());
+
   info!("Received {:?} for {:?}", event_type, pr_url);
 
-  let emoji = match event_type {
-    github::GitHubEventType::Closed => Emoji::Deleted,
-    github::GitHubEventType::Merged => Emoji::Merged,
-    github::GitHubEventType::Commented { commenter: _ } => Emoji::Comment,
-    github::GitHubEventType::ChangesRequested { reviewer: _ } => Emoji::ChangeRequest,
-    github::GitHubEventType::Approved { approver: _ } => Emoji::Approved,
+  metrics::GITHUB_EVENTS
+    .with_label_values(&[event_type.name()])
+    .inc();
+
+  let event = RecordedEvent {
+    id: EventId(uuid::Uuid::new_v4()),
+    delivery_id,
+    pr_url: pr_url.clone(),
+    event_type: event_type.recorded_name(),
+    actor: event_type.actor().login().to_string(),
+    received_at,
   };
 
-  let repo = state.pr_repository();
+  state.event_repository().record_event(event.clone()).await?;
 
-  let prs = repo.get_by_url(pr_url).await;
+  // Recorded for the audit log either way, the repository rules only decide about the reaction.
+  // Events that take a reaction back follow the rules of the event that added it
+  let removes = Emoji::removed_by(event_type.name());
+  let reaction_event_type = removes
+    .as_ref()
+    .map_or(event_type.name(), |emoji| emoji.event_type());
+  let reacts = repository.as_ref().is_none_or(|repository| {
+    state
+      .repository_rules()
+      .reacts_to(repository, reaction_event_type)
+  });
+  if !reacts {
+    info!("Not reacting to {} in {:?}", event_type.name(), repository);
+    return Ok(());
+  }
 
-  let slack = state.slack_client();
+  if state
+    .config()
+    .actors
+    .ignores(&event.actor, &event.event_type, pr_author.as_deref())
+  {
+    info!("Ignoring {} by {}", event.event_type, event.actor);
+    return Ok(());
+  }
 
-  let reactions = prs.into_iter().map(|pr| {
-    slack.add_reaction(AddReactionRequest {
-      channel: pr.channel,
-      name: emoji.clone(),
-      timestamp: pr.timestamp,
-    })
-  });
+  if !state.config().reviews.reacts_to(&event.event_type) {
+    return Ok(());
+  }
 
-  let results = futures::future::join_all(reactions).await;
+  if let github::GitHubEventType::ReviewDismissed { .. } = event_type {
+    reviews::review_dismissed(&*state, event, base_branch.as_deref()).await?;
+    return Ok(());
+  }
+
+  if let Some(emoji) = removes {
+    let prs = state.pr_repository().get_by_url(pr_url).await?;
+    reactions::remove_reactions(&*state, &event, prs, emoji).await;
+    return Ok(());
+  }
 
-  for result in results {
-    match result {
-      Ok(_) => info!("Successfully added reaction"),
-      Err(err) => warn!("Failed to add reaction: {:?}", err),
+  if let github::GitHubEventType::Commented { review_id, .. } = event_type {
+    comments::comment_received(&*state, event, review_id).await?;
+    return Ok(());
+  }
+
+  let emoji = match Emoji::for_event(&event.event_type) {
+    Some(emoji) => emoji,
+    None => return Ok(()),
+  };
+
+  // Pushes before the first review are business as usual
+  if emoji == Emoji::Updated {
+    let history = state
+      .event_repository()
+      .history(pr_url.clone())
+      .await?
+      .into_iter()
+      .map(|entry| entry.event)
+      .collect::<Vec<_>>();
+    if !reactions::reactions_for_history(&history).contains(&emoji) {
+      info!("{:?} was not reviewed yet", pr_url);
+      return Ok(());
     }
   }
 
+  let prs = state.pr_repository().get_by_url(pr_url).await?;
+
+  reactions::add_reactions(&*state, &event, prs, emoji).await;
+
   Ok(())
 }
 
+async fn apply_installation_change<S: AppState>(
+  state: &S,
+  change: github::InstallationChange,
+  received_at: DateTime<Utc>,
+) -> Result<(), RepositoryError> {
+  let repo = state.pr_repository();
+  match change {
+    github::InstallationChange::Added {
+      installation_id,
+      repositories,
+    } => {
+      repo
+        .save_installation(installation_id, repositories, received_at)
+        .await
+    }
+    github::InstallationChange::RepositoriesRemoved {
+      installation_id,
+      repositories,
+    } => repo
+      .delete_installation_repositories(installation_id, repositories)
+      .await
+      .map(|_| ()),
+    github::InstallationChange::Removed { installation_id } => {
+      repo.delete_installation(installation_id).await.map(|_| ())
+    }
+  }
+}
+
+#[tracing::instrument(name = "slack_webhook", skip_all)]
 pub async fn handle_slack_webhook<S: AppState>(
   state: State<S>,
   Json(payload): Json<slack::models::WebookCallback>,
 ) -> Result<Json<slack::models::Response>, ApiError> {
   match payload {
     slack::models::WebookCallback::UrlVerification { challenge, .. } => {
+      metrics::WEBHOOKS_RECEIVED
+        .with_label_values(&["slack", "url_verification"])
+        .inc();
       Ok(Json(slack::models::Response::ChallengeReply { challenge }))
     }
     slack::models::WebookCallback::EventCallback { event, .. } => {
-      match event {
-        slack::models::Event::Create(message) => {
-          let to_insert = ToInsert::new(
-            extract_pr_urls(&message.text.0),
-            message.channel,
-            message.event_ts,
-            state.clock().now(),
-          );
-
-          let repo = state.pr_repository();
-
-          info!("Extracted to_insert: {:?}", to_insert);
-          repo.insert_all(to_insert).await
-        }
-
-        slack::models::Event::Update(update) => match update {
-          slack::models::MessageUpdate::MessageChanged {
-            message,
-            previous_message,
-            channel,
-            channel_type: _,
-            event_ts,
-          } => {
-            info!("Received message update: {:?}", message);
-
-            let clock = state.clock();
-
-            let to_delete = ToDelete::new(
-              extract_pr_urls(&previous_message.text.0),
-              channel.clone(),
-              event_ts.clone(),
-            );
-
-            let to_insert = ToInsert::new(
-              extract_pr_urls(&message.text.0),
-              channel,
-              event_ts,
-              clock.now(),
-            );
-
-            info!("Extracted to_delete: {:?}", to_delete);
-            info!("Extracted to_insert: {:?}", to_insert);
-
-            let repo = state.pr_repository();
-
-            repo.update(to_insert, to_delete).await
-          }
-
-          slack::models::MessageUpdate::MessageDeleted {
-            channel,
-            channel_type: _,
-            event_ts,
-            previous_message,
-          } => {
-            let to_delete =
-              ToDelete::new(extract_pr_urls(&previous_message.text.0), channel, event_ts);
-
-            info!("Extracted to_delete: {:?}", to_delete);
-            let repo = state.pr_repository();
-            repo.delete_all(to_delete).await
-          }
-        },
-      }
+      metrics::WEBHOOKS_RECEIVED
+        .with_label_values(&["slack", event.name()])
+        .inc();
+
+      // Acknowledge right away, the event is processed in the background
+      state
+        .slack_queue()
+        .enqueue(event, state.clock().now())
+        .map_err(|err| {
+          warn!("Failed to enqueue Slack event: {:?}", err);
+          ApiError::new("Slack event queue is unavailable", 503)
+        })?;
       Ok(Json(slack::models::Response::Ok))
     }
   }
 }
 
-pub async fn debug(Json(payload): Json<serde_json::Value>) -> impl IntoResponse {
-  info!("Received: {:?}", payload);
-  StatusCode::INTERNAL_SERVER_ERROR
+pub async fn metrics<S: AppState>(state: State<S>) -> String {
+  metrics::render(&*state)
+}
+
+pub async fn slack_queue_stats<S: AppState>(state: State<S>) -> Json<QueueSnapshot> {
+  Json(state.slack_queue().snapshot())
+}
+
+// Deletes still use every URL of the previous message, so a PR stays untracked if the rules change
+fn tracked_pr_urls<S: AppState>(state: &S, text: &str) -> Vec<PrUrl> {
+  extract_pr_urls(text)
+    .into_iter()
+    .filter(|url| state.repository_rules().tracks_pr(url))
+    .collect()
+}
+
+#[tracing::instrument(
+  skip_all,
+  fields(event_type = event.name(), channel = Empty)
+)]
+pub async fn process_slack_event<S: AppState>(
+  state: &S,
+  event: slack::models::Event,
+) -> Result<(), RepositoryError> {
+  match event {
+    slack::models::Event::Create(message) => {
+      Span::current().record("channel", message.channel.0.as_str());
+      let to_insert = ToInsert::new(
+        tracked_pr_urls(state, &message.text.0),
+        message.channel.clone(),
+        message.event_ts.clone(),
+        state.clock().now(),
+      );
+      let urls = to_insert.urls.clone();
+
+      let repo = state.pr_repository();
+
+      info!("Extracted to_insert: {:?}", to_insert);
+      metrics::PR_URLS_EXTRACTED.inc_by(to_insert.urls.len() as u64);
+      repo.insert_all(to_insert).await?;
+
+      backfill::backfill(state, urls, &message.channel, &message.event_ts).await;
+      Ok(())
+    }
+
+    slack::models::Event::Update(update) => match update {
+      slack::models::MessageUpdate::MessageChanged {
+        message,
+        previous_message,
+        channel,
+        channel_type: _,
+        event_ts: _,
+      } => {
+        Span::current().record("channel", channel.0.as_str());
+        info!("Received message update: {:?}", message);
+
+        let clock = state.clock();
+
+        let to_delete = ToDelete::new(
+          extract_pr_urls(&previous_message.text.0),
+          channel.clone(),
+          previous_message.ts,
+        );
+
+        let to_insert = ToInsert::new(
+          tracked_pr_urls(state, &message.text.0),
+          channel.clone(),
+          message.ts.clone(),
+          clock.now(),
+        );
+
+        // Only URLs the edit added, the others already have their reactions
+        let added = to_insert
+          .urls
+          .iter()
+          .filter(|url| !to_delete.urls.contains(url))
+          .cloned()
+          .collect::<Vec<_>>();
+
+        info!("Extracted to_delete: {:?}", to_delete);
+        info!("Extracted to_insert: {:?}", to_insert);
+        metrics::PR_URLS_EXTRACTED.inc_by(to_insert.urls.len() as u64);
+
+        let repo = state.pr_repository();
+
+        repo.update(to_insert, to_delete).await?;
+
+        backfill::backfill(state, added, &channel, &message.ts).await;
+        Ok(())
+      }
+
+      slack::models::MessageUpdate::MessageDeleted {
+        channel,
+        channel_type: _,
+        event_ts: _,
+        deleted_ts,
+        previous_message,
+      } => {
+        Span::current().record("channel", channel.0.as_str());
+        let to_delete = ToDelete::new(
+          extract_pr_urls(&previous_message.text.0),
+          channel,
+          deleted_ts,
+        );
+
+        info!("Extracted to_delete: {:?}", to_delete);
+        let repo = state.pr_repository();
+        repo.delete_all(to_delete).await
+      }
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::extract::Query;
+
+  use super::*;
+  use crate::{
+    app_state::TestState,
+    config,
+    models::{PrFilter, SlackActionKind},
+    repository_rules::RepositoryRules,
+    test_support::{added_names, removed_names, track},
+  };
+
+  fn unavailable() -> TestState {
+    let state = TestState::for_tests();
+    state.pr_repository.set_unavailable(true);
+    state
+  }
+
+  fn with_repositories(repositories: config::Repositories) -> TestState {
+    let mut state = TestState::for_tests();
+    state.repository_rules = RepositoryRules::new(&repositories).unwrap();
+    state.config.repositories = repositories;
+    state
+  }
+
+  fn load<T: serde::de::DeserializeOwned>(file_location: &str) -> T {
+    let json = std::fs::read_to_string(file_location).unwrap();
+    serde_json::from_str(&json).unwrap()
+  }
+
+  // Delivers a GitHub webhook, fixtures can be changed as JSON before they are parsed
+  async fn webhook(state: &TestState, event_type: &str, payload: serde_json::Value) {
+    let mut headers = HeaderMap::new();
+    headers.insert("X-GitHub-Event", event_type.parse().unwrap());
+    let payload = serde_json::from_value(payload).unwrap();
+    handle_github_webhook(State(state.clone()), headers, Json(payload))
+      .await
+      .unwrap();
+  }
+
+  fn slack_event(file_location: &str) -> slack::models::Event {
+    match load(file_location) {
+      slack::models::WebookCallback::EventCallback { event } => event,
+      _ => panic!("Expected an event callback"),
+    }
+  }
+
+  #[tokio::test]
+  async fn github_webhook_fails_when_database_is_down() {
+    let mut headers = HeaderMap::new();
+    headers.insert("X-GitHub-Event", "pull_request".parse().unwrap());
+
+    let result = handle_github_webhook(
+      State(unavailable()),
+      headers,
+      Json(load("test_resources/github/merge.json")),
+    )
+    .await;
+
+    assert_eq!(
+      result,
+      Err(ApiError::new("Failed to access the database", 500))
+    );
+  }
+
+  #[tokio::test]
+  async fn github_webhook_keeps_metadata_up_to_date() {
+    let state = TestState::for_tests();
+    let url = crate::models::PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/1");
+    let mut headers = HeaderMap::new();
+    headers.insert("X-GitHub-Event", "issue_comment".parse().unwrap());
+
+    handle_github_webhook(
+      State(state.clone()),
+      headers,
+      Json(load("test_resources/github/comment.json")),
+    )
+    .await
+    .unwrap();
+
+    let metadata = state
+      .pr_repository()
+      .metadata(vec![url.clone()])
+      .await
+      .unwrap();
+    let metadata = metadata.get(&url).unwrap();
+    assert_eq!(metadata.repository, "NavidJalali/prmoji-testing");
+    assert_eq!(metadata.number, 1);
+    assert_eq!(metadata.title, "Update README.md title!!!!");
+    assert_eq!(metadata.author.as_deref(), Some("NavidJalali"));
+    assert_eq!(metadata.updated_at, state.clock().now());
+  }
+
+  #[tokio::test]
+  async fn github_webhook_keeps_track_of_installations() {
+    let state = TestState::for_tests();
+    let deliver = |header: &'static str, file_location: &'static str| {
+      let state = state.clone();
+      async move {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-GitHub-Event", header.parse().unwrap());
+        handle_github_webhook(State(state), headers, Json(load(file_location)))
+          .await
+          .unwrap();
+      }
+    };
+    let installation = |repository: &'static str| {
+      let state = state.clone();
+      async move {
+        state
+          .pr_repository()
+          .installation(repository)
+          .await
+          .unwrap()
+      }
+    };
+
+    deliver(
+      "installation",
+      "test_resources/github/installation-created.json",
+    )
+    .await;
+    assert_eq!(installation("NavidJalali/prmoji").await, Some(45678901));
+
+    deliver(
+      "installation_repositories",
+      "test_resources/github/installation-repositories-removed.json",
+    )
+    .await;
+    assert_eq!(installation("NavidJalali/prmoji").await, None);
+    assert_eq!(
+      installation("NavidJalali/prmoji-testing").await,
+      Some(45678901)
+    );
+
+    deliver(
+      "installation",
+      "test_resources/github/installation-deleted.json",
+    )
+    .await;
+    assert_eq!(installation("NavidJalali/prmoji-testing").await, None);
+  }
+
+  #[tokio::test]
+  async fn github_webhook_ignores_excluded_repositories() {
+    let state = with_repositories(config::Repositories {
+      exclude: vec!["navidjalali/prmoji-*".to_string()],
+      ..Default::default()
+    });
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/1");
+    let mut headers = HeaderMap::new();
+    headers.insert("X-GitHub-Event", "issue_comment".parse().unwrap());
+
+    handle_github_webhook(
+      State(state.clone()),
+      headers,
+      Json(load("test_resources/github/comment.json")),
+    )
+    .await
+    .unwrap();
+
+    let metadata = state.pr_repository().metadata(vec![url.clone()]).await;
+    assert!(metadata.unwrap().is_empty());
+    assert!(state
+      .event_repository()
+      .history(url)
+      .await
+      .unwrap()
+      .is_empty());
+  }
+
+  #[tokio::test]
+  async fn repository_rules_decide_which_events_get_a_reaction() {
+    let state = with_repositories(config::Repositories {
+      rules: vec![config::RepositoryRule {
+        pattern: "NavidJalali/*".to_string(),
+        events: Some(vec!["merged".to_string(), "closed".to_string()]),
+        emojis: Default::default(),
+      }],
+      ..Default::default()
+    });
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/1");
+    track(&state, &url).await;
+
+    webhook(
+      &state,
+      "issue_comment",
+      load("test_resources/github/comment.json"),
+    )
+    .await;
+
+    let history = state.event_repository().history(url).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(history[0].actions.is_empty());
+    assert!(added_names(&state).is_empty());
+  }
+
+  #[tokio::test]
+  async fn a_review_with_inline_comments_counts_as_one_comment() {
+    let mut state = TestState::for_tests();
+    state.config.comments.debounce_seconds = 0;
+    state.config.comments.counts = true;
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/2");
+    track(&state, &url).await;
+
+    for _ in 0..2 {
+      webhook(
+        &state,
+        "pull_request_review_comment",
+        load("test_resources/github/review-comment.json"),
+      )
+      .await;
+    }
+    webhook(
+      &state,
+      "pull_request_review",
+      load("test_resources/github/review-commented.json"),
+    )
+    .await;
+
+    assert_eq!(added_names(&state), vec!["speech_balloon", "one"]);
+    assert_eq!(
+      state
+        .pr_repository()
+        .comments(url)
+        .await
+        .unwrap()
+        .unwrap()
+        .comments,
+      1
+    );
+  }
+
+  #[tokio::test]
+  async fn deployments_of_merged_prs_get_a_reaction_per_environment() {
+    let state = TestState::for_tests();
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/1");
+    track(&state, &url).await;
+    let deploy = |environment: &str| {
+      let mut payload: serde_json::Value = load("test_resources/github/deployment-status.json");
+      payload["deployment"]["environment"] = environment.into();
+      webhook(&state, "deployment_status", payload)
+    };
+
+    // Nothing is known about the commit before the PR is merged
+    deploy("production").await;
+    assert!(added_names(&state).is_empty());
+
+    webhook(
+      &state,
+      "pull_request",
+      load("test_resources/github/merge.json"),
+    )
+    .await;
+    deploy("staging").await;
+    deploy("preview-1").await;
+    deploy("Production").await;
+
+    assert_eq!(added_names(&state), vec!["shipit", "test_tube", "rocket"]);
+
+    let history = state.event_repository().history(url).await.unwrap();
+    let event_types = history
+      .iter()
+      .map(|entry| entry.event.event_type.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      event_types,
+      vec![
+        "merged",
+        "deployed:staging",
+        "deployed:preview-1",
+        "deployed:Production"
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn leaving_the_merge_queue_takes_the_reaction_back() {
+    let state = TestState::for_tests();
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/1");
+    track(&state, &url).await;
+
+    webhook(
+      &state,
+      "merge_group",
+      load("test_resources/github/merge-group-checks-requested.json"),
+    )
+    .await;
+    webhook(
+      &state,
+      "merge_group",
+      load("test_resources/github/merge-group-destroyed.json"),
+    )
+    .await;
+
+    assert_eq!(added_names(&state), vec!["steam_locomotive"]);
+    assert_eq!(removed_names(&state), vec!["steam_locomotive"]);
+
+    let history = state.event_repository().history(url).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].actions[0].kind, SlackActionKind::RemoveReaction);
+  }
+
+  #[tokio::test]
+  async fn reacts_while_a_pr_has_merge_conflicts() {
+    let state = TestState::for_tests();
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/1");
+    track(&state, &url).await;
+
+    // Still computing, then conflicted twice, then resolved
+    for mergeable_state in ["unknown", "dirty", "dirty", "clean"] {
+      let mut payload: serde_json::Value = load("test_resources/github/synchronize.json");
+      payload["pull_request"]["mergeable_state"] = mergeable_state.into();
+      webhook(&state, "pull_request", payload).await;
+    }
+
+    assert_eq!(added_names(&state), vec!["crossed_swords"]);
+    assert_eq!(removed_names(&state), vec!["crossed_swords"]);
+
+    let history = state.event_repository().history(url).await.unwrap();
+    let event_types = history
+      .iter()
+      .map(|entry| entry.event.event_type.as_str())
+      .filter(|event_type| event_type.starts_with("conflict"))
+      .collect::<Vec<_>>();
+    assert_eq!(event_types, vec!["conflicted", "conflict_resolved"]);
+  }
+
+  #[tokio::test]
+  async fn pushes_after_a_review_get_a_reaction() {
+    let state = dismissing_approvals();
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/2");
+    track(&state, &url).await;
+    let send = |event_type: &'static str, file_location: &str| {
+      let mut payload: serde_json::Value = load(file_location);
+      payload["pull_request"]["_links"]["html"]["href"] = url.0.clone().into();
+      payload["pull_request"]["mergeable_state"] = "clean".into();
+      webhook(&state, event_type, payload)
+    };
+
+    // Nobody reviewed it yet
+    send("pull_request", "test_resources/github/synchronize.json").await;
+    assert!(added_names(&state).is_empty());
+
+    send("pull_request_review", "test_resources/github/approved.json").await;
+    send("pull_request", "test_resources/github/synchronize.json").await;
+    assert_eq!(
+      added_names(&state),
+      vec!["white_check_mark", "arrows_counterclockwise"]
+    );
+
+    send(
+      "pull_request_review",
+      "test_resources/github/review-dismissed.json",
+    )
+    .await;
+    assert_eq!(removed_names(&state), vec!["white_check_mark"]);
+  }
+
+  // Sends a review of PR 2 in a repository whose pushes dismiss approvals
+  async fn review(state: &TestState, file_location: &str, reviewer: &str) {
+    let mut payload: serde_json::Value = load(file_location);
+    payload["review"]["user"]["login"] = reviewer.into();
+    payload["sender"]["login"] = reviewer.into();
+    webhook(state, "pull_request_review", payload).await;
+  }
+
+  fn dismissing_approvals() -> TestState {
+    let mut state = TestState::for_tests();
+    state.config.reviews.clear_dismissed_approvals = true;
+    state
+      .github_client
+      .set_dismisses_stale_reviews("NavidJalali/prmoji-testing");
+    state
+  }
+
+  #[tokio::test]
+  async fn approvals_stay_until_the_last_one_is_dismissed() {
+    let state = dismissing_approvals();
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/2");
+    track(&state, &url).await;
+
+    review(&state, "test_resources/github/approved.json", "rhalm").await;
+    review(&state, "test_resources/github/approved.json", "NavidJalali").await;
+    review(
+      &state,
+      "test_resources/github/review-dismissed.json",
+      "rhalm",
+    )
+    .await;
+    assert!(removed_names(&state).is_empty());
+
+    review(
+      &state,
+      "test_resources/github/review-dismissed.json",
+      "NavidJalali",
+    )
+    .await;
+    assert_eq!(removed_names(&state), vec!["white_check_mark"]);
+
+    let history = state.event_repository().history(url).await.unwrap();
+    let actors = history
+      .iter()
+      .filter(|entry| entry.event.event_type == "review_dismissed")
+      .map(|entry| entry.event.actor.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(actors, vec!["rhalm", "NavidJalali"]);
+  }
+
+  #[tokio::test]
+  async fn dismissed_change_requests_keep_the_approval() {
+    let state = dismissing_approvals();
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/2");
+    track(&state, &url).await;
+
+    review(&state, "test_resources/github/approved.json", "rhalm").await;
+    review(
+      &state,
+      "test_resources/github/request-changes.json",
+      "NavidJalali",
+    )
+    .await;
+    review(
+      &state,
+      "test_resources/github/review-dismissed.json",
+      "NavidJalali",
+    )
+    .await;
+
+    assert!(removed_names(&state).is_empty());
+  }
+
+  #[tokio::test]
+  async fn approvals_stay_unless_pushes_dismiss_them() {
+    let mut state = TestState::for_tests();
+    state.config.reviews.clear_dismissed_approvals = true;
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/2");
+    track(&state, &url).await;
+
+    review(&state, "test_resources/github/approved.json", "rhalm").await;
+    review(
+      &state,
+      "test_resources/github/review-dismissed.json",
+      "rhalm",
+    )
+    .await;
+
+    assert!(removed_names(&state).is_empty());
+  }
+
+  #[tokio::test]
+  async fn dismissed_reviews_keep_the_approval_by_default() {
+    let state = TestState::for_tests();
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/2");
+    track(&state, &url).await;
+
+    webhook(
+      &state,
+      "pull_request_review",
+      load("test_resources/github/review-dismissed.json"),
+    )
+    .await;
+
+    assert!(removed_names(&state).is_empty());
+    let history = state.event_repository().history(url).await.unwrap();
+    assert_eq!(history[0].event.event_type, "review_dismissed");
+  }
+
+  #[tokio::test]
+  async fn ignored_actors_get_no_reaction() {
+    let mut state = TestState::for_tests();
+    state.config.comments.debounce_seconds = 0;
+    state.config.actors = config::Actors {
+      ignore_bots: true,
+      ignore_pr_author: true,
+      ..Default::default()
+    };
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/1");
+    track(&state, &url).await;
+    let comment = |login: &str| {
+      let mut payload: serde_json::Value = load("test_resources/github/comment.json");
+      payload["comment"]["user"]["login"] = login.into();
+      webhook(&state, "issue_comment", payload)
+    };
+
+    // The author of the PR answering a review, and dependabot
+    comment("NavidJalali").await;
+    comment("dependabot[bot]").await;
+    assert!(added_names(&state).is_empty());
+    assert_eq!(
+      state.event_repository().history(url).await.unwrap().len(),
+      2
+    );
+
+    comment("rhalm").await;
+    assert_eq!(added_names(&state).len(), 1);
+  }
+
+  #[tokio::test]
+  async fn bots_still_get_reactions_for_merges_and_deployments() {
+    let mut state = TestState::for_tests();
+    state.config.actors.ignore_bots = true;
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/1");
+    track(&state, &url).await;
+
+    let mut merge: serde_json::Value = load("test_resources/github/merge.json");
+    merge["sender"]["login"] = "mergify[bot]".into();
+    webhook(&state, "pull_request", merge).await;
+    let mut deployment: serde_json::Value = load("test_resources/github/deployment-status.json");
+    deployment["sender"]["login"] = "github-actions[bot]".into();
+    webhook(&state, "deployment_status", deployment).await;
+
+    assert_eq!(added_names(&state), vec!["shipit", "rocket"]);
+  }
+
+  #[tokio::test]
+  async fn slack_messages_only_track_included_repositories() {
+    let state = with_repositories(config::Repositories {
+      include: vec!["NavidJalali/*".to_string()],
+      ..Default::default()
+    });
+
+    let create = slack_event("test_resources/slack/create-with-prs.json");
+    process_slack_event(&state, create).await.unwrap();
+
+    assert_eq!(tracked(&state).await, vec![]);
+  }
+
+  #[tokio::test]
+  async fn slack_event_fails_when_database_is_down() {
+    let event = slack_event("test_resources/slack/create.json");
+
+    let result = process_slack_event(&unavailable(), event).await;
+
+    assert!(matches!(result, Err(RepositoryError::Database(_))));
+  }
+
+  #[tokio::test]
+  async fn admin_listing_fails_when_database_is_down() {
+    let result = admin::list_prs(
+      State(unavailable()),
+      Query(admin::ListQuery {
+        channel: None,
+        repo: None,
+        max_age_hours: None,
+        min_age_hours: None,
+        state: None,
+      }),
+    )
+    .await;
+
+    assert_eq!(
+      result.err(),
+      Some(ApiError::new("Failed to access the database", 500))
+    );
+  }
+
+  async fn tracked(state: &TestState) -> Vec<(String, String)> {
+    let mut tracked = state
+      .pr_repository()
+      .list(PrFilter::default())
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|pr| (pr.timestamp.0, pr.url.0))
+      .collect::<Vec<_>>();
+    tracked.sort();
+    tracked
+  }
+
+  // Edits and deletes arrive with their own `event_ts`, rows have to stay keyed on the original message
+  #[tokio::test]
+  async fn edits_and_deletes_track_the_original_message() {
+    let state = TestState::for_tests();
+    let ts = "1696367451.886309".to_string();
+    let pr = |n: i32| format!("https://github.com/fancy-org/cool-repo/pull/{n}");
+
+    let create = slack_event("test_resources/slack/create-with-prs.json");
+    process_slack_event(&state, create).await.unwrap();
+    assert_eq!(
+      tracked(&state).await,
+      vec![(ts.clone(), pr(267)), (ts.clone(), pr(268))]
+    );
+
+    let edit = slack_event("test_resources/slack/edit-with-prs.json");
+    process_slack_event(&state, edit).await.unwrap();
+    assert_eq!(
+      tracked(&state).await,
+      vec![(ts.clone(), pr(268)), (ts.clone(), pr(269))]
+    );
+
+    let delete = slack_event("test_resources/slack/delete-with-prs.json");
+    process_slack_event(&state, delete).await.unwrap();
+    assert_eq!(tracked(&state).await, vec![]);
+  }
 }
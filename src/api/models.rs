@@ -1,7 +1,10 @@
 use axum::response::{IntoResponse, Response};
 use hyper::StatusCode;
+use tracing::error;
 
-#[derive(serde::Serialize, Clone, Copy)]
+use crate::persistence::RepositoryError;
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ApiError {
   pub message: &'static str,
   pub status_code: u16,
@@ -16,6 +19,13 @@ impl ApiError {
   }
 }
 
+impl From<RepositoryError> for ApiError {
+  fn from(error: RepositoryError) -> Self {
+    error!("Repository error: {}", error);
+    ApiError::new("Failed to access the database", 500)
+  }
+}
+
 impl IntoResponse for ApiError {
   fn into_response(self) -> axum::response::Response {
     let status_code = StatusCode::from_u16(self.status_code).unwrap();
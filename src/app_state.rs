@@ -1,44 +1,110 @@
 use crate::clock;
+use crate::comments::CommentDebouncer;
 use crate::config::Configuration;
-use crate::persistence::pr_repository;
+use crate::github;
+use crate::persistence::{
+  event_repository, in_memory_event_repository, in_memory_pr_repository, pr_repository,
+  sqlite_event_repository, sqlite_pr_repository,
+};
+use crate::repository_rules::RepositoryRules;
 use crate::slack;
 
 pub trait AppState: Clone + Send + Sync + 'static {
   type PrRepo: pr_repository::PrRepository + Sync + Send;
+  type EventRepo: event_repository::EventRepository + Sync + Send;
   type SlackClient: slack::SlackClient + Sync + Send;
   type Clock: clock::Clock + Sync + Send;
+  type GitHubClient: github::client::GitHubClient + Sync + Send;
 
   fn pr_repository(&self) -> &Self::PrRepo;
+  fn event_repository(&self) -> &Self::EventRepo;
   fn clock(&self) -> &Self::Clock;
   fn slack_client(&self) -> &Self::SlackClient;
+  // Only available if GitHub credentials are configured
+  fn github_client(&self) -> Option<&Self::GitHubClient>;
   fn config(&self) -> &Configuration;
+  fn repository_rules(&self) -> &RepositoryRules;
+  fn slack_queue(&self) -> &slack::queue::SlackEventQueue;
+  fn comment_debouncer(&self) -> &CommentDebouncer;
 }
 
 #[derive(Clone)]
-pub struct LiveState {
+pub struct LiveState<PrRepo, EventRepo> {
   pub clock: clock::LiveClock,
   pub slack_client: slack::LiveSlackClient,
+  pub github_client: Option<github::client::LiveGitHubClient>,
   pub config: Configuration,
-  pub pr_repository: pr_repository::LivePrRepository,
+  pub repository_rules: RepositoryRules,
+  pub pr_repository: PrRepo,
+  pub event_repository: EventRepo,
+  pub slack_queue: slack::queue::SlackEventQueue,
+  pub comment_debouncer: CommentDebouncer,
 }
 
-impl LiveState {
-  pub async fn new(config: &Configuration) -> Self {
+pub type PostgresState =
+  LiveState<pr_repository::LivePrRepository, event_repository::LiveEventRepository>;
+
+pub type SqliteState = LiveState<
+  sqlite_pr_repository::SqlitePrRepository,
+  sqlite_event_repository::SqliteEventRepository,
+>;
+
+impl<PrRepo, EventRepo> LiveState<PrRepo, EventRepo> {
+  fn with_repositories(
+    config: &Configuration,
+    pr_repository: PrRepo,
+    event_repository: EventRepo,
+  ) -> Self {
     Self {
       clock: clock::LiveClock,
-      slack_client: slack::LiveSlackClient::new(&config),
+      slack_client: slack::LiveSlackClient::new(config),
+      github_client: config.github.auth.as_ref().map(|auth| {
+        github::client::LiveGitHubClient::new(&config.github, auth)
+          .expect("Failed to set up the GitHub client")
+      }),
       config: config.clone(),
-      pr_repository: pr_repository::LivePrRepository::new(&config.database).await,
+      repository_rules: RepositoryRules::new(&config.repositories)
+        .expect("Invalid repository rules"),
+      pr_repository,
+      event_repository,
+      slack_queue: slack::queue::SlackEventQueue::new(config.queue.capacity),
+      comment_debouncer: CommentDebouncer::new(),
     }
   }
 }
 
-impl AppState for LiveState {
-  type PrRepo = pr_repository::LivePrRepository;
+impl PostgresState {
+  pub async fn new(config: &Configuration) -> Self {
+    let pr_repository = pr_repository::LivePrRepository::new(&config.database).await;
+    let event_repository = event_repository::LiveEventRepository::new(pr_repository.pool.clone());
+    Self::with_repositories(config, pr_repository, event_repository)
+  }
+}
+
+impl SqliteState {
+  pub async fn new(config: &Configuration) -> Self {
+    let pr_repository = sqlite_pr_repository::SqlitePrRepository::new(&config.database).await;
+    let event_repository =
+      sqlite_event_repository::SqliteEventRepository::new(pr_repository.pool.clone());
+    Self::with_repositories(config, pr_repository, event_repository)
+  }
+}
+
+impl<PrRepo, EventRepo> AppState for LiveState<PrRepo, EventRepo>
+where
+  PrRepo: pr_repository::PrRepository + Clone + Send + Sync + 'static,
+  EventRepo: event_repository::EventRepository + Clone + Send + Sync + 'static,
+{
+  type PrRepo = PrRepo;
   fn pr_repository(&self) -> &Self::PrRepo {
     &self.pr_repository
   }
 
+  type EventRepo = EventRepo;
+  fn event_repository(&self) -> &Self::EventRepo {
+    &self.event_repository
+  }
+
   type Clock = clock::LiveClock;
   fn clock(&self) -> &Self::Clock {
     &self.clock
@@ -49,7 +115,115 @@ impl AppState for LiveState {
     &self.slack_client
   }
 
+  type GitHubClient = github::client::LiveGitHubClient;
+  fn github_client(&self) -> Option<&Self::GitHubClient> {
+    self.github_client.as_ref()
+  }
+
   fn config(&self) -> &Configuration {
     &self.config
   }
+
+  fn repository_rules(&self) -> &RepositoryRules {
+    &self.repository_rules
+  }
+
+  fn slack_queue(&self) -> &slack::queue::SlackEventQueue {
+    &self.slack_queue
+  }
+
+  fn comment_debouncer(&self) -> &CommentDebouncer {
+    &self.comment_debouncer
+  }
+}
+
+// Keeps everything in memory and never talks to Slack
+#[derive(Clone)]
+pub struct InMemoryState<Clock> {
+  pub clock: Clock,
+  pub slack_client: slack::in_memory::InMemorySlackClient,
+  pub github_client: github::in_memory::InMemoryGitHubClient,
+  pub config: Configuration,
+  pub repository_rules: RepositoryRules,
+  pub pr_repository: in_memory_pr_repository::InMemoryPrRepository,
+  pub event_repository: in_memory_event_repository::InMemoryEventRepository,
+  pub slack_queue: slack::queue::SlackEventQueue,
+  pub comment_debouncer: CommentDebouncer,
+}
+
+// `prmoji --demo`, reactions are only logged
+pub type DemoState = InMemoryState<clock::LiveClock>;
+
+#[cfg(test)]
+pub type TestState = InMemoryState<clock::FrozenClock>;
+
+impl<Clock> InMemoryState<Clock> {
+  pub fn new(config: &Configuration, clock: Clock) -> Self {
+    Self {
+      clock,
+      slack_client: slack::in_memory::InMemorySlackClient::new(),
+      github_client: github::in_memory::InMemoryGitHubClient::new(),
+      config: config.clone(),
+      repository_rules: RepositoryRules::new(&config.repositories)
+        .expect("Invalid repository rules"),
+      pr_repository: in_memory_pr_repository::InMemoryPrRepository::new(),
+      event_repository: in_memory_event_repository::InMemoryEventRepository::new(),
+      slack_queue: slack::queue::SlackEventQueue::new(config.queue.capacity),
+      comment_debouncer: CommentDebouncer::new(),
+    }
+  }
+}
+
+#[cfg(test)]
+impl TestState {
+  pub fn for_tests() -> Self {
+    let now = "2023-10-03T20:20:51Z".parse().unwrap();
+    Self::new(&Configuration::for_tests(), clock::FrozenClock::new(now))
+  }
+}
+
+impl<Clock> AppState for InMemoryState<Clock>
+where
+  Clock: clock::Clock + Clone + Send + Sync + 'static,
+{
+  type PrRepo = in_memory_pr_repository::InMemoryPrRepository;
+  fn pr_repository(&self) -> &Self::PrRepo {
+    &self.pr_repository
+  }
+
+  type EventRepo = in_memory_event_repository::InMemoryEventRepository;
+  fn event_repository(&self) -> &Self::EventRepo {
+    &self.event_repository
+  }
+
+  type Clock = Clock;
+  fn clock(&self) -> &Self::Clock {
+    &self.clock
+  }
+
+  type SlackClient = slack::in_memory::InMemorySlackClient;
+  fn slack_client(&self) -> &Self::SlackClient {
+    &self.slack_client
+  }
+
+  type GitHubClient = github::in_memory::InMemoryGitHubClient;
+  fn github_client(&self) -> Option<&Self::GitHubClient> {
+    Some(&self.github_client)
+  }
+
+  fn config(&self) -> &Configuration {
+    &self.config
+  }
+
+  fn repository_rules(&self) -> &RepositoryRules {
+    &self.repository_rules
+  }
+
+  fn slack_queue(&self) -> &slack::queue::SlackEventQueue {
+    &self.slack_queue
+  }
+
+  fn comment_debouncer(&self) -> &CommentDebouncer {
+    &self.comment_debouncer
+  }
 }
@@ -0,0 +1,315 @@
+use tracing::{info, warn};
+
+use crate::{
+  app_state::AppState,
+  clock::Clock,
+  conflicts,
+  github::client::{pull_request_path, GitHubClient, GitHubClientError},
+  metrics,
+  models::{EventId, PrState, PrUrl, RecordedEvent},
+  persistence::{event_repository::EventRepository, pr_repository::PrRepository, RepositoryError},
+  reactions,
+  slack::models::{Channel, Emoji, Timestamp},
+};
+
+#[derive(Debug)]
+pub enum BackfillError {
+  GitHub(GitHubClientError),
+  Repository(RepositoryError),
+}
+
+impl From<GitHubClientError> for BackfillError {
+  fn from(err: GitHubClientError) -> Self {
+    BackfillError::GitHub(err)
+  }
+}
+
+impl From<RepositoryError> for BackfillError {
+  fn from(err: RepositoryError) -> Self {
+    BackfillError::Repository(err)
+  }
+}
+
+/*
+ * A PR that was approved or merged before it was posted would get no reaction until the next webhook,
+ * so its current state is looked up on GitHub right away. Does nothing without GitHub credentials.
+ */
+pub async fn backfill<S: AppState>(
+  state: &S,
+  urls: Vec<PrUrl>,
+  channel: &Channel,
+  timestamp: &Timestamp,
+) {
+  let github = match state.github_client() {
+    Some(github) => github,
+    None => return,
+  };
+
+  for url in urls {
+    let outcome = match backfill_pr(state, github, url, channel, timestamp).await {
+      Ok(true) => "reacted",
+      Ok(false) => "nothing_to_do",
+      Err(BackfillError::GitHub(err)) => {
+        warn!(
+          "Failed to look up the pull request on GitHub: {}",
+          err.code()
+        );
+        "failed"
+      }
+      Err(BackfillError::Repository(err)) => {
+        warn!("Failed to backfill reactions: {}", err);
+        "failed"
+      }
+    };
+    metrics::BACKFILLS.with_label_values(&[outcome]).inc();
+  }
+}
+
+// The GitHub App installation that can read the repository of the PR, if we heard of one
+pub async fn installation_of<S: AppState>(
+  state: &S,
+  url: &PrUrl,
+) -> Result<Option<u64>, RepositoryError> {
+  match pull_request_path(url) {
+    Some((owner, name, _)) => {
+      state
+        .pr_repository()
+        .installation(&format!("{owner}/{name}"))
+        .await
+    }
+    None => Ok(None),
+  }
+}
+
+// Whether any reaction was added
+#[tracing::instrument(name = "backfill", skip_all, fields(pr_url = %url.0))]
+async fn backfill_pr<S: AppState, G: GitHubClient>(
+  state: &S,
+  github: &G,
+  url: PrUrl,
+  channel: &Channel,
+  timestamp: &Timestamp,
+) -> Result<bool, BackfillError> {
+  let installation_id = installation_of(state, &url).await?;
+  let status = github.pull_request(&url, installation_id).await?;
+
+  state
+    .pr_repository()
+    .upsert_metadata(status.metadata(state.clock().now()))
+    .await?;
+
+  if let (PrState::Open, Some(conflicted)) = (status.state, status.conflicted) {
+    conflicts::update(state, url.clone(), conflicted, None).await?;
+  }
+
+  let events = status
+    .events()
+    .into_iter()
+    .filter(|(event_type, actor)| {
+      state
+        .repository_rules()
+        .reacts_to(&status.repository, event_type)
+        && !state
+          .config()
+          .actors
+          .ignores(actor, event_type, status.author.as_deref())
+    })
+    .collect::<Vec<_>>();
+  if events.is_empty() {
+    return Ok(false);
+  }
+
+  let prs = state
+    .pr_repository()
+    .get_by_url(url.clone())
+    .await?
+    .into_iter()
+    .filter(|pr| pr.channel == *channel && pr.timestamp == *timestamp)
+    .collect::<Vec<_>>();
+
+  if prs.is_empty() {
+    return Ok(false);
+  }
+
+  let history = state.event_repository().history(url.clone()).await?;
+
+  for (event_type, actor) in events {
+    let emoji = match Emoji::for_event(event_type) {
+      Some(emoji) => emoji,
+      None => continue,
+    };
+
+    // Reactions are attached to the webhook event if we received one, so the audit log isn't duplicated
+    let recorded = history
+      .iter()
+      .rev()
+      .map(|entry| &entry.event)
+      .find(|event| event.event_type == event_type)
+      .cloned();
+
+    let event = match recorded {
+      Some(event) => event,
+      None => {
+        let event = RecordedEvent {
+          id: EventId(uuid::Uuid::new_v4()),
+          delivery_id: None,
+          pr_url: url.clone(),
+          event_type: event_type.to_string(),
+          actor,
+          received_at: state.clock().now(),
+        };
+        state.event_repository().record_event(event.clone()).await?;
+        event
+      }
+    };
+
+    info!("Backfilling {} for {:?}", event_type, url);
+    reactions::add_reactions(state, &event, prs.clone(), emoji).await;
+  }
+
+  Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    api::process_slack_event,
+    app_state::TestState,
+    github::client::{PullRequestStatus, Review, ReviewState},
+    models::PrState,
+    slack::models::{AddReactionRequest, Event, WebookCallback},
+  };
+
+  fn pr(n: u32) -> PrUrl {
+    PrUrl(format!("https://github.com/fancy-org/cool-repo/pull/{n}"))
+  }
+
+  fn status(n: u32, state: PrState, reviews: Vec<ReviewState>) -> PullRequestStatus {
+    PullRequestStatus {
+      url: pr(n),
+      repository: "fancy-org/cool-repo".to_string(),
+      number: n,
+      title: "Update README.md".to_string(),
+      author: Some("NavidJalali".to_string()),
+      state,
+      merged_by: Some("NavidJalali".to_string()),
+      merge_commit_sha: None,
+      conflicted: None,
+      commented: false,
+      reviews: reviews
+        .into_iter()
+        .map(|state| Review {
+          state,
+          reviewer: Some("rhalm".to_string()),
+        })
+        .collect(),
+    }
+  }
+
+  fn slack_event(file_location: &str) -> Event {
+    let json = std::fs::read_to_string(file_location).unwrap();
+    match serde_json::from_str(&json).unwrap() {
+      WebookCallback::EventCallback { event } => event,
+      other => panic!("Expected an event callback, got {:?}", other),
+    }
+  }
+
+  fn reaction(emoji: Emoji) -> AddReactionRequest {
+    AddReactionRequest {
+      channel: Channel("C05UBF6AJH3".to_string()),
+      name: crate::config::Configuration::for_tests()
+        .emojis
+        .get(emoji)
+        .unwrap(),
+      timestamp: Timestamp("1696367451.886309".to_string()),
+    }
+  }
+
+  #[tokio::test]
+  async fn reacts_to_what_happened_before_a_pr_was_posted() {
+    let state = TestState::for_tests();
+    state
+      .github_client
+      .set_pull_request(status(267, PrState::Merged, vec![ReviewState::Approved]));
+    state
+      .github_client
+      .set_pull_request(status(268, PrState::Open, vec![ReviewState::Dismissed]));
+
+    let create = slack_event("test_resources/slack/create-with-prs.json");
+    process_slack_event(&state, create).await.unwrap();
+
+    assert_eq!(
+      state.slack_client.reactions(),
+      vec![reaction(Emoji::Approved), reaction(Emoji::Merged)]
+    );
+
+    let history = state.event_repository().history(pr(267)).await.unwrap();
+    let events = history
+      .iter()
+      .map(|entry| (entry.event.event_type.as_str(), entry.event.actor.as_str()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      events,
+      vec![("approved", "rhalm"), ("merged", "NavidJalali")]
+    );
+
+    let metadata = state.pr_repository().metadata(vec![pr(268)]).await.unwrap();
+    assert_eq!(metadata.get(&pr(268)).unwrap().title, "Update README.md");
+  }
+
+  #[tokio::test]
+  async fn attaches_reactions_to_events_we_already_received() {
+    let state = TestState::for_tests();
+    state
+      .github_client
+      .set_pull_request(status(267, PrState::Merged, vec![]));
+    let merged = RecordedEvent {
+      id: EventId(uuid::Uuid::new_v4()),
+      delivery_id: Some("be3583aa-fe69-4151-87c2-90089c51bff3".to_string()),
+      pr_url: pr(267),
+      event_type: "merged".to_string(),
+      actor: "NavidJalali".to_string(),
+      received_at: state.clock().now(),
+    };
+    state
+      .event_repository()
+      .record_event(merged.clone())
+      .await
+      .unwrap();
+
+    let create = slack_event("test_resources/slack/create-with-prs.json");
+    process_slack_event(&state, create).await.unwrap();
+
+    let history = state.event_repository().history(pr(267)).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].event.id, merged.id);
+    assert_eq!(history[0].actions.len(), 1);
+  }
+
+  #[tokio::test]
+  async fn only_looks_up_prs_an_edit_added() {
+    let state = TestState::for_tests();
+    let create = slack_event("test_resources/slack/create-with-prs.json");
+    process_slack_event(&state, create).await.unwrap();
+
+    for n in [267, 268, 269] {
+      state
+        .github_client
+        .set_pull_request(status(n, PrState::Closed, vec![]));
+    }
+    let edit = slack_event("test_resources/slack/edit-with-prs.json");
+    process_slack_event(&state, edit).await.unwrap();
+
+    assert_eq!(
+      state.slack_client.reactions(),
+      vec![reaction(Emoji::Deleted)]
+    );
+    assert!(state
+      .event_repository()
+      .history(pr(268))
+      .await
+      .unwrap()
+      .is_empty());
+  }
+}
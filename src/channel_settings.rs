@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use crate::{
+  config::Configuration,
+  models::ChannelSetting,
+  repository_rules::RepositoryRules,
+  slack::models::{Channel, Emoji},
+};
+
+/*
+ * The emoji an event gets in a channel, or None if the channel doesn't want a reaction for it.
+ * The most specific setting wins: `/prmoji config` in the channel, then `[[channels]]`,
+ * `[[repositories.rules]]` and finally `[emojis]` from the configuration. Deployments only get a
+ * reaction in the environments `[emojis.deployed]` has an emoji for, so that e.g. preview
+ * environments stay quiet. At every level a setting for one environment wins over a setting for
+ * all deployments.
+ */
+pub fn resolve(
+  config: &Configuration,
+  rules: &RepositoryRules,
+  settings: &[ChannelSetting],
+  channel: &Channel,
+  repository: Option<&str>,
+  emoji: Emoji,
+) -> Option<String> {
+  let keys = emoji.setting_keys();
+  let default = config.emojis.get(emoji)?;
+  let settings = keys
+    .iter()
+    .filter_map(|key| {
+      settings
+        .iter()
+        .find(|setting| setting.channel == *channel && setting.event_type.eq_ignore_ascii_case(key))
+    })
+    .collect::<Vec<_>>();
+  let overrides = config.channel(&channel.0);
+
+  let enabled = match settings.iter().find_map(|setting| setting.enabled) {
+    Some(enabled) => enabled,
+    None => overrides.is_none_or(|overrides| {
+      !overrides
+        .disabled
+        .iter()
+        .any(|disabled| keys.iter().any(|key| disabled.eq_ignore_ascii_case(key)))
+    }),
+  };
+  if !enabled {
+    return None;
+  }
+
+  let emoji = settings
+    .iter()
+    .find_map(|setting| setting.emoji.clone())
+    .or_else(|| {
+      overrides.and_then(|overrides| keys.iter().find_map(|key| get(&overrides.emojis, key)))
+    })
+    .or_else(|| {
+      repository
+        .and_then(|repository| keys.iter().find_map(|key| rules.emoji(repository, key)))
+        .map(|emoji| emoji.to_string())
+    })
+    .unwrap_or(default);
+  Some(emoji)
+}
+
+fn get(emojis: &HashMap<String, String>, key: &str) -> Option<String> {
+  emojis
+    .iter()
+    .find(|(event_type, _)| event_type.eq_ignore_ascii_case(key))
+    .map(|(_, emoji)| emoji.clone())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config;
+
+  fn design() -> Channel {
+    Channel("C05UBF6AJH3".to_string())
+  }
+
+  fn configuration() -> (Configuration, RepositoryRules) {
+    let mut config = Configuration::for_tests();
+    config.channels = vec![config::ChannelOverrides {
+      id: design().0,
+      emojis: [
+        ("approved".to_string(), "art".to_string()),
+        ("deployed".to_string(), "airplane".to_string()),
+      ]
+      .into(),
+      disabled: vec!["commented".to_string(), "deployed:staging".to_string()],
+    }];
+    config.repositories.rules = vec![config::RepositoryRule {
+      pattern: "fancy-org/*".to_string(),
+      events: None,
+      emojis: [
+        ("approved".to_string(), "heart".to_string()),
+        ("merged".to_string(), "rocket".to_string()),
+      ]
+      .into(),
+    }];
+    let rules = RepositoryRules::new(&config.repositories).unwrap();
+    (config, rules)
+  }
+
+  fn setting(event_type: &str, emoji: Option<&str>, enabled: Option<bool>) -> ChannelSetting {
+    ChannelSetting {
+      channel: design(),
+      event_type: event_type.to_string(),
+      emoji: emoji.map(|emoji| emoji.to_string()),
+      enabled,
+      updated_at: "2023-10-03T20:20:51Z".parse().unwrap(),
+    }
+  }
+
+  #[test]
+  fn the_most_specific_setting_wins() {
+    let (config, rules) = configuration();
+    let platform = Channel("C05UBF6AJH4".to_string());
+    let resolve = |settings: &[ChannelSetting], channel: &Channel, emoji: Emoji| {
+      resolve(
+        &config,
+        &rules,
+        settings,
+        channel,
+        Some("fancy-org/cool-repo"),
+        emoji,
+      )
+    };
+
+    assert_eq!(
+      resolve(&[], &platform, Emoji::Deleted).as_deref(),
+      Some("wastebasket")
+    );
+    assert_eq!(
+      resolve(&[], &platform, Emoji::Merged).as_deref(),
+      Some("rocket")
+    );
+    assert_eq!(
+      resolve(&[], &design(), Emoji::Approved).as_deref(),
+      Some("art")
+    );
+
+    let settings = [setting("approved", Some("tada"), None)];
+    assert_eq!(
+      resolve(&settings, &design(), Emoji::Approved).as_deref(),
+      Some("tada")
+    );
+    assert_eq!(
+      resolve(&settings, &platform, Emoji::Approved).as_deref(),
+      Some("heart")
+    );
+  }
+
+  #[test]
+  fn channels_can_turn_events_off_and_on_again() {
+    let (config, rules) = configuration();
+    let resolve = |settings: &[ChannelSetting], emoji: Emoji| {
+      resolve(&config, &rules, settings, &design(), None, emoji)
+    };
+
+    assert_eq!(resolve(&[], Emoji::Comment), None);
+    assert_eq!(
+      resolve(&[setting("commented", None, Some(true))], Emoji::Comment).as_deref(),
+      Some("speech_balloon")
+    );
+    assert_eq!(
+      resolve(
+        &[setting("merged", Some("tada"), Some(false))],
+        Emoji::Merged
+      ),
+      None
+    );
+  }
+
+  #[test]
+  fn deployments_can_be_set_up_per_environment() {
+    let (config, rules) = configuration();
+    let production = || Emoji::Deployed("Production".to_string());
+    let staging = || Emoji::Deployed("staging".to_string());
+    let resolve = |settings: &[ChannelSetting], emoji: Emoji| {
+      resolve(&config, &rules, settings, &design(), None, emoji)
+    };
+
+    assert_eq!(resolve(&[], production()).as_deref(), Some("airplane"));
+    assert_eq!(resolve(&[], staging()), None);
+
+    let settings = [
+      setting("deployed", Some("ship"), None),
+      setting("deployed:production", Some("rocket"), None),
+      setting("deployed:staging", None, Some(true)),
+    ];
+    assert_eq!(resolve(&settings, production()).as_deref(), Some("rocket"));
+    assert_eq!(resolve(&settings, staging()).as_deref(), Some("ship"));
+  }
+}
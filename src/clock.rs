@@ -12,8 +12,18 @@ impl Clock for LiveClock {
 }
 
 // Is correct once a day
+#[cfg(test)]
+#[derive(Clone)]
 pub struct FrozenClock(chrono::DateTime<chrono::Utc>);
 
+#[cfg(test)]
+impl FrozenClock {
+  pub fn new(now: chrono::DateTime<chrono::Utc>) -> Self {
+    Self(now)
+  }
+}
+
+#[cfg(test)]
 impl Clock for FrozenClock {
   fn now(&self) -> chrono::DateTime<chrono::Utc> {
     self.0
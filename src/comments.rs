@@ -0,0 +1,266 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use tracing::{info, warn};
+
+use crate::{
+  app_state::AppState,
+  clock::Clock,
+  metrics,
+  models::{PrUrl, RecordedEvent, SlackActionKind},
+  persistence::{pr_repository::PrRepository, RepositoryError},
+  reactions,
+  slack::models::Emoji,
+};
+
+/*
+ * A busy review sends a webhook for every comment. Comments are counted as they arrive, but the
+ * reactions of a PR are only updated once the debounce window after its first new comment is over:
+ * every message of the PR gets the comment emoji, and the count emoji is swapped if counts are enabled.
+ * Pending updates are kept in memory, the next comment after a restart catches up with the count.
+ */
+#[derive(Clone, Default)]
+pub struct CommentDebouncer {
+  pending: Arc<Mutex<HashMap<PrUrl, Pending>>>,
+}
+
+struct Pending {
+  due: DateTime<Utc>,
+  // The latest comment, which the Slack actions are recorded against
+  event: RecordedEvent,
+}
+
+impl CommentDebouncer {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Keeps the deadline of a pending update, so that a steady stream of comments still gets reactions
+  fn push(&self, event: RecordedEvent, due: DateTime<Utc>) {
+    let mut pending = self.pending.lock().unwrap();
+    match pending.get_mut(&event.pr_url) {
+      Some(existing) => {
+        metrics::COMMENTS_DEBOUNCED.inc();
+        existing.event = event;
+      }
+      None => {
+        pending.insert(event.pr_url.clone(), Pending { due, event });
+      }
+    }
+  }
+
+  fn take_due(&self, now: DateTime<Utc>) -> Vec<RecordedEvent> {
+    let mut pending = self.pending.lock().unwrap();
+    let due = pending
+      .iter()
+      .filter(|(_, pending)| pending.due <= now)
+      .map(|(url, _)| url.clone())
+      .collect::<Vec<_>>();
+    due
+      .into_iter()
+      .filter_map(|url| pending.remove(&url))
+      .map(|pending| pending.event)
+      .collect()
+  }
+}
+
+// Counts a comment that was already recorded, and updates the reactions now or once the window is over
+pub async fn comment_received<S: AppState>(
+  state: &S,
+  event: RecordedEvent,
+  review_id: Option<u64>,
+) -> Result<(), RepositoryError> {
+  state
+    .pr_repository()
+    .count_comment(event.pr_url.clone(), review_id, event.received_at)
+    .await?;
+
+  let window = Duration::seconds(state.config().comments.debounce_seconds);
+  if window <= Duration::zero() {
+    return update_reactions(state, &event).await;
+  }
+
+  let due = event.received_at + window;
+  state.comment_debouncer().push(event, due);
+  Ok(())
+}
+
+// Updates the reactions of every PR whose window is over
+pub async fn flush<S: AppState>(state: &S, now: DateTime<Utc>) {
+  for event in state.comment_debouncer().take_due(now) {
+    if let Err(err) = update_reactions(state, &event).await {
+      warn!("Failed to update comment reactions: {}", err);
+    }
+  }
+}
+
+pub fn spawn<S: AppState>(state: S) {
+  if state.config().comments.debounce_seconds <= 0 {
+    return;
+  }
+
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+    loop {
+      interval.tick().await;
+      flush(&state, state.clock().now()).await;
+    }
+  });
+}
+
+#[tracing::instrument(skip_all, fields(pr_url = %event.pr_url.0))]
+async fn update_reactions<S: AppState>(
+  state: &S,
+  event: &RecordedEvent,
+) -> Result<(), RepositoryError> {
+  let repo = state.pr_repository();
+  let comments = match repo.comments(event.pr_url.clone()).await? {
+    Some(comments) => comments,
+    None => return Ok(()),
+  };
+
+  let prs = repo.get_by_url(event.pr_url.clone()).await?;
+  let messages = reactions::resolve_emojis(state, event, prs, Emoji::Comment).await;
+
+  // Every message gets the emojis, also those posted after the first comment. Slack answers
+  // `already_reacted` for the messages that have them
+  reactions::perform(state, event, SlackActionKind::AddReaction, messages.clone()).await;
+
+  let config = &state.config().comments;
+  if config.counts {
+    let previous = config.count_emoji(comments.reacted_comments);
+    let current = config.count_emoji(comments.comments);
+    let with = |emoji: &str| {
+      messages
+        .iter()
+        .map(|(pr, _)| (pr.clone(), emoji.to_string()))
+        .collect::<Vec<_>>()
+    };
+    if let Some(previous) = previous.filter(|previous| Some(*previous) != current) {
+      info!(
+        "Updating the comment count of {:?} to {}",
+        event.pr_url, comments.comments
+      );
+      reactions::perform(
+        state,
+        event,
+        SlackActionKind::RemoveReaction,
+        with(previous),
+      )
+      .await;
+    }
+    if let Some(current) = current {
+      reactions::perform(state, event, SlackActionKind::AddReaction, with(current)).await;
+    }
+  }
+
+  repo
+    .set_reacted_comments(event.pr_url.clone(), comments.comments)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    app_state::TestState,
+    models::{EventId, ToInsert},
+    slack::models::{Channel, Timestamp},
+    test_support::{added_names, removed_names, track},
+  };
+
+  fn pr() -> PrUrl {
+    PrUrl::from("https://github.com/fancy-org/cool-repo/pull/267")
+  }
+
+  async fn state(debounce_seconds: i64) -> TestState {
+    let mut state = TestState::for_tests();
+    state.config.comments.debounce_seconds = debounce_seconds;
+    state.config.comments.counts = true;
+    state.config.comments.count_emojis = vec!["one".into(), "two".into(), "many".into()];
+    track(&state, &pr()).await;
+    state
+  }
+
+  async fn comment(state: &TestState, seconds_later: i64) {
+    let event = RecordedEvent {
+      id: EventId(uuid::Uuid::new_v4()),
+      delivery_id: None,
+      pr_url: pr(),
+      event_type: "commented".to_string(),
+      actor: "rhalm".to_string(),
+      received_at: state.clock().now() + Duration::seconds(seconds_later),
+    };
+    comment_received(state, event, None).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn reactions_catch_up_once_the_window_is_over() {
+    let state = state(30).await;
+    let start = state.clock().now();
+
+    comment(&state, 0).await;
+    comment(&state, 10).await;
+    flush(&state, start + Duration::seconds(29)).await;
+    assert_eq!(added_names(&state), Vec::<String>::new());
+
+    flush(&state, start + Duration::seconds(30)).await;
+    assert_eq!(added_names(&state), vec!["speech_balloon", "two"]);
+
+    comment(&state, 40).await;
+    flush(&state, start + Duration::seconds(70)).await;
+    assert_eq!(added_names(&state), vec!["speech_balloon", "two", "many"]);
+    assert_eq!(removed_names(&state), vec!["two"]);
+
+    // Nothing changes once the count emoji stands for many comments
+    comment(&state, 80).await;
+    flush(&state, start + Duration::seconds(110)).await;
+    assert_eq!(added_names(&state).len(), 3);
+    assert_eq!(removed_names(&state).len(), 1);
+
+    let comments = state.pr_repository().comments(pr()).await.unwrap().unwrap();
+    assert_eq!(comments.comments, 4);
+    assert_eq!(comments.reacted_comments, 4);
+  }
+
+  #[tokio::test]
+  async fn reacts_right_away_without_a_window() {
+    let state = state(0).await;
+
+    comment(&state, 0).await;
+    assert_eq!(added_names(&state), vec!["speech_balloon", "one"]);
+  }
+
+  #[tokio::test]
+  async fn messages_posted_later_get_the_emojis_too() {
+    let state = state(0).await;
+    comment(&state, 0).await;
+    comment(&state, 10).await;
+
+    let later = Timestamp("1696367999.000100".to_string());
+    state
+      .pr_repository()
+      .insert_all(ToInsert::new(
+        vec![pr()],
+        Channel("C05UBF6AJH4".to_string()),
+        later.clone(),
+        state.clock().now(),
+      ))
+      .await
+      .unwrap();
+    comment(&state, 20).await;
+
+    let reactions_of_later = state
+      .slack_client
+      .reactions()
+      .into_iter()
+      .filter(|reaction| reaction.timestamp == later)
+      .map(|reaction| reaction.name)
+      .collect::<Vec<_>>();
+    assert_eq!(reactions_of_later, vec!["speech_balloon", "many"]);
+    assert_eq!(removed_names(&state), vec!["one", "two"]);
+  }
+}
@@ -1,9 +1,26 @@
+use std::collections::HashMap;
+
 use config::{Config, ConfigError, Environment, File};
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::slack::models::Emoji;
 
-#[derive(Debug, Deserialize, Clone)]
+// Secrets are never serialized, so that the configuration can be shown for diagnostics
+fn redacted<T, S: Serializer>(_: &T, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_str("<redacted>")
+}
+
+fn redacted_option<S: Serializer>(
+  value: &Option<String>,
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  match value {
+    Some(_) => serializer.serialize_some("<redacted>"),
+    None => serializer.serialize_none(),
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Server {
   pub host: String,
   pub port: u16,
@@ -15,22 +32,27 @@ impl Server {
   }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Slack {
+  #[serde(serialize_with = "redacted")]
   signing_secret: String,
   pub token: SlackToken,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(tag = "type")]
 pub enum SlackToken {
   BotToken {
+    #[serde(serialize_with = "redacted")]
     bot_token: String,
   },
   OAuth {
     client_id: String,
+    #[serde(serialize_with = "redacted")]
     client_secret: String,
+    #[serde(serialize_with = "redacted")]
     access_token: String,
+    #[serde(serialize_with = "redacted")]
     refresh_token: String,
   },
 }
@@ -41,9 +63,30 @@ impl Slack {
   }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Github {
+  #[serde(serialize_with = "redacted")]
   secret: String,
+  // The REST API, e.g. `https://github.example.com/api/v3` on GitHub Enterprise
+  pub api_url: String,
+  // The API is only called if credentials are configured
+  #[serde(default)]
+  pub auth: Option<GithubAuth>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "type")]
+pub enum GithubAuth {
+  Token {
+    #[serde(serialize_with = "redacted")]
+    token: String,
+  },
+  App {
+    app_id: u64,
+    // The PEM encoded private key of the app
+    #[serde(serialize_with = "redacted")]
+    private_key: String,
+  },
 }
 
 impl Github {
@@ -52,11 +95,24 @@ impl Github {
   }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+  #[default]
+  Postgres,
+  Sqlite,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Database {
+  #[serde(default)]
+  pub backend: Backend,
+  // The database file, only used by the SQLite backend
+  pub path: String,
   pub host: String,
   pub port: u16,
   pub user: String,
+  #[serde(serialize_with = "redacted")]
   pub password: String,
   pub database: String,
   pub pool_size: u32,
@@ -71,34 +127,287 @@ impl Database {
   }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Admin {
+  #[serde(serialize_with = "redacted_option")]
+  token: Option<String>,
+}
+
+impl Admin {
+  // Admin endpoints are disabled unless a token is configured
+  pub fn token(&self) -> Option<&[u8]> {
+    self.token.as_ref().map(|token| token.as_bytes())
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Diagnostics {
+  pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+  Text,
+  Json,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Telemetry {
+  // A `tracing_subscriber::EnvFilter` directive, e.g. `info` or `prmoji=debug,sqlx=warn`
+  pub level: String,
+  pub format: LogFormat,
+  pub service_name: String,
+  // e.g. `http://localhost:4318/v1/traces`, spans are not exported if unset
+  pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Queue {
+  pub capacity: usize,
+  pub workers: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Retention {
+  // Whether the server purges old rows in the background, `prmoji gc` works either way
+  pub enabled: bool,
+  pub interval_minutes: u64,
+  // How long merged and closed PRs stay tracked, so late reviews still get a reaction
+  pub grace_period_hours: i64,
+  // Rows older than this are purged whatever the state of the PR
+  pub max_age_days: i64,
+  // How long events and Slack actions stay in the audit log, at least as long as PRs are tracked
+  pub audit_log_days: i64,
+}
+
+// Lists can be given as a comma separated string, e.g. `REPOSITORIES.INCLUDE=fancy-org/*,other-org/prmoji`
+fn comma_separated<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum StringOrList {
+    String(String),
+    List(Vec<String>),
+  }
+
+  Ok(match StringOrList::deserialize(deserializer)? {
+    StringOrList::String(value) => value
+      .split(',')
+      .map(|item| item.trim().to_string())
+      .filter(|item| !item.is_empty())
+      .collect(),
+    StringOrList::List(items) => items,
+  })
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Repositories {
+  // `owner/name` globs of the repositories to track, every repository if empty
+  #[serde(default, deserialize_with = "comma_separated")]
+  pub include: Vec<String>,
+  // Repositories matching any of these are ignored even if they are included
+  #[serde(default, deserialize_with = "comma_separated")]
+  pub exclude: Vec<String>,
+  #[serde(default)]
+  pub rules: Vec<RepositoryRule>,
+}
+
+fn optional_comma_separated<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+  comma_separated(deserializer).map(Some)
+}
+
+// Overrides for the repositories matching `pattern`, the first matching rule applies
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RepositoryRule {
+  pub pattern: String,
+  // The event types that produce reactions, by `GitHubEventType::name`. All of them if unset
+  #[serde(default, deserialize_with = "optional_comma_separated")]
+  pub events: Option<Vec<String>>,
+  // Emojis by event type, e.g. `{ approved = "heart" }`
+  #[serde(default)]
+  pub emojis: HashMap<String, String>,
+}
+
+// Overrides for a Slack channel, which win over the repository rules
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ChannelOverrides {
+  // The channel id, e.g. `C05UBF6AJH3`
+  pub id: String,
+  // Emojis by event type, e.g. `{ approved = "art" }`
+  #[serde(default)]
+  pub emojis: HashMap<String, String>,
+  // Event types that get no reaction in the channel
+  #[serde(default, deserialize_with = "comma_separated")]
+  pub disabled: Vec<String>,
+}
+
+// Whose feedback gets no reaction, logins are compared case insensitively like on GitHub
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Actors {
+  // GitHub Apps, whose logins end with `[bot]`, e.g. `dependabot[bot]`
+  #[serde(default)]
+  pub ignore_bots: bool,
+  // Comments by the author of a PR on their own PR
+  #[serde(default)]
+  pub ignore_pr_author: bool,
+  // Logins to ignore, e.g. a CI user
+  #[serde(default, deserialize_with = "comma_separated")]
+  pub ignore: Vec<String>,
+  // The event types the filters apply to. Merges, deployments and the merge queue are usually
+  // triggered by bots, and still get their reactions
+  #[serde(default = "feedback_events", deserialize_with = "comma_separated")]
+  pub events: Vec<String>,
+}
+
+fn feedback_events() -> Vec<String> {
+  ["commented", "approved", "changes_requested"]
+    .map(String::from)
+    .to_vec()
+}
+
+impl Default for Actors {
+  fn default() -> Self {
+    Self {
+      ignore_bots: false,
+      ignore_pr_author: false,
+      ignore: vec![],
+      events: feedback_events(),
+    }
+  }
+}
+
+impl Actors {
+  pub fn ignores(&self, actor: &str, event_type: &str, pr_author: Option<&str>) -> bool {
+    if !self.events.iter().any(|event| event == event_type) {
+      return false;
+    }
+    let is_bot = actor.to_ascii_lowercase().ends_with("[bot]");
+    let is_author = pr_author.is_some_and(|author| author.eq_ignore_ascii_case(actor));
+    (self.ignore_bots && is_bot)
+      || (self.ignore_pr_author && is_author && event_type == "commented")
+      || self
+        .ignore
+        .iter()
+        .any(|login| login.eq_ignore_ascii_case(actor))
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Reviews {
+  // Whether a dismissed review takes the approval reaction back, meant for branch protection
+  // that dismisses stale approvals when new commits are pushed
+  #[serde(default)]
+  pub clear_dismissed_approvals: bool,
+}
+
+impl Reviews {
+  // Dismissed reviews are recorded either way
+  pub fn reacts_to(&self, event_type: &str) -> bool {
+    event_type != "review_dismissed" || self.clear_dismissed_approvals
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Comments {
+  // Comment reactions of a PR are updated once this long after its first new comment, 0 updates them right away
+  pub debounce_seconds: i64,
+  // Whether PRs also get an emoji for their number of comments
+  pub counts: bool,
+  // The emojis for one comment, two comments and so on, the last one stands for that many or more
+  #[serde(deserialize_with = "comma_separated")]
+  pub count_emojis: Vec<String>,
+}
+
+impl Comments {
+  pub fn count_emoji(&self, comments: u32) -> Option<&str> {
+    if comments == 0 {
+      return None;
+    }
+    let index = (comments as usize - 1).min(self.count_emojis.len().checked_sub(1)?);
+    Some(self.count_emojis[index].as_str())
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Conflicts {
+  // Whether open PRs are checked on GitHub in the background, webhooks often don't know yet
+  pub poll: bool,
+  pub poll_interval_minutes: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Emojis {
   pub merged: String,
   pub closed: String,
   pub approved: String,
   pub commented: String,
   pub changes_requested: String,
+  pub auto_merge_enabled: String,
+  pub merge_queued: String,
+  pub updated: String,
+  pub conflicted: String,
+  // By environment, deployments to other environments get no reaction
+  #[serde(default)]
+  pub deployed: HashMap<String, String>,
 }
 
 impl Emojis {
-  pub fn get(&self, emoji: Emoji) -> String {
+  pub fn get(&self, emoji: Emoji) -> Option<String> {
     match emoji {
-      Emoji::Merged => self.merged.clone(),
-      Emoji::Deleted => self.closed.clone(),
-      Emoji::Approved => self.approved.clone(),
-      Emoji::Comment => self.commented.clone(),
-      Emoji::ChangeRequest => self.changes_requested.clone(),
+      Emoji::Merged => Some(self.merged.clone()),
+      Emoji::Deleted => Some(self.closed.clone()),
+      Emoji::Approved => Some(self.approved.clone()),
+      Emoji::Comment => Some(self.commented.clone()),
+      Emoji::ChangeRequest => Some(self.changes_requested.clone()),
+      Emoji::AutoMerge => Some(self.auto_merge_enabled.clone()),
+      Emoji::MergeQueue => Some(self.merge_queued.clone()),
+      Emoji::Updated => Some(self.updated.clone()),
+      Emoji::Conflict => Some(self.conflicted.clone()),
+      // Environment names are case insensitive on GitHub
+      Emoji::Deployed(environment) => self
+        .deployed
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(&environment))
+        .map(|(_, emoji)| emoji.clone()),
     }
   }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl Configuration {
+  pub fn channel(&self, channel: &str) -> Option<&ChannelOverrides> {
+    self
+      .channels
+      .iter()
+      .find(|overrides| overrides.id == channel)
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Configuration {
   pub server: Server,
   pub slack: Slack,
   pub database: Database,
   pub github: Github,
   pub emojis: Emojis,
+  pub comments: Comments,
+  pub conflicts: Conflicts,
+  pub queue: Queue,
+  pub retention: Retention,
+  pub telemetry: Telemetry,
+  #[serde(default)]
+  pub admin: Admin,
+  #[serde(default)]
+  pub diagnostics: Diagnostics,
+  #[serde(default)]
+  pub repositories: Repositories,
+  #[serde(default)]
+  pub actors: Actors,
+  #[serde(default)]
+  pub reviews: Reviews,
+  #[serde(default)]
+  pub channels: Vec<ChannelOverrides>,
 }
 
 impl Configuration {
@@ -109,4 +418,142 @@ impl Configuration {
       .build()?
       .try_deserialize()
   }
+
+  #[cfg(test)]
+  pub fn for_tests() -> Self {
+    Config::builder()
+      .add_source(File::with_name("config/default"))
+      .set_override("slack.signing_secret", "slack-signing-secret")
+      .unwrap()
+      .set_override("slack.token.type", "BotToken")
+      .unwrap()
+      .set_override("slack.token.bot_token", "xoxb-test")
+      .unwrap()
+      .set_override("github.secret", "github-secret")
+      .unwrap()
+      .set_override("admin.token", "admin-token")
+      .unwrap()
+      .build()
+      .unwrap()
+      .try_deserialize()
+      .unwrap()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn secrets_are_redacted() {
+    let slack: Slack = serde_json::from_value(serde_json::json!({
+      "signing_secret": "It's a Secret to Everybody",
+      "token": { "type": "BotToken", "bot_token": "xoxb-secret" }
+    }))
+    .unwrap();
+
+    assert_eq!(
+      serde_json::to_value(&slack).unwrap(),
+      serde_json::json!({
+        "signing_secret": "<redacted>",
+        "token": { "type": "BotToken", "bot_token": "<redacted>" }
+      })
+    );
+  }
+
+  #[test]
+  fn reads_channel_and_repository_overrides() {
+    let config: Configuration = Config::builder()
+      .add_source(File::with_name("config/default"))
+      .add_source(config::File::from_str(
+        r#"
+          [slack]
+          signing_secret = "slack-signing-secret"
+          token = { type = "BotToken", bot_token = "xoxb-test" }
+
+          [github]
+          secret = "github-secret"
+
+          [[repositories.rules]]
+          pattern = "fancy-org/design-*"
+          emojis = { approved = "heart" }
+
+          [[channels]]
+          id = "C05UBF6AJH3"
+          emojis = { merged = "art" }
+          disabled = ["commented"]
+        "#,
+        config::FileFormat::Toml,
+      ))
+      .build()
+      .unwrap()
+      .try_deserialize()
+      .unwrap();
+
+    assert_eq!(config.repositories.rules[0].events, None);
+    assert_eq!(config.repositories.rules[0].emojis["approved"], "heart");
+    let channel = config.channel("C05UBF6AJH3").unwrap();
+    assert_eq!(channel.emojis["merged"], "art");
+    assert_eq!(channel.disabled, vec!["commented"]);
+    assert!(config.channel("C05UBF6AJH4").is_none());
+  }
+
+  #[test]
+  fn the_last_count_emoji_stands_for_many_comments() {
+    let comments = Comments {
+      debounce_seconds: 30,
+      counts: true,
+      count_emojis: vec!["one".to_string(), "two".to_string(), "many".to_string()],
+    };
+    assert_eq!(comments.count_emoji(0), None);
+    assert_eq!(comments.count_emoji(1), Some("one"));
+    assert_eq!(comments.count_emoji(3), Some("many"));
+    assert_eq!(comments.count_emoji(42), Some("many"));
+
+    let none = Comments {
+      count_emojis: vec![],
+      ..comments
+    };
+    assert_eq!(none.count_emoji(1), None);
+  }
+
+  #[test]
+  fn ignores_bots_authors_and_listed_actors() {
+    let actors = Actors {
+      ignore_bots: true,
+      ignore_pr_author: true,
+      ignore: vec!["ci-user".to_string()],
+      ..Default::default()
+    };
+    assert!(actors.ignores("dependabot[bot]", "approved", None));
+    assert!(actors.ignores("CI-User", "changes_requested", None));
+    // Bots merging and deploying PRs still get reactions
+    assert!(!actors.ignores("mergify[bot]", "merged", None));
+    assert!(!actors.ignores("CI-User", "deployed:production", None));
+    assert!(actors.ignores("navidjalali", "commented", Some("NavidJalali")));
+    // Authors still get reactions for merging their own PR
+    assert!(!actors.ignores("NavidJalali", "merged", Some("NavidJalali")));
+    assert!(!actors.ignores("rhalm", "commented", Some("NavidJalali")));
+    assert!(!Actors::default().ignores("dependabot[bot]", "commented", None));
+  }
+
+  #[test]
+  fn repository_lists_can_be_comma_separated() {
+    let repositories: Repositories = serde_json::from_value(serde_json::json!({
+      "include": "fancy-org/*, NavidJalali/prmoji",
+      "exclude": ["fancy-org/legacy-*"],
+      "rules": [{ "pattern": "fancy-org/noisy", "events": "merged,closed" }]
+    }))
+    .unwrap();
+
+    assert_eq!(
+      repositories.include,
+      vec!["fancy-org/*", "NavidJalali/prmoji"]
+    );
+    assert_eq!(repositories.exclude, vec!["fancy-org/legacy-*"]);
+    assert_eq!(
+      repositories.rules[0].events,
+      Some(vec!["merged".to_string(), "closed".to_string()])
+    );
+  }
 }
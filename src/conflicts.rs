@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use tracing::{error, info, warn};
+
+use crate::{
+  app_state::AppState,
+  backfill,
+  clock::Clock,
+  github::client::GitHubClient,
+  models::{EventId, PrFilter, PrState, PrUrl, RecordedEvent},
+  persistence::{event_repository::EventRepository, pr_repository::PrRepository, RepositoryError},
+  reactions,
+  slack::models::Emoji,
+};
+
+const CONFLICTED: &str = "conflicted";
+const RESOLVED: &str = "conflict_resolved";
+// GitHub finds the conflicts, whoever caused them
+const ACTOR: &str = "github";
+
+/*
+ * Whether a PR has merge conflicts is a state rather than an event: GitHub reports it with the
+ * pull_request webhooks once it knows, and the background check asks the API. Only changes of it
+ * are recorded, as `conflicted` and `conflict_resolved`, which add and remove the conflict reaction.
+ * Nothing is looked up for PRs that get no conflict reaction. Returns whether the state changed.
+ */
+pub async fn update<S: AppState>(
+  state: &S,
+  url: PrUrl,
+  conflicted: bool,
+  delivery_id: Option<String>,
+) -> Result<bool, RepositoryError> {
+  if !gets_conflict_reactions(state, &url) {
+    return Ok(false);
+  }
+
+  let was_conflicted = state
+    .event_repository()
+    .latest_of(url.clone(), &[CONFLICTED, RESOLVED])
+    .await?
+    .is_some_and(|event| event.event_type == CONFLICTED);
+  if was_conflicted == conflicted {
+    return Ok(false);
+  }
+
+  let event = RecordedEvent {
+    id: EventId(uuid::Uuid::new_v4()),
+    delivery_id,
+    pr_url: url.clone(),
+    event_type: if conflicted { CONFLICTED } else { RESOLVED }.to_string(),
+    actor: ACTOR.to_string(),
+    received_at: state.clock().now(),
+  };
+  state.event_repository().record_event(event.clone()).await?;
+  info!("Merge conflicts of {:?}: {}", url, conflicted);
+
+  let prs = state.pr_repository().get_by_url(url).await?;
+  match conflicted {
+    true => reactions::add_reactions(state, &event, prs, Emoji::Conflict).await,
+    false => reactions::remove_reactions(state, &event, prs, Emoji::Conflict).await,
+  };
+  Ok(true)
+}
+
+fn gets_conflict_reactions<S: AppState>(state: &S, url: &PrUrl) -> bool {
+  state.repository_rules().reacts_to_pr(url, CONFLICTED)
+    && !state.config().actors.ignores(ACTOR, CONFLICTED, None)
+}
+
+// Asks GitHub about every open PR that is tracked and gets conflict reactions, does nothing without
+// GitHub credentials
+#[tracing::instrument(name = "check_conflicts", skip_all)]
+pub async fn check_open_prs<S: AppState>(state: &S) -> Result<(), RepositoryError> {
+  let github = match state.github_client() {
+    Some(github) => github,
+    None => return Ok(()),
+  };
+
+  let mut urls = Vec::new();
+  for pr in state.pr_repository().list(PrFilter::default()).await? {
+    if !urls.contains(&pr.url) && gets_conflict_reactions(state, &pr.url) {
+      urls.push(pr.url);
+    }
+  }
+  let states: HashMap<PrUrl, PrState> = state.event_repository().states(urls.clone()).await?;
+
+  for url in urls {
+    if states
+      .get(&url)
+      .is_some_and(|state| *state != PrState::Open)
+    {
+      continue;
+    }
+
+    let installation_id = backfill::installation_of(state, &url).await?;
+    let status = match github.pull_request(&url, installation_id).await {
+      Ok(status) => status,
+      Err(err) => {
+        warn!(
+          "Failed to look up the pull request on GitHub: {}",
+          err.code()
+        );
+        continue;
+      }
+    };
+
+    if let (PrState::Open, Some(conflicted)) = (status.state, status.conflicted) {
+      update(state, url, conflicted, None).await?;
+    }
+  }
+  Ok(())
+}
+
+pub fn spawn<S: AppState>(state: S) {
+  let config = state.config().conflicts.clone();
+  if !config.poll || state.github_client().is_none() {
+    info!("Merge conflicts are not polled");
+    return;
+  }
+
+  let period = std::time::Duration::from_secs(config.poll_interval_minutes.max(1) * 60);
+  info!(
+    "Checking open PRs for merge conflicts every {} minute(s)",
+    period.as_secs() / 60
+  );
+
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(period);
+    loop {
+      interval.tick().await;
+      if let Err(err) = check_open_prs(&state).await {
+        error!("Failed to check open PRs for merge conflicts: {}", err);
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    app_state::TestState,
+    config,
+    github::client::PullRequestStatus,
+    repository_rules::RepositoryRules,
+    test_support::{added_names, removed_names, track},
+  };
+
+  fn pr() -> PrUrl {
+    PrUrl::from("https://github.com/fancy-org/cool-repo/pull/267")
+  }
+
+  fn status(conflicted: Option<bool>) -> PullRequestStatus {
+    PullRequestStatus {
+      url: pr(),
+      repository: "fancy-org/cool-repo".to_string(),
+      number: 267,
+      title: "Update README.md".to_string(),
+      author: Some("NavidJalali".to_string()),
+      state: PrState::Open,
+      merged_by: None,
+      merge_commit_sha: None,
+      conflicted,
+      commented: false,
+      reviews: vec![],
+    }
+  }
+
+  #[tokio::test]
+  async fn polling_follows_the_conflicts_of_open_prs() {
+    let state = TestState::for_tests();
+    track(&state, &pr()).await;
+
+    for conflicted in [Some(true), None, Some(true), Some(false), Some(false)] {
+      state.github_client.set_pull_request(status(conflicted));
+      check_open_prs(&state).await.unwrap();
+    }
+
+    assert_eq!(added_names(&state), vec!["crossed_swords"]);
+    assert_eq!(removed_names(&state), vec!["crossed_swords"]);
+
+    let history = state.event_repository().history(pr()).await.unwrap();
+    let event_types = history
+      .iter()
+      .map(|entry| entry.event.event_type.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(event_types, vec![CONFLICTED, RESOLVED]);
+  }
+
+  #[tokio::test]
+  async fn repositories_without_conflict_reactions_are_left_alone() {
+    let mut state = TestState::for_tests();
+    state.repository_rules = RepositoryRules::new(&config::Repositories {
+      rules: vec![config::RepositoryRule {
+        pattern: "fancy-org/*".to_string(),
+        events: Some(vec!["merged".to_string()]),
+        emojis: Default::default(),
+      }],
+      ..Default::default()
+    })
+    .unwrap();
+    track(&state, &pr()).await;
+    state.github_client.set_pull_request(status(Some(true)));
+
+    check_open_prs(&state).await.unwrap();
+    assert_eq!(state.github_client.lookups(), 0);
+
+    assert!(!update(&state, pr(), true, None).await.unwrap());
+    assert!(added_names(&state).is_empty());
+    assert!(state
+      .event_repository()
+      .history(pr())
+      .await
+      .unwrap()
+      .is_empty());
+  }
+}
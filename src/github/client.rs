@@ -0,0 +1,764 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+
+use super::{Repository, User};
+use crate::{
+  config::GithubAuth,
+  models::{PrMetadata, PrState, PrUrl},
+};
+
+#[derive(Debug)]
+pub enum GitHubClientError {
+  NotAPullRequest(PrUrl),
+  InvalidPrivateKey(jsonwebtoken::errors::Error),
+  ClientSendError(reqwest::Error),
+  CannotReadBody(reqwest::Error),
+  UnexpectedResponse(reqwest::StatusCode),
+}
+
+impl GitHubClientError {
+  pub fn code(&self) -> String {
+    match self {
+      GitHubClientError::NotAPullRequest(_) => "not_a_pull_request".to_string(),
+      GitHubClientError::InvalidPrivateKey(_) => "invalid_private_key".to_string(),
+      GitHubClientError::ClientSendError(_) => "client_send_error".to_string(),
+      GitHubClientError::CannotReadBody(_) => "cannot_read_body".to_string(),
+      GitHubClientError::UnexpectedResponse(status) => format!("http_{}", status.as_u16()),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewState {
+  Approved,
+  ChangesRequested,
+  Commented,
+  Dismissed,
+  Other,
+}
+
+impl ReviewState {
+  fn from_api(state: &str) -> Self {
+    match state {
+      "APPROVED" => ReviewState::Approved,
+      "CHANGES_REQUESTED" => ReviewState::ChangesRequested,
+      "COMMENTED" => ReviewState::Commented,
+      "DISMISSED" => ReviewState::Dismissed,
+      _ => ReviewState::Other,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Review {
+  pub state: ReviewState,
+  pub reviewer: Option<String>,
+}
+
+// What GitHub says about a pull request right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullRequestStatus {
+  pub url: PrUrl,
+  pub repository: String,
+  pub number: u32,
+  pub title: String,
+  pub author: Option<String>,
+  pub state: PrState,
+  pub merged_by: Option<String>,
+  // Only set once the PR is merged, GitHub names a test merge before that
+  pub merge_commit_sha: Option<String>,
+  // None while GitHub is still working it out
+  pub conflicted: Option<bool>,
+  pub commented: bool,
+  pub reviews: Vec<Review>,
+}
+
+impl PullRequestStatus {
+  /*
+   * The events a webhook would have told us about, by `GitHubEventType::name`, with whoever caused them.
+   * Every kind of event is only listed once, in the order its reactions would have been added.
+   */
+  pub fn events(&self) -> Vec<(&'static str, String)> {
+    let unknown = || "github".to_string();
+    let mut events: Vec<(&'static str, String)> = Vec::new();
+    let mut push = |name: &'static str, actor: String| {
+      if !events.iter().any(|(seen, _)| *seen == name) {
+        events.push((name, actor));
+      }
+    };
+
+    if self.commented {
+      push("commented", unknown());
+    }
+
+    for review in &self.reviews {
+      let name = match review.state {
+        ReviewState::Approved => "approved",
+        ReviewState::ChangesRequested => "changes_requested",
+        _ => continue,
+      };
+      push(name, review.reviewer.clone().unwrap_or_else(unknown));
+    }
+
+    match self.state {
+      PrState::Merged => push("merged", self.merged_by.clone().unwrap_or_else(unknown)),
+      PrState::Closed => push("closed", unknown()),
+      PrState::Open => {}
+    }
+
+    events
+  }
+
+  pub fn metadata(&self, updated_at: DateTime<Utc>) -> PrMetadata {
+    PrMetadata {
+      url: self.url.clone(),
+      repository: self.repository.clone(),
+      number: self.number,
+      title: self.title.clone(),
+      author: self.author.clone(),
+      merge_commit_sha: self.merge_commit_sha.clone(),
+      updated_at,
+    }
+  }
+}
+
+#[async_trait::async_trait]
+pub trait GitHubClient {
+  // As the given GitHub App installation if known, it is looked up otherwise
+  async fn pull_request(
+    &self,
+    url: &PrUrl,
+    installation_id: Option<u64>,
+  ) -> Result<PullRequestStatus, GitHubClientError>;
+
+  // Whether pushes to PRs into the branch dismiss their approvals, by a ruleset or branch protection
+  async fn dismisses_stale_reviews(
+    &self,
+    url: &PrUrl,
+    branch: &str,
+    installation_id: Option<u64>,
+  ) -> Result<bool, GitHubClientError>;
+}
+
+// `owner`, `name` and number of the repository a pull request URL points to
+pub fn pull_request_path(url: &PrUrl) -> Option<(String, String, u32)> {
+  let mut segments = url.0.trim_end_matches('/').rsplit('/');
+  let number = segments.next()?.parse().ok()?;
+  if segments.next()? != "pull" {
+    return None;
+  }
+  let name = segments.next()?.to_string();
+  let owner = segments.next()?.to_string();
+  Some((owner, name, number))
+}
+
+#[derive(Deserialize)]
+struct ApiBranch {
+  repo: Repository,
+}
+
+#[derive(Deserialize)]
+struct ApiPullRequest {
+  number: u32,
+  title: String,
+  user: Option<User>,
+  state: String,
+  merged: Option<bool>,
+  merged_by: Option<User>,
+  merge_commit_sha: Option<String>,
+  mergeable_state: Option<String>,
+  comments: Option<u32>,
+  review_comments: Option<u32>,
+  base: ApiBranch,
+}
+
+#[derive(Deserialize)]
+struct ApiReview {
+  state: String,
+  user: Option<User>,
+}
+
+#[derive(Deserialize)]
+struct ApiInstallation {
+  id: u64,
+}
+
+#[derive(Deserialize)]
+struct ApiBranchRule {
+  #[serde(rename = "type")]
+  rule_type: String,
+  #[serde(default)]
+  parameters: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ApiRequiredReviews {
+  #[serde(default)]
+  dismiss_stale_reviews: bool,
+}
+
+#[derive(Deserialize)]
+struct ApiAccessToken {
+  token: String,
+  expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct AppClaims {
+  iat: i64,
+  exp: i64,
+  iss: String,
+}
+
+#[derive(Clone)]
+struct AccessToken {
+  token: String,
+  expires_at: DateTime<Utc>,
+}
+
+enum Credentials {
+  Token(String),
+  App {
+    app_id: u64,
+    key: EncodingKey,
+    // Installation ids by repository owner
+    installations: Mutex<HashMap<String, u64>>,
+    tokens: Mutex<HashMap<u64, AccessToken>>,
+  },
+}
+
+#[derive(Clone)]
+pub struct LiveGitHubClient {
+  api_url: String,
+  credentials: Arc<Credentials>,
+  http_client: Arc<reqwest::Client>,
+}
+
+impl LiveGitHubClient {
+  pub fn new(config: &crate::config::Github, auth: &GithubAuth) -> Result<Self, GitHubClientError> {
+    let credentials = match auth {
+      GithubAuth::Token { token } => Credentials::Token(token.clone()),
+      GithubAuth::App {
+        app_id,
+        private_key,
+      } => Credentials::App {
+        app_id: *app_id,
+        key: EncodingKey::from_rsa_pem(private_key.as_bytes())
+          .map_err(GitHubClientError::InvalidPrivateKey)?,
+        installations: Mutex::new(HashMap::new()),
+        tokens: Mutex::new(HashMap::new()),
+      },
+    };
+
+    Ok(Self {
+      api_url: config.api_url.trim_end_matches('/').to_string(),
+      credentials: Arc::new(credentials),
+      http_client: Arc::new(reqwest::Client::new()),
+    })
+  }
+
+  fn request(&self, method: reqwest::Method, path: &str, token: &str) -> reqwest::RequestBuilder {
+    self
+      .http_client
+      .request(method, format!("{}{}", self.api_url, path))
+      .bearer_auth(token)
+      .header("Accept", "application/vnd.github+json")
+      .header("X-GitHub-Api-Version", "2022-11-28")
+      .header("User-Agent", "prmoji")
+  }
+
+  async fn read_response<T: serde::de::DeserializeOwned>(
+    response: reqwest::Response,
+  ) -> Result<T, GitHubClientError> {
+    let status = response.status();
+    if !status.is_success() {
+      return Err(GitHubClientError::UnexpectedResponse(status));
+    }
+
+    response
+      .json::<T>()
+      .await
+      .map_err(GitHubClientError::CannotReadBody)
+  }
+
+  async fn get<T: serde::de::DeserializeOwned>(
+    &self,
+    path: &str,
+    token: &str,
+  ) -> Result<T, GitHubClientError> {
+    let response = self
+      .request(reqwest::Method::GET, path, token)
+      .send()
+      .await
+      .map_err(GitHubClientError::ClientSendError)?;
+
+    Self::read_response(response).await
+  }
+
+  // Apps authenticate as themselves with a short-lived JWT, and as an installation with a token
+  fn app_jwt(app_id: u64, key: &EncodingKey) -> Result<String, GitHubClientError> {
+    let now = Utc::now();
+    let claims = AppClaims {
+      // Allows for clock drift, as GitHub recommends
+      iat: (now - Duration::seconds(60)).timestamp(),
+      exp: (now + Duration::minutes(9)).timestamp(),
+      iss: app_id.to_string(),
+    };
+
+    jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, key)
+      .map_err(GitHubClientError::InvalidPrivateKey)
+  }
+
+  async fn token(
+    &self,
+    owner: &str,
+    name: &str,
+    installation_id: Option<u64>,
+  ) -> Result<String, GitHubClientError> {
+    let (app_id, key, installations, tokens) = match self.credentials.as_ref() {
+      Credentials::Token(token) => return Ok(token.clone()),
+      Credentials::App {
+        app_id,
+        key,
+        installations,
+        tokens,
+      } => (*app_id, key, installations, tokens),
+    };
+
+    let known = installation_id.or_else(|| installations.lock().unwrap().get(owner).copied());
+    let installation = match known {
+      Some(installation) => installation,
+      None => {
+        let jwt = Self::app_jwt(app_id, key)?;
+        let installation = self
+          .get::<ApiInstallation>(&format!("/repos/{owner}/{name}/installation"), &jwt)
+          .await?
+          .id;
+        installations
+          .lock()
+          .unwrap()
+          .insert(owner.to_string(), installation);
+        installation
+      }
+    };
+
+    // Tokens are valid for an hour, they are renewed a little early
+    let cached = tokens.lock().unwrap().get(&installation).cloned();
+    if let Some(token) = cached.filter(|token| token.expires_at > Utc::now() + Duration::minutes(5))
+    {
+      return Ok(token.token);
+    }
+
+    let jwt = Self::app_jwt(app_id, key)?;
+    let response = self
+      .request(
+        reqwest::Method::POST,
+        &format!("/app/installations/{installation}/access_tokens"),
+        &jwt,
+      )
+      .send()
+      .await
+      .map_err(GitHubClientError::ClientSendError)?;
+    let token = Self::read_response::<ApiAccessToken>(response).await?;
+
+    tokens.lock().unwrap().insert(
+      installation,
+      AccessToken {
+        token: token.token.clone(),
+        expires_at: token.expires_at,
+      },
+    );
+
+    Ok(token.token)
+  }
+}
+
+#[async_trait::async_trait]
+impl GitHubClient for LiveGitHubClient {
+  #[tracing::instrument(name = "github.pulls.get", skip_all, fields(pr_url = %url.0))]
+  async fn pull_request(
+    &self,
+    url: &PrUrl,
+    installation_id: Option<u64>,
+  ) -> Result<PullRequestStatus, GitHubClientError> {
+    let (owner, name, number) =
+      pull_request_path(url).ok_or_else(|| GitHubClientError::NotAPullRequest(url.clone()))?;
+    let token = self.token(&owner, &name, installation_id).await?;
+
+    let pull_request = self
+      .get::<ApiPullRequest>(&format!("/repos/{owner}/{name}/pulls/{number}"), &token)
+      .await?;
+    let reviews = self
+      .get::<Vec<ApiReview>>(
+        &format!("/repos/{owner}/{name}/pulls/{number}/reviews?per_page=100"),
+        &token,
+      )
+      .await?;
+
+    let state = match (pull_request.state.as_str(), pull_request.merged) {
+      (_, Some(true)) => PrState::Merged,
+      ("closed", _) => PrState::Closed,
+      _ => PrState::Open,
+    };
+
+    Ok(PullRequestStatus {
+      url: url.clone(),
+      repository: pull_request.base.repo.full_name,
+      number: pull_request.number,
+      title: pull_request.title,
+      author: pull_request.user.map(|user| user.login),
+      state,
+      merged_by: pull_request.merged_by.map(|user| user.login),
+      merge_commit_sha: match state {
+        PrState::Merged => pull_request.merge_commit_sha,
+        _ => None,
+      },
+      conflicted: super::has_conflicts(pull_request.mergeable_state.as_deref()),
+      commented: pull_request.comments.unwrap_or(0) + pull_request.review_comments.unwrap_or(0) > 0,
+      reviews: reviews
+        .into_iter()
+        .map(|review| Review {
+          state: ReviewState::from_api(&review.state),
+          reviewer: review.user.map(|user| user.login),
+        })
+        .collect(),
+    })
+  }
+
+  #[tracing::instrument(name = "github.branches.rules", skip_all, fields(pr_url = %url.0, branch = %branch))]
+  async fn dismisses_stale_reviews(
+    &self,
+    url: &PrUrl,
+    branch: &str,
+    installation_id: Option<u64>,
+  ) -> Result<bool, GitHubClientError> {
+    let (owner, name, _) =
+      pull_request_path(url).ok_or_else(|| GitHubClientError::NotAPullRequest(url.clone()))?;
+    let token = self.token(&owner, &name, installation_id).await?;
+
+    let rules = self
+      .get::<Vec<ApiBranchRule>>(
+        &format!("/repos/{owner}/{name}/rules/branches/{branch}"),
+        &token,
+      )
+      .await?;
+    if rules.iter().any(|rule| {
+      rule.rule_type == "pull_request" && rule.parameters["dismiss_stale_reviews_on_push"] == true
+    }) {
+      return Ok(true);
+    }
+
+    // Classic branch protection is only visible with administration access to the repository
+    let required_reviews = self
+      .get::<ApiRequiredReviews>(
+        &format!(
+          "/repos/{owner}/{name}/branches/{branch}/protection/required_pull_request_reviews"
+        ),
+        &token,
+      )
+      .await;
+    match required_reviews {
+      Ok(required_reviews) => Ok(required_reviews.dismiss_stale_reviews),
+      Err(GitHubClientError::UnexpectedResponse(
+        reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::FORBIDDEN,
+      )) => Ok(false),
+      Err(err) => Err(err),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  use axum::{
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+  };
+  use jsonwebtoken::{DecodingKey, Validation};
+
+  use super::*;
+  use crate::config::Configuration;
+
+  const PERSONAL_TOKEN: &str = "ghp_personal-token";
+  const INSTALLATION_TOKEN: &str = "ghs_installation-token";
+  const APP_ID: u64 = 1234;
+
+  fn fixture(file_location: &str) -> serde_json::Value {
+    let json = std::fs::read_to_string(file_location).unwrap();
+    serde_json::from_str(&json).unwrap()
+  }
+
+  fn bearer(headers: &HeaderMap) -> String {
+    headers
+      .get("Authorization")
+      .and_then(|value| value.to_str().ok())
+      .and_then(|value| value.strip_prefix("Bearer "))
+      .unwrap_or_default()
+      .to_string()
+  }
+
+  fn signed_by_app(headers: &HeaderMap) -> bool {
+    let public_key = std::fs::read("test_resources/github/api/app-public-key.pem").unwrap();
+    let mut validation = Validation::new(Algorithm::RS256);
+    validation.set_issuer(&[APP_ID.to_string()]);
+    jsonwebtoken::decode::<serde_json::Value>(
+      &bearer(headers),
+      &DecodingKey::from_rsa_pem(&public_key).unwrap(),
+      &validation,
+    )
+    .is_ok()
+  }
+
+  fn api_token(headers: &HeaderMap) -> bool {
+    let token = bearer(headers);
+    token == PERSONAL_TOKEN || token == INSTALLATION_TOKEN
+  }
+
+  #[derive(Default)]
+  struct Calls {
+    installations_looked_up: AtomicUsize,
+    tokens_issued: AtomicUsize,
+  }
+
+  // The parts of GitHub's REST API prmoji calls, counting what the app asked for
+  async fn fake_github(calls: Arc<Calls>) -> String {
+    let lookups = calls.clone();
+    let repo = "/repos/NavidJalali/prmoji-testing";
+    let router = Router::new()
+      .route(
+        &format!("{repo}/pulls/1"),
+        get(|headers: HeaderMap| async move {
+          match api_token(&headers) {
+            true => Ok(Json(fixture("test_resources/github/api/pull.json"))),
+            false => Err(StatusCode::UNAUTHORIZED),
+          }
+        }),
+      )
+      .route(
+        &format!("{repo}/pulls/1/reviews"),
+        get(|headers: HeaderMap| async move {
+          match api_token(&headers) {
+            true => Ok(Json(fixture("test_resources/github/api/reviews.json"))),
+            false => Err(StatusCode::UNAUTHORIZED),
+          }
+        }),
+      )
+      .route(
+        &format!("{repo}/rules/branches/main"),
+        get(|| async {
+          Json(serde_json::json!([
+            { "type": "deletion", "ruleset_id": 1 },
+            {
+              "type": "pull_request",
+              "ruleset_id": 2,
+              "parameters": { "dismiss_stale_reviews_on_push": true }
+            }
+          ]))
+        }),
+      )
+      .route(
+        &format!("{repo}/rules/branches/{{branch}}"),
+        get(|| async { Json(serde_json::json!([])) }),
+      )
+      .route(
+        &format!("{repo}/branches/protected/protection/required_pull_request_reviews"),
+        get(|| async { Json(serde_json::json!({ "dismiss_stale_reviews": true })) }),
+      )
+      .route(
+        &format!("{repo}/installation"),
+        get(|headers: HeaderMap| async move {
+          if !signed_by_app(&headers) {
+            return Err(StatusCode::UNAUTHORIZED);
+          }
+          lookups
+            .installations_looked_up
+            .fetch_add(1, Ordering::SeqCst);
+          Ok(Json(serde_json::json!({ "id": 42 })))
+        }),
+      )
+      .route(
+        "/app/installations/42/access_tokens",
+        post(|headers: HeaderMap| async move {
+          if !signed_by_app(&headers) {
+            return Err(StatusCode::UNAUTHORIZED);
+          }
+          calls.tokens_issued.fetch_add(1, Ordering::SeqCst);
+          Ok(Json(serde_json::json!({
+            "token": INSTALLATION_TOKEN,
+            "expires_at": (Utc::now() + Duration::hours(1)).to_rfc3339(),
+          })))
+        }),
+      );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{addr}")
+  }
+
+  fn client(api_url: String, auth: GithubAuth) -> LiveGitHubClient {
+    let mut config = Configuration::for_tests().github;
+    config.api_url = api_url;
+    LiveGitHubClient::new(&config, &auth).unwrap()
+  }
+
+  fn app() -> GithubAuth {
+    GithubAuth::App {
+      app_id: APP_ID,
+      private_key: std::fs::read_to_string("test_resources/github/api/app-private-key.pem")
+        .unwrap(),
+    }
+  }
+
+  fn merged_pr() -> PullRequestStatus {
+    PullRequestStatus {
+      url: "https://github.com/NavidJalali/prmoji-testing/pull/1".into(),
+      repository: "NavidJalali/prmoji-testing".to_string(),
+      number: 1,
+      title: "Update README.md title!!!!".to_string(),
+      author: Some("NavidJalali".to_string()),
+      state: PrState::Merged,
+      merged_by: Some("NavidJalali".to_string()),
+      merge_commit_sha: Some("41b41582d00218775965fea79173ef2be360bf17".to_string()),
+      conflicted: None,
+      commented: true,
+      reviews: vec![
+        Review {
+          state: ReviewState::ChangesRequested,
+          reviewer: Some("rhalm".to_string()),
+        },
+        Review {
+          state: ReviewState::Approved,
+          reviewer: Some("rhalm".to_string()),
+        },
+      ],
+    }
+  }
+
+  #[test]
+  fn finds_the_repository_of_pull_request_urls() {
+    assert_eq!(
+      pull_request_path(&"https://github.com/NavidJalali/prmoji-testing/pull/1".into()),
+      Some(("NavidJalali".to_string(), "prmoji-testing".to_string(), 1))
+    );
+    assert_eq!(
+      pull_request_path(&"https://github.com/NavidJalali/prmoji-testing/issues/1".into()),
+      None
+    );
+  }
+
+  #[test]
+  fn lists_every_kind_of_event_once() {
+    let mut status = merged_pr();
+    status.reviews.push(Review {
+      state: ReviewState::Approved,
+      reviewer: Some("NavidJalali".to_string()),
+    });
+
+    assert_eq!(
+      status.events(),
+      vec![
+        ("commented", "github".to_string()),
+        ("changes_requested", "rhalm".to_string()),
+        ("approved", "rhalm".to_string()),
+        ("merged", "NavidJalali".to_string()),
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn fetches_pull_requests_with_a_token() {
+    let api_url = fake_github(Arc::default()).await;
+    let github = client(
+      api_url,
+      GithubAuth::Token {
+        token: PERSONAL_TOKEN.to_string(),
+      },
+    );
+
+    let status = github
+      .pull_request(
+        &"https://github.com/NavidJalali/prmoji-testing/pull/1".into(),
+        None,
+      )
+      .await
+      .unwrap();
+
+    assert_eq!(status, merged_pr());
+  }
+
+  #[tokio::test]
+  async fn fetches_pull_requests_as_an_app_installation() {
+    let calls = Arc::new(Calls::default());
+    let github = client(fake_github(calls.clone()).await, app());
+    let url = "https://github.com/NavidJalali/prmoji-testing/pull/1".into();
+
+    assert_eq!(github.pull_request(&url, None).await.unwrap(), merged_pr());
+    assert_eq!(github.pull_request(&url, None).await.unwrap(), merged_pr());
+    assert_eq!(calls.installations_looked_up.load(Ordering::SeqCst), 1);
+    assert_eq!(calls.tokens_issued.load(Ordering::SeqCst), 1);
+  }
+
+  #[tokio::test]
+  async fn uses_installations_it_was_told_about() {
+    let calls = Arc::new(Calls::default());
+    let github = client(fake_github(calls.clone()).await, app());
+    let url = "https://github.com/NavidJalali/prmoji-testing/pull/1".into();
+
+    assert_eq!(
+      github.pull_request(&url, Some(42)).await.unwrap(),
+      merged_pr()
+    );
+    assert_eq!(calls.installations_looked_up.load(Ordering::SeqCst), 0);
+    assert_eq!(calls.tokens_issued.load(Ordering::SeqCst), 1);
+  }
+
+  #[tokio::test]
+  async fn finds_out_whether_pushes_dismiss_approvals() {
+    let api_url = fake_github(Arc::default()).await;
+    let github = client(
+      api_url,
+      GithubAuth::Token {
+        token: PERSONAL_TOKEN.to_string(),
+      },
+    );
+    let url = "https://github.com/NavidJalali/prmoji-testing/pull/1".into();
+
+    for (branch, dismisses) in [("main", true), ("protected", true), ("develop", false)] {
+      assert_eq!(
+        github
+          .dismisses_stale_reviews(&url, branch, None)
+          .await
+          .unwrap(),
+        dismisses,
+        "{branch}"
+      );
+    }
+  }
+
+  #[tokio::test]
+  async fn reports_missing_pull_requests() {
+    let api_url = fake_github(Arc::default()).await;
+    let github = client(
+      api_url,
+      GithubAuth::Token {
+        token: PERSONAL_TOKEN.to_string(),
+      },
+    );
+
+    let result = github
+      .pull_request(
+        &"https://github.com/NavidJalali/prmoji-testing/pull/2".into(),
+        None,
+      )
+      .await;
+
+    assert_eq!(result.unwrap_err().code(), "http_404");
+  }
+}
@@ -0,0 +1,84 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{
+  atomic::{AtomicUsize, Ordering},
+  Arc, Mutex,
+};
+
+use reqwest::StatusCode;
+
+use super::client::{pull_request_path, GitHubClient, GitHubClientError, PullRequestStatus};
+use crate::models::PrUrl;
+
+// Answers with whatever pull requests it was given, for tests and demo mode
+#[derive(Clone, Default)]
+pub struct InMemoryGitHubClient {
+  pull_requests: Arc<Mutex<HashMap<PrUrl, PullRequestStatus>>>,
+  // `owner/name` of the repositories whose pushes dismiss approvals, on every branch
+  dismissing_stale_reviews: Arc<Mutex<HashSet<String>>>,
+  lookups: Arc<AtomicUsize>,
+}
+
+impl InMemoryGitHubClient {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  #[allow(dead_code)]
+  pub fn set_pull_request(&self, status: PullRequestStatus) {
+    self
+      .pull_requests
+      .lock()
+      .unwrap()
+      .insert(status.url.clone(), status);
+  }
+
+  // How many pull requests were looked up so far
+  #[cfg(test)]
+  pub fn lookups(&self) -> usize {
+    self.lookups.load(Ordering::SeqCst)
+  }
+
+  #[allow(dead_code)]
+  pub fn set_dismisses_stale_reviews(&self, repository: &str) {
+    self
+      .dismissing_stale_reviews
+      .lock()
+      .unwrap()
+      .insert(repository.to_string());
+  }
+}
+
+#[async_trait::async_trait]
+impl GitHubClient for InMemoryGitHubClient {
+  async fn pull_request(
+    &self,
+    url: &PrUrl,
+    _installation_id: Option<u64>,
+  ) -> Result<PullRequestStatus, GitHubClientError> {
+    self.lookups.fetch_add(1, Ordering::SeqCst);
+    self
+      .pull_requests
+      .lock()
+      .unwrap()
+      .get(url)
+      .cloned()
+      .ok_or(GitHubClientError::UnexpectedResponse(StatusCode::NOT_FOUND))
+  }
+
+  async fn dismisses_stale_reviews(
+    &self,
+    url: &PrUrl,
+    _branch: &str,
+    _installation_id: Option<u64>,
+  ) -> Result<bool, GitHubClientError> {
+    let (owner, name, _) =
+      pull_request_path(url).ok_or_else(|| GitHubClientError::NotAPullRequest(url.clone()))?;
+    Ok(
+      self
+        .dismissing_stale_reviews
+        .lock()
+        .unwrap()
+        .contains(&format!("{owner}/{name}")),
+    )
+  }
+}
@@ -0,0 +1,1004 @@
+pub mod client;
+pub mod in_memory;
+
+use chrono::{DateTime, Utc};
+use serde::{self, Deserialize};
+
+use crate::models::{PrMetadata, PrUrl};
+/*
+ * We are only interested in the following events:
+ * - A PR is closed -> pull_request
+ * - A PR is merged -> pull_request
+ * - A comment is added to a PR -> issue_comment
+ * - PR review comment is added -> pull_request_review_comment
+ * - A review is added to a PR -> pull_request_review
+ * - A review is approved -> pull_request_review
+ * - A review with only comments is submitted -> pull_request_review, together with its inline comments
+ * - Auto-merge is enabled or disabled -> pull_request
+ * - A PR enters or leaves the merge queue -> merge_group
+ * - The merge commit of a PR is deployed -> deployment_status
+ * - A PR has merge conflicts, or not anymore -> pull_request, with any action once GitHub knows
+ * - New commits are pushed to a PR -> pull_request
+ * - A review is dismissed, e.g. a stale approval after new commits -> pull_request_review
+ * - The GitHub App is installed on or removed from repositories -> installation, installation_repositories
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventTypeHeader {
+  PullRequest,
+  IssueComment,
+  PullRequestReview,
+  PullRequestReviewComment,
+  MergeGroup,
+  DeploymentStatus,
+  Installation,
+  InstallationRepositories,
+}
+
+impl EventTypeHeader {
+  pub fn from_raw(s: &str) -> Option<Self> {
+    match s {
+      "pull_request" => Some(EventTypeHeader::PullRequest),
+      "issue_comment" => Some(EventTypeHeader::IssueComment),
+      "pull_request_review" => Some(EventTypeHeader::PullRequestReview),
+      "pull_request_review_comment" => Some(EventTypeHeader::PullRequestReviewComment),
+      "merge_group" => Some(EventTypeHeader::MergeGroup),
+      "deployment_status" => Some(EventTypeHeader::DeploymentStatus),
+      "installation" => Some(EventTypeHeader::Installation),
+      "installation_repositories" => Some(EventTypeHeader::InstallationRepositories),
+      _ => None,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      EventTypeHeader::PullRequest => "pull_request",
+      EventTypeHeader::IssueComment => "issue_comment",
+      EventTypeHeader::PullRequestReview => "pull_request_review",
+      EventTypeHeader::PullRequestReviewComment => "pull_request_review_comment",
+      EventTypeHeader::MergeGroup => "merge_group",
+      EventTypeHeader::DeploymentStatus => "deployment_status",
+      EventTypeHeader::Installation => "installation",
+      EventTypeHeader::InstallationRepositories => "installation_repositories",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionField {
+  Opened,      // PR opened
+  Created,     // PR comment created, app installed, deployment status created
+  Closed,      // PR closed, PR merged
+  Reopened,    // PR reopened
+  Submitted,   // PR review submitted, PR approved
+  Dismissed,   // PR review dismissed
+  Synchronize, // New commits pushed to a PR
+  AutoMergeEnabled,
+  AutoMergeDisabled,
+  ChecksRequested, // Merge group created for a PR entering the merge queue
+  Destroyed,       // Merge group merged or dropped
+  Deleted,         // App uninstalled
+  Suspend,         // App suspended
+  Unsuspend,       // App unsuspended
+  Added,           // Repositories added to an installation
+  Removed,         // Repositories removed from an installation
+  Other,           // Other action. We don't care about it.
+}
+
+impl<'de> Deserialize<'de> for ActionField {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    let option_string = Option::<String>::deserialize(deserializer)?;
+    match option_string {
+      Some(string) => match string.as_str() {
+        "opened" => Ok(ActionField::Opened),
+        "created" => Ok(ActionField::Created),
+        "closed" => Ok(ActionField::Closed),
+        "reopened" => Ok(ActionField::Reopened),
+        "submitted" => Ok(ActionField::Submitted),
+        "dismissed" => Ok(ActionField::Dismissed),
+        "synchronize" => Ok(ActionField::Synchronize),
+        "auto_merge_enabled" => Ok(ActionField::AutoMergeEnabled),
+        "auto_merge_disabled" => Ok(ActionField::AutoMergeDisabled),
+        "checks_requested" => Ok(ActionField::ChecksRequested),
+        "destroyed" => Ok(ActionField::Destroyed),
+        "deleted" => Ok(ActionField::Deleted),
+        "suspend" => Ok(ActionField::Suspend),
+        "unsuspend" => Ok(ActionField::Unsuspend),
+        "added" => Ok(ActionField::Added),
+        "removed" => Ok(ActionField::Removed),
+        _ => Ok(ActionField::Other),
+      },
+      None => Ok(ActionField::Other),
+    }
+  }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Href {
+  href: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PullRequestLinks {
+  html: Href,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct User {
+  login: String,
+}
+
+impl User {
+  pub fn login(&self) -> &str {
+    &self.login
+  }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PullRequest {
+  _links: PullRequestLinks,
+  number: u32,
+  merged_at: Option<String>,
+  merge_commit_sha: Option<String>,
+  mergeable_state: Option<String>,
+  user: Option<User>,
+  title: String,
+  base: Option<Base>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Base {
+  #[serde(rename = "ref")]
+  branch: String,
+}
+
+// Whether a PR has merge conflicts, None while GitHub is still working it out
+pub fn has_conflicts(mergeable_state: Option<&str>) -> Option<bool> {
+  match mergeable_state? {
+    "dirty" => Some(true),
+    "unknown" => None,
+    _ => Some(false),
+  }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+  full_name: String,
+  // Missing from the repositories listed by installation events
+  html_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Review {
+  id: u64,
+  state: String,
+  user: Option<User>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+  user: Option<User>,
+  // Set on the inline comments of a review
+  pull_request_review_id: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IssuePullRequest {
+  html_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+  number: u32,
+  title: String,
+  user: Option<User>,
+  pull_request: Option<IssuePullRequest>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MergeGroup {
+  // e.g. `refs/heads/gh-readonly-queue/main/pr-2-cabe450a30a641d3e5ae508cee7488aebf335eeb`
+  head_ref: String,
+}
+
+impl MergeGroup {
+  // The number of the PR the merge group was created for
+  fn pr_number(&self) -> Option<u32> {
+    let (_, branch) = self.head_ref.rsplit_once('/')?;
+    let (number, _) = branch.strip_prefix("pr-")?.split_once('-')?;
+    number.parse().ok()
+  }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Deployment {
+  sha: String,
+  environment: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeploymentStatus {
+  // `success` once the deployment is done, or e.g. `pending`, `in_progress` and `failure`
+  state: String,
+}
+
+// Sent with every event delivered to a GitHub App
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Installation {
+  id: u64,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RawGitHubEvent {
+  action: ActionField,
+  pull_request: Option<PullRequest>,
+  // Missing from installation events, which list `repositories` instead
+  repository: Option<Repository>,
+  review: Option<Review>,
+  comment: Option<Comment>,
+  issue: Option<Issue>,
+  sender: Option<User>,
+  installation: Option<Installation>,
+  merge_group: Option<MergeGroup>,
+  deployment: Option<Deployment>,
+  deployment_status: Option<DeploymentStatus>,
+  // Why a merge group was destroyed: `merged`, `invalidated` or `dequeued`
+  reason: Option<String>,
+  repositories: Option<Vec<Repository>>,
+  repositories_added: Option<Vec<Repository>>,
+  repositories_removed: Option<Vec<Repository>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallationChange {
+  // The app can now read these repositories, with this installation
+  Added {
+    installation_id: u64,
+    repositories: Vec<String>,
+  },
+  RepositoriesRemoved {
+    installation_id: u64,
+    repositories: Vec<String>,
+  },
+  // The app was uninstalled or suspended and can't read any of its repositories
+  Removed {
+    installation_id: u64,
+  },
+}
+
+impl RawGitHubEvent {
+  fn get_pr_url(&self) -> Option<PrUrl> {
+    self
+      .pull_request
+      .as_ref()
+      .map(|pr| &pr._links.html.href)
+      .or(
+        self
+          .issue
+          .as_ref()
+          .and_then(|issue| issue.pull_request.as_ref())
+          .and_then(|pr| pr.html_url.as_ref()),
+      )
+      .map(|href| PrUrl(href.to_string()))
+      .or_else(|| self.merge_group_pr_url())
+  }
+
+  // Merge groups only name their PR in the branch they were created on
+  fn merge_group_pr_url(&self) -> Option<PrUrl> {
+    let number = self.merge_group.as_ref()?.pr_number()?;
+    let repository = self.repository.as_ref()?.html_url.as_ref()?;
+    Some(PrUrl(format!("{repository}/pull/{number}")))
+  }
+
+  // Every event about a pull request carries its title and author, comments carry them on the issue
+  pub fn metadata(&self, received_at: DateTime<Utc>) -> Option<PrMetadata> {
+    let url = self.get_pr_url()?;
+    let (number, title, user, merge_commit_sha) = match (&self.pull_request, &self.issue) {
+      (Some(pr), _) => (
+        pr.number,
+        &pr.title,
+        &pr.user,
+        pr.merged_at.as_ref().and(pr.merge_commit_sha.clone()),
+      ),
+      (None, Some(issue)) => (issue.number, &issue.title, &issue.user, None),
+      (None, None) => return None,
+    };
+
+    Some(PrMetadata {
+      url,
+      repository: self.repository.as_ref()?.full_name.clone(),
+      number,
+      title: title.clone(),
+      author: user.as_ref().map(|user| user.login.clone()),
+      merge_commit_sha,
+      updated_at: received_at,
+    })
+  }
+
+  // Whether the PR of a pull_request event has merge conflicts, if GitHub knew when it was sent
+  pub fn merge_conflicts(&self, event_type: EventTypeHeader) -> Option<(PrUrl, bool)> {
+    match (event_type, &self.action) {
+      (EventTypeHeader::PullRequest, ActionField::Closed) => None,
+      (EventTypeHeader::PullRequest, _) => {
+        let pr = self.pull_request.as_ref()?;
+        let conflicted = has_conflicts(pr.mergeable_state.as_deref())?;
+        Some((self.get_pr_url()?, conflicted))
+      }
+      _ => None,
+    }
+  }
+
+  // The commit a deployment finished deploying, which may be the merge commit of a PR
+  pub fn deployed_commit(&self, event_type: EventTypeHeader) -> Option<&str> {
+    match (event_type, &self.action) {
+      (EventTypeHeader::DeploymentStatus, ActionField::Created) => {
+        let status = self.deployment_status.as_ref()?;
+        (status.state == "success").then_some(self.deployment.as_ref()?.sha.as_str())
+      }
+      _ => None,
+    }
+  }
+
+  // The branch the PR is going to be merged into
+  pub fn base_branch(&self) -> Option<&str> {
+    let base = self.pull_request.as_ref()?.base.as_ref()?;
+    Some(base.branch.as_str())
+  }
+
+  // `owner/name` of the repository the event happened in
+  pub fn repository(&self) -> Option<&str> {
+    self
+      .repository
+      .as_ref()
+      .map(|repository| repository.full_name.as_str())
+  }
+
+  // The installation that can read the repository of this event, if it was delivered to a GitHub App
+  pub fn installation(&self) -> Option<(u64, String)> {
+    let installation = self.installation.as_ref()?;
+    let repository = self.repository.as_ref()?;
+    Some((installation.id, repository.full_name.clone()))
+  }
+
+  pub fn installation_change(&self, event_type: EventTypeHeader) -> Option<InstallationChange> {
+    let installation_id = self.installation.as_ref()?.id;
+    let names = |repositories: &Option<Vec<Repository>>| {
+      repositories
+        .iter()
+        .flatten()
+        .map(|repository| repository.full_name.clone())
+        .collect::<Vec<_>>()
+    };
+
+    match (event_type, &self.action) {
+      (EventTypeHeader::Installation, ActionField::Created | ActionField::Unsuspend) => {
+        Some(InstallationChange::Added {
+          installation_id,
+          repositories: names(&self.repositories),
+        })
+      }
+      (EventTypeHeader::Installation, ActionField::Deleted | ActionField::Suspend) => {
+        Some(InstallationChange::Removed { installation_id })
+      }
+      (EventTypeHeader::InstallationRepositories, ActionField::Added) => {
+        Some(InstallationChange::Added {
+          installation_id,
+          repositories: names(&self.repositories_added),
+        })
+      }
+      (EventTypeHeader::InstallationRepositories, ActionField::Removed) => {
+        Some(InstallationChange::RepositoriesRemoved {
+          installation_id,
+          repositories: names(&self.repositories_removed),
+        })
+      }
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitHubEvent {
+  pub pr_url: PrUrl,
+  pub event_type: GitHubEventType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitHubEventType {
+  Closed {
+    closed_by: User,
+  },
+  Merged {
+    merged_by: User,
+  },
+  // Only recorded, a reopened PR is open again
+  Reopened {
+    reopened_by: User,
+  },
+  // The review a review or its inline comments belong to, so they only count as one comment
+  Commented {
+    commenter: User,
+    review_id: Option<u64>,
+  },
+  ChangesRequested {
+    reviewer: User,
+  },
+  Approved {
+    approver: User,
+  },
+  AutoMergeEnabled {
+    enabled_by: User,
+  },
+  AutoMergeDisabled {
+    disabled_by: User,
+  },
+  MergeQueued {
+    queued_by: User,
+  },
+  // Left the merge queue without being merged
+  MergeDequeued {
+    dequeued_by: User,
+  },
+  Deployed {
+    deployed_by: User,
+    environment: String,
+  },
+  // New commits were pushed
+  Updated {
+    pushed_by: User,
+  },
+  // Also sent when branch protection dismisses stale approvals. GitHub only says who wrote the
+  // review, what it was before is in the history
+  ReviewDismissed {
+    reviewer: User,
+  },
+}
+
+impl GitHubEventType {
+  pub fn name(&self) -> &'static str {
+    match self {
+      GitHubEventType::Closed { .. } => "closed",
+      GitHubEventType::Merged { .. } => "merged",
+      GitHubEventType::Reopened { .. } => "reopened",
+      GitHubEventType::Commented { .. } => "commented",
+      GitHubEventType::ChangesRequested { .. } => "changes_requested",
+      GitHubEventType::Approved { .. } => "approved",
+      GitHubEventType::AutoMergeEnabled { .. } => "auto_merge_enabled",
+      GitHubEventType::AutoMergeDisabled { .. } => "auto_merge_disabled",
+      GitHubEventType::MergeQueued { .. } => "merge_queued",
+      GitHubEventType::MergeDequeued { .. } => "merge_dequeued",
+      GitHubEventType::Deployed { .. } => "deployed",
+      GitHubEventType::Updated { .. } => "updated",
+      GitHubEventType::ReviewDismissed { .. } => "review_dismissed",
+    }
+  }
+
+  // The event type in the audit log, deployments also say where they went, e.g. `deployed:production`
+  pub fn recorded_name(&self) -> String {
+    match self {
+      GitHubEventType::Deployed { environment, .. } => format!("deployed:{environment}"),
+      _ => self.name().to_string(),
+    }
+  }
+
+  pub fn actor(&self) -> &User {
+    match self {
+      GitHubEventType::Closed { closed_by } => closed_by,
+      GitHubEventType::Merged { merged_by } => merged_by,
+      GitHubEventType::Reopened { reopened_by } => reopened_by,
+      GitHubEventType::Commented { commenter, .. } => commenter,
+      GitHubEventType::ChangesRequested { reviewer } => reviewer,
+      GitHubEventType::Approved { approver } => approver,
+      GitHubEventType::AutoMergeEnabled { enabled_by } => enabled_by,
+      GitHubEventType::AutoMergeDisabled { disabled_by } => disabled_by,
+      GitHubEventType::MergeQueued { queued_by } => queued_by,
+      GitHubEventType::MergeDequeued { dequeued_by } => dequeued_by,
+      GitHubEventType::Deployed { deployed_by, .. } => deployed_by,
+      GitHubEventType::Updated { pushed_by } => pushed_by,
+      GitHubEventType::ReviewDismissed { reviewer } => reviewer,
+    }
+  }
+}
+
+impl GitHubEvent {
+  pub fn new(pr_url: PrUrl, event_type: GitHubEventType) -> Self {
+    Self { pr_url, event_type }
+  }
+
+  #[tracing::instrument(name = "parse_github_event", skip(raw_event))]
+  pub fn from_raw(event_type: EventTypeHeader, raw_event: RawGitHubEvent) -> Option<Self> {
+    let pr_url = raw_event.get_pr_url()?;
+    let event_type = match (event_type, &raw_event.action) {
+      (EventTypeHeader::IssueComment, ActionField::Created) => {
+        let commenter = raw_event.comment?.user?;
+        Some(GitHubEventType::Commented {
+          commenter,
+          review_id: None,
+        })
+      }
+      (EventTypeHeader::PullRequestReviewComment, ActionField::Created) => {
+        let comment = raw_event.comment?;
+        Some(GitHubEventType::Commented {
+          commenter: comment.user?,
+          review_id: comment.pull_request_review_id,
+        })
+      }
+      (EventTypeHeader::PullRequest, ActionField::Closed) => {
+        let merged_at = raw_event.pull_request?.merged_at;
+        let sender = raw_event.sender?;
+        match merged_at {
+          Some(_) => Some(GitHubEventType::Merged { merged_by: sender }),
+          None => Some(GitHubEventType::Closed { closed_by: sender }),
+        }
+      }
+      (EventTypeHeader::PullRequest, ActionField::Reopened) => Some(GitHubEventType::Reopened {
+        reopened_by: raw_event.sender?,
+      }),
+      (EventTypeHeader::PullRequest, ActionField::AutoMergeEnabled) => {
+        Some(GitHubEventType::AutoMergeEnabled {
+          enabled_by: raw_event.sender?,
+        })
+      }
+      (EventTypeHeader::PullRequest, ActionField::AutoMergeDisabled) => {
+        Some(GitHubEventType::AutoMergeDisabled {
+          disabled_by: raw_event.sender?,
+        })
+      }
+      (EventTypeHeader::PullRequest, ActionField::Synchronize) => Some(GitHubEventType::Updated {
+        pushed_by: raw_event.sender?,
+      }),
+      (EventTypeHeader::PullRequestReview, ActionField::Dismissed) => {
+        Some(GitHubEventType::ReviewDismissed {
+          reviewer: raw_event.review?.user?,
+        })
+      }
+      (EventTypeHeader::MergeGroup, ActionField::ChecksRequested) => {
+        Some(GitHubEventType::MergeQueued {
+          queued_by: raw_event.sender?,
+        })
+      }
+      // A merged PR gets its reaction from the pull_request event
+      (EventTypeHeader::MergeGroup, ActionField::Destroyed) => match raw_event.reason.as_deref() {
+        Some("merged") => None,
+        _ => Some(GitHubEventType::MergeDequeued {
+          dequeued_by: raw_event.sender?,
+        }),
+      },
+      (EventTypeHeader::PullRequestReview, ActionField::Submitted) => {
+        let review = raw_event.review?;
+        let user = review.user?;
+        let state = review.state;
+        match state.as_str() {
+          "changes_requested" => Some(GitHubEventType::ChangesRequested { reviewer: user }),
+          "approved" => Some(GitHubEventType::Approved { approver: user }),
+          "commented" => Some(GitHubEventType::Commented {
+            commenter: user,
+            review_id: Some(review.id),
+          }),
+          _ => None,
+        }
+      }
+      _ => None,
+    };
+
+    event_type.map(|event_type| Self::new(pr_url, event_type))
+  }
+
+  // Deployments don't name a PR, it is the one whose merge commit was deployed
+  pub fn from_deployment(pr_url: PrUrl, raw_event: RawGitHubEvent) -> Option<Self> {
+    let event_type = GitHubEventType::Deployed {
+      deployed_by: raw_event.sender?,
+      environment: raw_event.deployment?.environment,
+    };
+    Some(Self::new(pr_url, event_type))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+
+  fn load_raw(file_location: &str) -> RawGitHubEvent {
+    let json = std::fs::read_to_string(file_location).unwrap();
+    serde_json::from_str(&json).unwrap()
+  }
+
+  use super::*;
+  #[test]
+  fn can_detect_approved() {
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/approved.json");
+    let header = EventTypeHeader::from_raw("pull_request_review").unwrap();
+    let event = GitHubEvent::from_raw(header, raw_event).unwrap();
+
+    assert_eq!(
+      event,
+      GitHubEvent::new(
+        "https://github.com/NavidJalali/prmoji-testing/pull/2".into(),
+        GitHubEventType::Approved {
+          approver: User {
+            login: "rhalm".to_string()
+          },
+        }
+      )
+    );
+  }
+
+  #[test]
+  fn can_detect_changes_requested() {
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/request-changes.json");
+    let header = EventTypeHeader::from_raw("pull_request_review").unwrap();
+    let event = GitHubEvent::from_raw(header, raw_event).unwrap();
+
+    assert_eq!(
+      event,
+      GitHubEvent::new(
+        "https://github.com/NavidJalali/prmoji-testing/pull/2".into(),
+        GitHubEventType::ChangesRequested {
+          reviewer: User {
+            login: "rhalm".to_string()
+          },
+        }
+      )
+    );
+  }
+
+  #[test]
+  fn can_detect_pushes_and_dismissed_reviews() {
+    let pull_request = EventTypeHeader::from_raw("pull_request").unwrap();
+    let event = GitHubEvent::from_raw(
+      pull_request,
+      load_raw("test_resources/github/synchronize.json"),
+    )
+    .unwrap();
+    assert_eq!(
+      event.event_type,
+      GitHubEventType::Updated {
+        pushed_by: User {
+          login: "NavidJalali".to_string()
+        },
+      }
+    );
+
+    let review = EventTypeHeader::from_raw("pull_request_review").unwrap();
+    let event = GitHubEvent::from_raw(
+      review,
+      load_raw("test_resources/github/review-dismissed.json"),
+    )
+    .unwrap();
+    assert_eq!(
+      event.event_type,
+      GitHubEventType::ReviewDismissed {
+        reviewer: User {
+          login: "rhalm".to_string()
+        },
+      }
+    );
+    assert_eq!(
+      load_raw("test_resources/github/review-dismissed.json").base_branch(),
+      Some("main")
+    );
+  }
+
+  #[test]
+  fn can_detect_commented() {
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/comment.json");
+    let header = EventTypeHeader::from_raw("issue_comment").unwrap();
+    let event = GitHubEvent::from_raw(header, raw_event).unwrap();
+
+    assert_eq!(
+      event,
+      GitHubEvent::new(
+        "https://github.com/NavidJalali/prmoji-testing/pull/1".into(),
+        GitHubEventType::Commented {
+          commenter: User {
+            login: "NavidJalali".to_string()
+          },
+          review_id: None,
+        }
+      )
+    );
+  }
+
+  #[test]
+  fn reviews_with_only_comments_are_comments() {
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/review-commented.json");
+    let header = EventTypeHeader::from_raw("pull_request_review").unwrap();
+    let review = GitHubEvent::from_raw(header, raw_event).unwrap();
+
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/review-comment.json");
+    let header = EventTypeHeader::from_raw("pull_request_review_comment").unwrap();
+    let inline_comment = GitHubEvent::from_raw(header, raw_event).unwrap();
+
+    let commented = GitHubEvent::new(
+      "https://github.com/NavidJalali/prmoji-testing/pull/2".into(),
+      GitHubEventType::Commented {
+        commenter: User {
+          login: "rhalm".to_string(),
+        },
+        review_id: Some(1760999599),
+      },
+    );
+    assert_eq!(review, commented);
+    assert_eq!(inline_comment, commented);
+  }
+
+  #[test]
+  fn can_detect_merged() {
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/merge.json");
+    let header = EventTypeHeader::from_raw("pull_request").unwrap();
+    let event = GitHubEvent::from_raw(header, raw_event).unwrap();
+
+    assert_eq!(
+      event,
+      GitHubEvent::new(
+        "https://github.com/NavidJalali/prmoji-testing/pull/1".into(),
+        GitHubEventType::Merged {
+          merged_by: User {
+            login: "NavidJalali".to_string()
+          },
+        }
+      )
+    );
+  }
+
+  #[test]
+  fn can_detect_auto_merge() {
+    let json = std::fs::read_to_string("test_resources/github/merge.json").unwrap();
+    let mut payload: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let header = EventTypeHeader::from_raw("pull_request").unwrap();
+    let mut event = |action: &str| {
+      payload["action"] = action.into();
+      let raw_event: RawGitHubEvent = serde_json::from_value(payload.clone()).unwrap();
+      GitHubEvent::from_raw(header, raw_event).unwrap().event_type
+    };
+    let navid = || User {
+      login: "NavidJalali".to_string(),
+    };
+
+    assert_eq!(
+      event("auto_merge_enabled"),
+      GitHubEventType::AutoMergeEnabled {
+        enabled_by: navid()
+      }
+    );
+    assert_eq!(
+      event("auto_merge_disabled"),
+      GitHubEventType::AutoMergeDisabled {
+        disabled_by: navid()
+      }
+    );
+  }
+
+  #[test]
+  fn can_detect_merge_queue_changes() {
+    let header = EventTypeHeader::from_raw("merge_group").unwrap();
+    let url: PrUrl = "https://github.com/NavidJalali/prmoji-testing/pull/1".into();
+    let navid = || User {
+      login: "NavidJalali".to_string(),
+    };
+
+    let raw_event = load_raw("test_resources/github/merge-group-checks-requested.json");
+    assert_eq!(
+      GitHubEvent::from_raw(header, raw_event),
+      Some(GitHubEvent::new(
+        url.clone(),
+        GitHubEventType::MergeQueued { queued_by: navid() }
+      ))
+    );
+
+    let raw_event = load_raw("test_resources/github/merge-group-destroyed.json");
+    assert_eq!(
+      GitHubEvent::from_raw(header, raw_event.clone()),
+      Some(GitHubEvent::new(
+        url,
+        GitHubEventType::MergeDequeued {
+          dequeued_by: navid()
+        }
+      ))
+    );
+
+    let merged = RawGitHubEvent {
+      reason: Some("merged".to_string()),
+      ..raw_event
+    };
+    assert_eq!(GitHubEvent::from_raw(header, merged), None);
+  }
+
+  #[test]
+  fn reads_successful_deployments() {
+    let json = std::fs::read_to_string("test_resources/github/deployment-status.json").unwrap();
+    let mut payload: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let header = EventTypeHeader::from_raw("deployment_status").unwrap();
+    let raw_event: RawGitHubEvent = serde_json::from_value(payload.clone()).unwrap();
+
+    assert_eq!(
+      raw_event.deployed_commit(header),
+      Some("41b41582d00218775965fea79173ef2be360bf17")
+    );
+    assert_eq!(
+      GitHubEvent::from_deployment(
+        "https://github.com/NavidJalali/prmoji-testing/pull/1".into(),
+        raw_event
+      )
+      .unwrap()
+      .event_type
+      .recorded_name(),
+      "deployed:production"
+    );
+
+    payload["deployment_status"]["state"] = "in_progress".into();
+    let raw_event: RawGitHubEvent = serde_json::from_value(payload).unwrap();
+    assert_eq!(raw_event.deployed_commit(header), None);
+  }
+
+  #[test]
+  fn reads_merge_conflicts_of_open_prs() {
+    let header = EventTypeHeader::from_raw("pull_request").unwrap();
+    let url = PrUrl::from("https://github.com/NavidJalali/prmoji-testing/pull/1");
+    let raw_event = |file_location: &str| -> RawGitHubEvent {
+      serde_json::from_str(&std::fs::read_to_string(file_location).unwrap()).unwrap()
+    };
+
+    assert_eq!(
+      raw_event("test_resources/github/synchronize.json").merge_conflicts(header),
+      Some((url, true))
+    );
+    assert_eq!(
+      raw_event("test_resources/github/merge.json").merge_conflicts(header),
+      None
+    );
+    assert_eq!(has_conflicts(Some("blocked")), Some(false));
+    assert_eq!(has_conflicts(Some("unknown")), None);
+  }
+
+  #[test]
+  fn can_detect_closed() {
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/close.json");
+    let header = EventTypeHeader::from_raw("pull_request").unwrap();
+    let event = GitHubEvent::from_raw(header, raw_event).unwrap();
+
+    assert_eq!(
+      event,
+      GitHubEvent::new(
+        "https://github.com/NavidJalali/prmoji-testing/pull/1".into(),
+        GitHubEventType::Closed {
+          closed_by: User {
+            login: "NavidJalali".to_string()
+          },
+        }
+      )
+    );
+  }
+
+  #[test]
+  fn can_detect_reopened() {
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/reopen.json");
+    let header = EventTypeHeader::from_raw("pull_request").unwrap();
+    let event = GitHubEvent::from_raw(header, raw_event).unwrap();
+
+    assert_eq!(
+      event.event_type,
+      GitHubEventType::Reopened {
+        reopened_by: User {
+          login: "NavidJalali".to_string()
+        },
+      }
+    );
+  }
+
+  fn received_at() -> DateTime<Utc> {
+    "2023-10-03T20:20:51Z".parse().unwrap()
+  }
+
+  #[test]
+  fn reads_metadata_from_pull_requests() {
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/approved.json");
+
+    assert_eq!(
+      raw_event.metadata(received_at()),
+      Some(PrMetadata {
+        url: "https://github.com/NavidJalali/prmoji-testing/pull/2".into(),
+        repository: "NavidJalali/prmoji-testing".to_string(),
+        number: 2,
+        title: "Update README.md testing request review".to_string(),
+        author: Some("NavidJalali".to_string()),
+        merge_commit_sha: None,
+        updated_at: received_at(),
+      })
+    );
+  }
+
+  #[test]
+  fn reads_metadata_from_comments() {
+    let raw_event: RawGitHubEvent = load_raw("test_resources/github/comment.json");
+
+    assert_eq!(
+      raw_event.metadata(received_at()),
+      Some(PrMetadata {
+        url: "https://github.com/NavidJalali/prmoji-testing/pull/1".into(),
+        repository: "NavidJalali/prmoji-testing".to_string(),
+        number: 1,
+        title: "Update README.md title!!!!".to_string(),
+        author: Some("NavidJalali".to_string()),
+        merge_commit_sha: None,
+        updated_at: received_at(),
+      })
+    );
+  }
+
+  fn installation_change(file_location: &str, header: &str) -> Option<InstallationChange> {
+    let raw_event: RawGitHubEvent = load_raw(file_location);
+    raw_event.installation_change(EventTypeHeader::from_raw(header).unwrap())
+  }
+
+  #[test]
+  fn can_detect_installation_changes() {
+    let repositories = |names: &[&str]| {
+      names
+        .iter()
+        .map(|name| format!("NavidJalali/{name}"))
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      installation_change(
+        "test_resources/github/installation-created.json",
+        "installation"
+      ),
+      Some(InstallationChange::Added {
+        installation_id: 45678901,
+        repositories: repositories(&["prmoji-testing", "prmoji"]),
+      })
+    );
+    assert_eq!(
+      installation_change(
+        "test_resources/github/installation-deleted.json",
+        "installation"
+      ),
+      Some(InstallationChange::Removed {
+        installation_id: 45678901
+      })
+    );
+    assert_eq!(
+      installation_change(
+        "test_resources/github/installation-repositories-added.json",
+        "installation_repositories"
+      ),
+      Some(InstallationChange::Added {
+        installation_id: 45678901,
+        repositories: repositories(&["prmoji"]),
+      })
+    );
+    assert_eq!(
+      installation_change(
+        "test_resources/github/installation-repositories-removed.json",
+        "installation_repositories"
+      ),
+      Some(InstallationChange::RepositoriesRemoved {
+        installation_id: 45678901,
+        repositories: repositories(&["prmoji"]),
+      })
+    );
+    assert_eq!(
+      installation_change("test_resources/github/merge.json", "pull_request"),
+      None
+    );
+  }
+
+  #[test]
+  fn reads_the_installation_of_app_events() {
+    let json = std::fs::read_to_string("test_resources/github/merge.json").unwrap();
+    let mut payload: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let raw_event: RawGitHubEvent = serde_json::from_value(payload.clone()).unwrap();
+    assert_eq!(raw_event.installation(), None);
+
+    payload["installation"] = serde_json::json!({ "id": 45678901, "node_id": "MDIzOkludGVncmF0aW9uSW5zdGFsbGF0aW9uNDU2Nzg5MDE=" });
+    let raw_event: RawGitHubEvent = serde_json::from_value(payload).unwrap();
+    assert_eq!(
+      raw_event.installation(),
+      Some((45678901, "NavidJalali/prmoji-testing".to_string()))
+    );
+  }
+}
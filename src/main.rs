@@ -1,22 +1,39 @@
-use std::ops::Deref;
-
 use app_state::AppState;
-use axum::{middleware::from_fn_with_state, routing::post, Router};
+use axum::{
+  middleware::from_fn_with_state,
+  routing::{get, post},
+  Router,
+};
 use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
-use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
+use tracing::{error, info};
 
-use crate::{app_state::LiveState, config::Configuration};
+use crate::{
+  app_state::{DemoState, PostgresState, SqliteState},
+  clock::LiveClock,
+  config::{Backend, Configuration},
+};
 
 mod api;
 mod app_state;
+mod backfill;
+mod channel_settings;
 mod clock;
+mod comments;
 mod config;
+mod conflicts;
 mod github;
+mod metrics;
 mod models;
 mod persistence;
+mod reactions;
+mod repository_rules;
+mod retention;
+mod reviews;
 mod slack;
+mod telemetry;
+#[cfg(test)]
+mod test_support;
 mod url_extractor;
 
 pub fn make_router<S: AppState>(state: S) -> Router {
@@ -29,39 +46,119 @@ pub fn make_router<S: AppState>(state: S) -> Router {
 
   let slack = Router::new()
     .route("/slack", post(api::handle_slack_webhook::<S>))
+    .route(
+      "/slack/commands",
+      post(api::commands::handle_slack_command::<S>),
+    )
     .route_layer(from_fn_with_state(
       state.clone(),
       api::auth::authenticate_slack_webhook::<S>,
     ));
 
-  let debug = Router::<S>::new().route("/debug", post(api::debug));
+  let metrics = Router::<S>::new().route("/metrics", get(api::metrics::<S>));
+
+  let admin = Router::new()
+    .route("/admin/events", get(api::admin::event_history::<S>))
+    .route("/admin/prs", get(api::admin::list_prs::<S>))
+    .route(
+      "/admin/pr",
+      get(api::admin::get_pr::<S>).delete(api::admin::delete_pr::<S>),
+    )
+    .route("/admin/pr/resync", post(api::admin::resync_pr::<S>))
+    .route("/slack/queue", get(api::slack_queue_stats::<S>))
+    .route_layer(from_fn_with_state(
+      state.clone(),
+      api::auth::authenticate_admin::<S>,
+    ));
 
-  Router::new()
+  let mut router = Router::new()
     .merge(github)
     .merge(slack)
-    .merge(debug)
-    .with_state(state)
-    .layer(TraceLayer::new_for_http())
+    .merge(metrics)
+    .merge(admin);
+
+  if state.config().diagnostics.enabled {
+    let diagnostics = Router::new()
+      .route("/healthz", get(api::diagnostics::healthz))
+      .route("/readyz", get(api::diagnostics::readyz::<S>))
+      .route("/version", get(api::diagnostics::version))
+      .route("/config", get(api::diagnostics::config::<S>))
+      .route_layer(from_fn_with_state(
+        state.clone(),
+        api::auth::authenticate_admin::<S>,
+      ));
+
+    router = router.merge(diagnostics);
+  }
+
+  router.with_state(state).layer(TraceLayer::new_for_http())
+}
+
+enum Command {
+  Serve,
+  Gc,
+  // Serves without a database or Slack, see `DemoState`
+  Demo,
+}
+
+impl Command {
+  fn from_args() -> Self {
+    match std::env::args().nth(1).as_deref() {
+      None | Some("serve") => Command::Serve,
+      Some("gc") => Command::Gc,
+      Some("--demo") => Command::Demo,
+      Some(other) => {
+        eprintln!(
+          "Unknown command {:?}, expected `serve`, `gc` or `--demo`",
+          other
+        );
+        std::process::exit(2);
+      }
+    }
+  }
 }
 
 #[tokio::main]
 async fn main() {
+  let command = Command::from_args();
+
   dotenv::dotenv().ok();
 
   let config = Configuration::new().unwrap();
 
-  let subscriber = FmtSubscriber::builder()
-    .with_max_level(Level::DEBUG)
-    .finish();
+  let tracer_provider = telemetry::init(&config.telemetry);
 
-  tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+  match (command, config.database.backend) {
+    (Command::Demo, _) => {
+      info!("Running in demo mode, nothing is stored and no reactions are sent to Slack");
+      serve(DemoState::new(&config, LiveClock), &config).await
+    }
+    (command, Backend::Postgres) => run(PostgresState::new(&config).await, command, &config).await,
+    (command, Backend::Sqlite) => run(SqliteState::new(&config).await, command, &config).await,
+  }
 
-  let state = LiveState::new(&config).await;
+  if let Some(provider) = tracer_provider {
+    if let Err(err) = provider.shutdown() {
+      eprintln!("Failed to flush spans: {:?}", err);
+    }
+  }
+}
 
-  sqlx::migrate!("./migrations")
-    .run(state.pr_repository().pool.deref())
-    .await
-    .unwrap();
+async fn run<S: AppState>(state: S, command: Command, config: &Configuration) {
+  match command {
+    Command::Serve | Command::Demo => serve(state, config).await,
+    Command::Gc => gc(&state, config).await,
+  }
+}
+
+async fn serve<S: AppState>(state: S, config: &Configuration) {
+  state
+    .slack_queue()
+    .spawn_workers(state.clone(), config.queue.workers);
+
+  retention::spawn(state.clone());
+  comments::spawn(state.clone());
+  conflicts::spawn(state.clone());
 
   let app = make_router(state);
 
@@ -70,6 +167,293 @@ async fn main() {
   info!("Listening on {:?}", listener.local_addr().unwrap());
 
   axum::serve(listener, app.into_make_service())
+    .with_graceful_shutdown(shutdown_signal())
     .await
     .unwrap();
 }
+
+// Runs the retention job once, e.g. from a cron job when the background job is disabled
+async fn gc<S: AppState>(state: &S, config: &Configuration) {
+  let result = retention::purge(
+    state.pr_repository(),
+    state.event_repository(),
+    state.clock(),
+    &config.retention,
+  )
+  .await;
+
+  if let Err(err) = result {
+    error!("Failed to purge old PRs: {}", err);
+    std::process::exit(1);
+  }
+}
+
+async fn shutdown_signal() {
+  let ctrl_c = async {
+    tokio::signal::ctrl_c()
+      .await
+      .expect("Failed to listen for ctrl-c");
+  };
+
+  #[cfg(unix)]
+  let terminate = async {
+    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+      .expect("Failed to listen for SIGTERM")
+      .recv()
+      .await;
+  };
+
+  #[cfg(not(unix))]
+  let terminate = std::future::pending::<()>();
+
+  tokio::select! {
+    _ = ctrl_c => {},
+    _ = terminate => {},
+  }
+
+  info!("Shutting down");
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+  };
+  use http_body_util::BodyExt;
+  use tower::ServiceExt;
+
+  use super::*;
+  use crate::{
+    api::auth::hmac,
+    app_state::TestState,
+    clock::Clock,
+    slack::models::{AddReactionRequest, Channel, Timestamp},
+  };
+
+  const PR_URL: &str = "https://github.com/NavidJalali/prmoji-testing/pull/1";
+
+  // The create fixture, saying something else
+  fn slack_message(text: &str) -> String {
+    let json = std::fs::read_to_string("test_resources/slack/create.json").unwrap();
+    let mut message: serde_json::Value = serde_json::from_str(&json).unwrap();
+    message["event"]["text"] = text.into();
+    message.to_string()
+  }
+
+  fn github_request(state: &TestState, event: &str, body: String) -> Request<Body> {
+    let signature = hex::encode(hmac(state.config().github.secret(), body.as_bytes()));
+    Request::post("/github")
+      .header("Content-Type", "application/json")
+      .header("X-GitHub-Event", event)
+      .header("X-GitHub-Delivery", "be3583aa-fe69-4151-87c2-90089c51bff3")
+      .header("X-Hub-Signature-256", format!("sha256={signature}"))
+      .body(Body::from(body))
+      .unwrap()
+  }
+
+  fn slack_request(state: &TestState, sent_at: i64, body: String) -> Request<Body> {
+    let signed = format!("v0:{}:{}", sent_at, body);
+    let signature = hex::encode(hmac(
+      state.config().slack.signing_secret(),
+      signed.as_bytes(),
+    ));
+    Request::post("/slack")
+      .header("Content-Type", "application/json")
+      .header("X-Slack-Request-Timestamp", sent_at.to_string())
+      .header("X-Slack-Signature", format!("v0={signature}"))
+      .body(Body::from(body))
+      .unwrap()
+  }
+
+  // Slash commands are posted as a form and signed like events
+  fn slack_command(state: &TestState, sent_at: i64, text: &str) -> Request<Body> {
+    let body = format!(
+      "command=%2Fprmoji&text={}&channel_id=C05UBF6AJH3&user_id=U05U3PX7QRE",
+      text.replace(' ', "+")
+    );
+    let mut request = slack_request(state, sent_at, body);
+    *request.uri_mut() = "/slack/commands".parse().unwrap();
+    request.headers_mut().insert(
+      "Content-Type",
+      "application/x-www-form-urlencoded".parse().unwrap(),
+    );
+    request
+  }
+
+  async fn send(state: &TestState, request: Request<Body>) -> (StatusCode, String) {
+    let response = make_router(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+  }
+
+  // Slack events are processed in the background
+  async fn wait_for_slack_events(state: &TestState, count: u64) {
+    for _ in 0..200 {
+      let queue = state.slack_queue().snapshot();
+      if queue.processed + queue.failed >= count {
+        return;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Slack events were not processed in time");
+  }
+
+  #[tokio::test]
+  async fn reacts_to_github_events_on_tracked_messages() {
+    let state = TestState::for_tests();
+    state.slack_queue().spawn_workers(state.clone(), 1);
+    let now = state.clock().now().timestamp();
+
+    let message = slack_message(&format!("Please take a look at {PR_URL}"));
+    let (status, _) = send(&state, slack_request(&state, now, message)).await;
+    assert_eq!(status, StatusCode::OK);
+    wait_for_slack_events(&state, 1).await;
+
+    let merge = std::fs::read_to_string("test_resources/github/merge.json").unwrap();
+    let (status, _) = send(&state, github_request(&state, "pull_request", merge)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(
+      state.slack_client.reactions(),
+      vec![AddReactionRequest {
+        channel: Channel("C05UBF6AJH3".to_string()),
+        name: "shipit".to_string(),
+        timestamp: Timestamp("1696367451.886309".to_string()),
+      }]
+    );
+  }
+
+  #[tokio::test]
+  async fn ignores_github_events_for_untracked_prs() {
+    let state = TestState::for_tests();
+
+    let approved = std::fs::read_to_string("test_resources/github/approved.json").unwrap();
+    let (status, _) = send(
+      &state,
+      github_request(&state, "pull_request_review", approved),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(state.slack_client.reactions(), vec![]);
+  }
+
+  #[tokio::test]
+  async fn rejects_github_webhooks_with_a_bad_signature() {
+    let state = TestState::for_tests();
+    let mut request = github_request(&state, "pull_request", "{}".to_string());
+    request.headers_mut().insert(
+      "X-Hub-Signature-256",
+      format!("sha256={}", hex::encode([0u8; 32]))
+        .parse()
+        .unwrap(),
+    );
+
+    let (status, _) = send(&state, request).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+  }
+
+  #[tokio::test]
+  async fn counts_webhooks_without_a_signature() {
+    let state = TestState::for_tests();
+    let missing = |source: &str| {
+      metrics::SIGNATURE_FAILURES
+        .with_label_values(&[source, "missing"])
+        .get()
+    };
+    let (github, slack) = (missing("github"), missing("slack"));
+
+    let mut request = github_request(&state, "pull_request", "{}".to_string());
+    request.headers_mut().remove("X-Hub-Signature-256");
+    let (status, _) = send(&state, request).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let now = state.clock().now().timestamp();
+    for header in ["X-Slack-Signature", "X-Slack-Request-Timestamp"] {
+      let mut request = slack_request(&state, now, slack_message("Hello World!"));
+      request.headers_mut().remove(header);
+      let (status, _) = send(&state, request).await;
+      assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    assert_eq!(missing("github"), github + 1);
+    assert_eq!(missing("slack"), slack + 2);
+  }
+
+  #[tokio::test]
+  async fn rejects_expired_slack_webhooks() {
+    let state = TestState::for_tests();
+    let ten_minutes_ago = state.clock().now().timestamp() - 600;
+
+    let request = slack_request(&state, ten_minutes_ago, slack_message("Hello World!"));
+    let (status, _) = send(&state, request).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(state.slack_queue().snapshot().depth, 0);
+  }
+
+  #[tokio::test]
+  async fn answers_slack_url_verification() {
+    let state = TestState::for_tests();
+    let now = state.clock().now().timestamp();
+
+    let body = r#"{"type":"url_verification","challenge":"3eZbrw1aBm2rZgRNFdxV2595E9CY3gmdALWMmHkvFXO7tYXAYM8P"}"#;
+    let (status, response) = send(&state, slack_request(&state, now, body.to_string())).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+      response,
+      r#"{"challenge":"3eZbrw1aBm2rZgRNFdxV2595E9CY3gmdALWMmHkvFXO7tYXAYM8P"}"#
+    );
+  }
+
+  #[tokio::test]
+  async fn channels_pick_their_own_emojis() {
+    let state = TestState::for_tests();
+    state.slack_queue().spawn_workers(state.clone(), 1);
+    let now = state.clock().now().timestamp();
+
+    let (status, response) = send(
+      &state,
+      slack_command(&state, now, "config emoji merged :art:"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(response.contains(r#""response_type":"ephemeral""#));
+
+    let message = slack_message(&format!("Please take a look at {PR_URL}"));
+    send(&state, slack_request(&state, now, message)).await;
+    wait_for_slack_events(&state, 1).await;
+
+    let merge = std::fs::read_to_string("test_resources/github/merge.json").unwrap();
+    send(&state, github_request(&state, "pull_request", merge)).await;
+
+    let reactions = state.slack_client.reactions();
+    assert_eq!(reactions.len(), 1);
+    assert_eq!(reactions[0].name, "art");
+  }
+
+  #[tokio::test]
+  async fn admin_endpoints_require_the_token() {
+    let state = TestState::for_tests();
+
+    for path in ["/admin/prs", "/slack/queue"] {
+      let anonymous = Request::get(path).body(Body::empty()).unwrap();
+      let (status, _) = send(&state, anonymous).await;
+      assert_eq!(status, StatusCode::UNAUTHORIZED, "{path}");
+    }
+
+    let admin = Request::get("/admin/prs")
+      .header("Authorization", "Bearer admin-token")
+      .body(Body::empty())
+      .unwrap();
+    let (status, body) = send(&state, admin).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "[]");
+  }
+}
@@ -0,0 +1,155 @@
+use once_cell::sync::Lazy;
+use prometheus::{
+  register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+  register_int_gauge_vec, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+  TextEncoder,
+};
+
+use crate::{app_state::AppState, persistence::pr_repository::PrRepository};
+
+pub static WEBHOOKS_RECEIVED: Lazy<IntCounterVec> = Lazy::new(|| {
+  register_int_counter_vec!(
+    "prmoji_webhooks_received_total",
+    "Webhook calls received, by source and event type header",
+    &["source", "event_type"]
+  )
+  .unwrap()
+});
+
+pub static GITHUB_EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+  register_int_counter_vec!(
+    "prmoji_github_events_total",
+    "GitHub events prmoji reacts to, by event type",
+    &["event_type"]
+  )
+  .unwrap()
+});
+
+pub static SIGNATURE_FAILURES: Lazy<IntCounterVec> = Lazy::new(|| {
+  register_int_counter_vec!(
+    "prmoji_signature_failures_total",
+    "Webhook calls rejected because of their signature, by source and reason",
+    &["source", "reason"]
+  )
+  .unwrap()
+});
+
+pub static PR_URLS_EXTRACTED: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "prmoji_pr_urls_extracted_total",
+    "Pull request URLs extracted from Slack messages"
+  )
+  .unwrap()
+});
+
+pub static REACTIONS_ADDED: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "prmoji_reactions_added_total",
+    "Reactions successfully added to Slack messages"
+  )
+  .unwrap()
+});
+
+pub static REACTIONS_FAILED: Lazy<IntCounterVec> = Lazy::new(|| {
+  register_int_counter_vec!(
+    "prmoji_reactions_failed_total",
+    "Reactions that could not be added to or removed from Slack messages, by Slack error code",
+    &["error"]
+  )
+  .unwrap()
+});
+
+pub static REACTIONS_REMOVED: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "prmoji_reactions_removed_total",
+    "Reactions successfully removed from Slack messages"
+  )
+  .unwrap()
+});
+
+pub static COMMENTS_DEBOUNCED: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "prmoji_comments_debounced_total",
+    "Comments whose reactions were folded into the pending update of their pull request"
+  )
+  .unwrap()
+});
+
+pub static BACKFILLS: Lazy<IntCounterVec> = Lazy::new(|| {
+  register_int_counter_vec!(
+    "prmoji_backfills_total",
+    "Newly posted pull requests looked up on GitHub, by outcome",
+    &["outcome"]
+  )
+  .unwrap()
+});
+
+pub static PRS_PURGED: Lazy<IntCounterVec> = Lazy::new(|| {
+  register_int_counter_vec!(
+    "prmoji_pull_requests_purged_total",
+    "Tracked pull request rows removed by the retention job, by reason",
+    &["reason"]
+  )
+  .unwrap()
+});
+
+pub static DB_QUERY_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+  register_histogram_vec!(
+    "prmoji_db_query_duration_seconds",
+    "Duration of database queries, by query",
+    &["query"],
+    vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
+  )
+  .unwrap()
+});
+
+pub static DB_POOL_CONNECTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+  register_int_gauge_vec!(
+    "prmoji_db_pool_connections",
+    "Database pool connections, by state (max, open, idle, in_use)",
+    &["state"]
+  )
+  .unwrap()
+});
+
+pub static SLACK_QUEUE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+  register_int_gauge!(
+    "prmoji_slack_queue_depth",
+    "Slack events waiting to be processed"
+  )
+  .unwrap()
+});
+
+pub static SLACK_QUEUE_LAG: Lazy<IntGauge> = Lazy::new(|| {
+  register_int_gauge!(
+    "prmoji_slack_queue_lag_milliseconds",
+    "Time the last processed Slack event spent in the queue"
+  )
+  .unwrap()
+});
+
+// Gauges are sampled when scraped, counters and histograms are updated as things happen
+pub fn render<S: AppState>(state: &S) -> String {
+  if let Some(pool) = state.pr_repository().pool_status() {
+    DB_POOL_CONNECTIONS
+      .with_label_values(&["max"])
+      .set(pool.max.into());
+    DB_POOL_CONNECTIONS
+      .with_label_values(&["open"])
+      .set(pool.open.into());
+    DB_POOL_CONNECTIONS
+      .with_label_values(&["idle"])
+      .set(pool.idle.into());
+    DB_POOL_CONNECTIONS
+      .with_label_values(&["in_use"])
+      .set(pool.open.saturating_sub(pool.idle).into());
+  }
+
+  let queue = state.slack_queue().snapshot();
+  SLACK_QUEUE_DEPTH.set(queue.depth as i64);
+  SLACK_QUEUE_LAG.set(queue.last_lag_ms);
+
+  TextEncoder::new()
+    .encode_to_string(&prometheus::gather())
+    .unwrap_or_else(|err| format!("# Failed to encode metrics: {}", err))
+}
@@ -1,11 +1,14 @@
 use std::hash::Hash;
 
 use crate::{
-  persistence::models::PullRequestTable,
+  persistence::models::{
+    ChannelSettingTable, EventTable, PullRequestCommentsTable, PullRequestMetadataTable,
+    PullRequestTable, SlackActionTable,
+  },
   slack::models::{Channel, Timestamp},
 };
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize)]
@@ -47,6 +50,118 @@ impl From<PullRequestTable> for PR {
   }
 }
 
+// What GitHub last told us about a pull request, keyed by its URL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PrMetadata {
+  pub url: PrUrl,
+  // `owner/name` of the repository
+  pub repository: String,
+  pub number: u32,
+  pub title: String,
+  pub author: Option<String>,
+  // Only known once the PR is merged, and kept by later webhooks that don't carry it
+  pub merge_commit_sha: Option<String>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl From<PullRequestMetadataTable> for PrMetadata {
+  fn from(metadata: PullRequestMetadataTable) -> Self {
+    Self {
+      url: PrUrl(metadata.url),
+      repository: metadata.repository,
+      number: metadata.number as u32,
+      title: metadata.title,
+      author: metadata.author,
+      merge_commit_sha: metadata.merge_commit_sha,
+      updated_at: metadata.updated_at,
+    }
+  }
+}
+
+// How many comments a PR got, and how many of them its reactions already reflect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrComments {
+  pub url: PrUrl,
+  pub comments: u32,
+  pub reacted_comments: u32,
+  // The review the latest counted comment belonged to
+  pub last_review_id: Option<u64>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl From<PullRequestCommentsTable> for PrComments {
+  fn from(comments: PullRequestCommentsTable) -> Self {
+    Self {
+      url: PrUrl(comments.url),
+      comments: comments.comments as u32,
+      reacted_comments: comments.reacted_comments as u32,
+      last_review_id: comments.last_review_id.map(|id| id as u64),
+      updated_at: comments.updated_at,
+    }
+  }
+}
+
+// A `/prmoji config` override of one event type in one channel, None falls back to the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelSetting {
+  pub channel: Channel,
+  // By `GitHubEventType::name`
+  pub event_type: String,
+  pub emoji: Option<String>,
+  pub enabled: Option<bool>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl From<ChannelSettingTable> for ChannelSetting {
+  fn from(setting: ChannelSettingTable) -> Self {
+    Self {
+      channel: Channel(setting.channel),
+      event_type: setting.event_type,
+      emoji: setting.emoji,
+      enabled: setting.enabled,
+      updated_at: setting.updated_at,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PrFilter {
+  pub channel: Option<Channel>,
+  // `owner/name` of the repository
+  pub repository: Option<String>,
+  pub inserted_after: Option<DateTime<Utc>>,
+  pub inserted_before: Option<DateTime<Utc>>,
+}
+
+impl PrFilter {
+  pub fn url_prefix(&self) -> Option<String> {
+    self
+      .repository
+      .as_ref()
+      .map(|repository| format!("https://github.com/{}/pull/", repository))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrState {
+  Open,
+  Merged,
+  Closed,
+}
+
+impl PrState {
+  // The state a pull request is left in by a GitHub event, by `GitHubEventType::name`
+  pub fn after_event(event_type: &str) -> Option<Self> {
+    match event_type {
+      "merged" => Some(PrState::Merged),
+      "closed" => Some(PrState::Closed),
+      "reopened" => Some(PrState::Open),
+      _ => None,
+    }
+  }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ToDelete {
   pub urls: Vec<PrUrl>,
@@ -87,3 +202,91 @@ impl ToInsert {
     }
   }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct EventId(pub Uuid);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedEvent {
+  pub id: EventId,
+  pub delivery_id: Option<String>,
+  pub pr_url: PrUrl,
+  pub event_type: String,
+  pub actor: String,
+  pub received_at: DateTime<Utc>,
+}
+
+impl From<EventTable> for RecordedEvent {
+  fn from(event: EventTable) -> Self {
+    Self {
+      id: EventId(event.id),
+      delivery_id: event.delivery_id,
+      pr_url: PrUrl(event.pr_url),
+      event_type: event.event_type,
+      actor: event.actor,
+      received_at: event.received_at,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlackActionKind {
+  AddReaction,
+  RemoveReaction,
+}
+
+impl SlackActionKind {
+  pub fn name(&self) -> &'static str {
+    match self {
+      SlackActionKind::AddReaction => "add_reaction",
+      SlackActionKind::RemoveReaction => "remove_reaction",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "add_reaction" => Some(SlackActionKind::AddReaction),
+      "remove_reaction" => Some(SlackActionKind::RemoveReaction),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlackAction {
+  pub event_id: EventId,
+  pub kind: SlackActionKind,
+  pub channel: Channel,
+  pub timestamp: Timestamp,
+  pub emoji: String,
+  pub succeeded: bool,
+  pub error: Option<String>,
+  pub performed_at: DateTime<Utc>,
+}
+
+impl TryFrom<SlackActionTable> for SlackAction {
+  type Error = String;
+
+  fn try_from(action: SlackActionTable) -> Result<Self, Self::Error> {
+    let kind = SlackActionKind::from_name(&action.kind)
+      .ok_or_else(|| format!("Unknown slack action {}", action.kind))?;
+    Ok(Self {
+      event_id: EventId(action.event_id),
+      kind,
+      channel: Channel(action.channel),
+      timestamp: Timestamp(action.timestamp),
+      emoji: action.emoji,
+      succeeded: action.succeeded,
+      error: action.error,
+      performed_at: action.performed_at,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventHistoryEntry {
+  #[serde(flatten)]
+  pub event: RecordedEvent,
+  pub actions: Vec<SlackAction>,
+}
@@ -0,0 +1,746 @@
+/*
+ * Behaviour every storage backend has to share, each backend runs these against its own database.
+ * Messages come from the `test_resources/slack` fixtures.
+ */
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+  models::{
+    ChannelSetting, EventId, PrComments, PrFilter, PrMetadata, PrState, PrUrl, RecordedEvent,
+    SlackAction, SlackActionKind, ToDelete, ToInsert,
+  },
+  persistence::{event_repository::EventRepository, pr_repository::PrRepository},
+  slack::models::{
+    AlteredMessage, Channel, Event, Message, MessageUpdate, Text, Timestamp, WebookCallback,
+  },
+  url_extractor::extract_pr_urls,
+};
+
+pub fn pr(n: i32) -> PrUrl {
+  PrUrl(format!("https://github.com/fancy-org/cool-repo/pull/{n}"))
+}
+
+fn now() -> DateTime<Utc> {
+  "2023-10-03T20:20:51Z".parse().unwrap()
+}
+
+fn load_event(file_location: &str) -> Event {
+  let json = std::fs::read_to_string(file_location).unwrap();
+  match serde_json::from_str(&json).unwrap() {
+    WebookCallback::EventCallback { event } => event,
+    other => panic!("Expected an event callback, got {:?}", other),
+  }
+}
+
+pub fn created() -> Message {
+  match load_event("test_resources/slack/create-with-prs.json") {
+    Event::Create(message) => message,
+    other => panic!("Expected a new message, got {:?}", other),
+  }
+}
+
+fn edited() -> (AlteredMessage, AlteredMessage) {
+  match load_event("test_resources/slack/edit-with-prs.json") {
+    Event::Update(MessageUpdate::MessageChanged {
+      message,
+      previous_message,
+      ..
+    }) => (previous_message, message),
+    other => panic!("Expected a changed message, got {:?}", other),
+  }
+}
+
+fn deleted() -> AlteredMessage {
+  match load_event("test_resources/slack/delete-with-prs.json") {
+    Event::Update(MessageUpdate::MessageDeleted {
+      previous_message, ..
+    }) => previous_message,
+    other => panic!("Expected a deleted message, got {:?}", other),
+  }
+}
+
+async fn create<R: PrRepository>(repo: &R, message: &Message) {
+  repo
+    .insert_all(ToInsert::new(
+      extract_pr_urls(&message.text.0),
+      message.channel.clone(),
+      message.event_ts.clone(),
+      now(),
+    ))
+    .await
+    .unwrap();
+}
+
+async fn edit<R: PrRepository>(repo: &R, message: &Message) {
+  let (before, after) = edited();
+  repo
+    .update(
+      ToInsert::new(
+        extract_pr_urls(&after.text.0),
+        message.channel.clone(),
+        after.ts,
+        now(),
+      ),
+      ToDelete::new(
+        extract_pr_urls(&before.text.0),
+        message.channel.clone(),
+        before.ts,
+      ),
+    )
+    .await
+    .unwrap();
+}
+
+async fn urls<R: PrRepository>(repo: &R, message: &Message) -> Vec<PrUrl> {
+  let mut urls = repo
+    .list(PrFilter::default())
+    .await
+    .unwrap()
+    .into_iter()
+    .filter(|pr| pr.channel == message.channel && pr.timestamp == message.event_ts)
+    .map(|pr| pr.url)
+    .collect::<Vec<_>>();
+  urls.sort_by(|a, b| a.0.cmp(&b.0));
+  urls
+}
+
+pub async fn create_tracks_every_url<R: PrRepository>(repo: &R) {
+  let message = created();
+
+  create(repo, &message).await;
+
+  assert_eq!(urls(repo, &message).await, vec![pr(267), pr(268)]);
+}
+
+pub async fn edit_replaces_every_url<R: PrRepository>(repo: &R) {
+  let message = created();
+
+  create(repo, &message).await;
+  edit(repo, &message).await;
+
+  assert_eq!(urls(repo, &message).await, vec![pr(268), pr(269)]);
+}
+
+pub async fn delete_removes_every_url<R: PrRepository>(repo: &R) {
+  let message = created();
+
+  create(repo, &message).await;
+  edit(repo, &message).await;
+  let previous = deleted();
+  repo
+    .delete_all(ToDelete::new(
+      extract_pr_urls(&previous.text.0),
+      message.channel.clone(),
+      previous.ts,
+    ))
+    .await
+    .unwrap();
+
+  assert_eq!(urls(repo, &message).await, vec![]);
+}
+
+pub async fn delete_leaves_other_messages_alone<R: PrRepository>(repo: &R) {
+  let message = created();
+  let other = Message {
+    event_ts: Timestamp("1696367999.000100".to_string()),
+    ..created()
+  };
+
+  create(repo, &message).await;
+  create(repo, &other).await;
+  repo
+    .delete_all(ToDelete::new(
+      extract_pr_urls(&message.text.0),
+      message.channel.clone(),
+      message.event_ts.clone(),
+    ))
+    .await
+    .unwrap();
+
+  assert_eq!(urls(repo, &message).await, vec![]);
+  assert_eq!(urls(repo, &other).await, vec![pr(267), pr(268)]);
+}
+
+pub async fn retried_creates_are_tracked_once<R: PrRepository>(repo: &R) {
+  let message = created();
+
+  create(repo, &message).await;
+  create(repo, &message).await;
+
+  assert_eq!(urls(repo, &message).await, vec![pr(267), pr(268)]);
+}
+
+pub async fn repeated_urls_are_tracked_once<R: PrRepository>(repo: &R) {
+  let message = Message {
+    text: Text(format!("{} {}", pr(267).0, pr(267).0)),
+    ..created()
+  };
+
+  create(repo, &message).await;
+
+  assert_eq!(urls(repo, &message).await, vec![pr(267)]);
+}
+
+pub async fn list_applies_filters<R: PrRepository>(repo: &R) {
+  let track = |url: &str, channel: &str, hours_ago: i64| {
+    ToInsert::new(
+      vec![PrUrl(url.to_string())],
+      Channel(channel.to_string()),
+      Timestamp(format!("1696367451.{hours_ago}")),
+      now() - Duration::hours(hours_ago),
+    )
+  };
+  repo
+    .insert_all(track(
+      "https://github.com/fancy-org/cool-repo/pull/1",
+      "C1",
+      1,
+    ))
+    .await
+    .unwrap();
+  repo
+    .insert_all(track(
+      "https://github.com/fancy-org/cool-repo/pull/2",
+      "C2",
+      5,
+    ))
+    .await
+    .unwrap();
+  repo
+    .insert_all(track(
+      "https://github.com/fancy-org/other-repo/pull/3",
+      "C1",
+      10,
+    ))
+    .await
+    .unwrap();
+
+  let listed = |filter: PrFilter| async move {
+    repo
+      .list(filter)
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|pr| pr.url.0.rsplit('/').next().unwrap().to_string())
+      .collect::<Vec<_>>()
+  };
+
+  assert_eq!(listed(PrFilter::default()).await, vec!["3", "2", "1"]);
+  assert_eq!(
+    listed(PrFilter {
+      channel: Some(Channel("C1".to_string())),
+      ..Default::default()
+    })
+    .await,
+    vec!["3", "1"]
+  );
+  assert_eq!(
+    listed(PrFilter {
+      repository: Some("fancy-org/cool-repo".to_string()),
+      ..Default::default()
+    })
+    .await,
+    vec!["2", "1"]
+  );
+  assert_eq!(
+    listed(PrFilter {
+      inserted_after: Some(now() - Duration::hours(6)),
+      inserted_before: Some(now() - Duration::hours(2)),
+      ..Default::default()
+    })
+    .await,
+    vec!["2"]
+  );
+}
+
+pub async fn purges_by_url_and_age<R: PrRepository>(repo: &R) {
+  let track = |url: PrUrl, days_ago: i64| {
+    ToInsert::new(
+      vec![url],
+      Channel("C05UBF6AJH3".to_string()),
+      Timestamp(format!("1696367451.{days_ago}")),
+      now() - Duration::days(days_ago),
+    )
+  };
+  repo.insert_all(track(pr(1), 1)).await.unwrap();
+  repo.insert_all(track(pr(2), 1)).await.unwrap();
+  repo.insert_all(track(pr(3), 100)).await.unwrap();
+  repo.insert_all(track(pr(4), 1)).await.unwrap();
+
+  assert_eq!(repo.delete_by_urls(vec![pr(1), pr(2)]).await.unwrap(), 2);
+  assert_eq!(
+    repo
+      .delete_inserted_before(now() - Duration::days(90))
+      .await
+      .unwrap(),
+    1
+  );
+  assert_eq!(repo.delete_by_url(pr(4)).await.unwrap(), 1);
+  assert!(repo.list(PrFilter::default()).await.unwrap().is_empty());
+}
+
+fn metadata(n: i32, title: &str, days_ago: i64) -> PrMetadata {
+  PrMetadata {
+    url: pr(n),
+    repository: "fancy-org/cool-repo".to_string(),
+    number: n as u32,
+    title: title.to_string(),
+    author: Some("NavidJalali".to_string()),
+    merge_commit_sha: None,
+    updated_at: now() - Duration::days(days_ago),
+  }
+}
+
+pub async fn keeps_the_latest_metadata<R: PrRepository>(repo: &R) {
+  repo
+    .upsert_metadata(metadata(1, "Add a title", 2))
+    .await
+    .unwrap();
+  repo
+    .upsert_metadata(metadata(1, "Add a better title", 1))
+    .await
+    .unwrap();
+  repo
+    .upsert_metadata(metadata(2, "Fix everything", 100))
+    .await
+    .unwrap();
+
+  let found = repo.metadata(vec![pr(1), pr(2), pr(3)]).await.unwrap();
+  assert_eq!(found.len(), 2);
+  assert_eq!(
+    found.get(&pr(1)),
+    Some(&metadata(1, "Add a better title", 1))
+  );
+  assert_eq!(found.get(&pr(2)), Some(&metadata(2, "Fix everything", 100)));
+
+  assert_eq!(
+    repo
+      .delete_metadata_updated_before(now() - Duration::days(90))
+      .await
+      .unwrap(),
+    1
+  );
+  assert_eq!(
+    repo
+      .metadata(vec![pr(1), pr(2)])
+      .await
+      .unwrap()
+      .into_keys()
+      .collect::<Vec<_>>(),
+    vec![pr(1)]
+  );
+}
+
+pub async fn finds_merged_prs_by_merge_commit<R: PrRepository>(repo: &R) {
+  let sha = "cba2c0cc6ce22b467e16dd81ab847f66ab957eb0";
+  repo
+    .upsert_metadata(PrMetadata {
+      merge_commit_sha: Some(sha.to_string()),
+      ..metadata(1, "Add a title", 2)
+    })
+    .await
+    .unwrap();
+  // A comment after the merge doesn't know the merge commit
+  repo
+    .upsert_metadata(metadata(1, "Add a title", 1))
+    .await
+    .unwrap();
+  repo
+    .upsert_metadata(metadata(2, "Fix everything", 1))
+    .await
+    .unwrap();
+
+  assert_eq!(
+    repo.merged_pr("fancy-org/cool-repo", sha).await.unwrap(),
+    Some(pr(1))
+  );
+  assert_eq!(
+    repo.merged_pr("fancy-org/other-repo", sha).await.unwrap(),
+    None
+  );
+  assert_eq!(
+    repo
+      .merged_pr(
+        "fancy-org/cool-repo",
+        "9f6f4fb989c26216cdbf768351067df79089e5a3"
+      )
+      .await
+      .unwrap(),
+    None
+  );
+}
+
+pub async fn tracks_installations<R: PrRepository>(repo: &R) {
+  let repositories = |names: &[&str]| {
+    names
+      .iter()
+      .map(|name| format!("fancy-org/{name}"))
+      .collect::<Vec<_>>()
+  };
+  let installation = |name: &str| {
+    let repository = format!("fancy-org/{name}");
+    async move { repo.installation(&repository).await.unwrap() }
+  };
+
+  repo
+    .save_installation(1, repositories(&["cool-repo", "other-repo"]), now())
+    .await
+    .unwrap();
+  repo
+    .save_installation(2, repositories(&["lonely-repo"]), now())
+    .await
+    .unwrap();
+  assert_eq!(installation("cool-repo").await, Some(1));
+  assert_eq!(installation("lonely-repo").await, Some(2));
+  assert_eq!(installation("unknown-repo").await, None);
+
+  // Reinstalling moves the repository over
+  repo
+    .save_installation(3, repositories(&["cool-repo"]), now())
+    .await
+    .unwrap();
+  assert_eq!(installation("cool-repo").await, Some(3));
+
+  assert_eq!(
+    repo
+      .delete_installation_repositories(1, repositories(&["cool-repo", "other-repo"]))
+      .await
+      .unwrap(),
+    1
+  );
+  assert_eq!(installation("cool-repo").await, Some(3));
+  assert_eq!(installation("other-repo").await, None);
+
+  assert_eq!(repo.delete_installation(3).await.unwrap(), 1);
+  assert_eq!(installation("cool-repo").await, None);
+  assert_eq!(installation("lonely-repo").await, Some(2));
+}
+
+pub async fn keeps_one_setting_per_channel_and_event<R: PrRepository>(repo: &R) {
+  let design = Channel("C05UBF6AJH3".to_string());
+  let platform = Channel("C05UBF6AJH4".to_string());
+  let setting =
+    |channel: &Channel, event_type: &str, emoji: Option<&str>, enabled: Option<bool>| {
+      ChannelSetting {
+        channel: channel.clone(),
+        event_type: event_type.to_string(),
+        emoji: emoji.map(|emoji| emoji.to_string()),
+        enabled,
+        updated_at: now(),
+      }
+    };
+
+  repo
+    .save_channel_setting(setting(&design, "merged", Some("art"), None))
+    .await
+    .unwrap();
+  repo
+    .save_channel_setting(setting(&design, "commented", None, Some(false)))
+    .await
+    .unwrap();
+  repo
+    .save_channel_setting(setting(&platform, "merged", Some("rocket"), None))
+    .await
+    .unwrap();
+  // Saving again replaces the whole setting
+  repo
+    .save_channel_setting(setting(&design, "merged", Some("tada"), Some(true)))
+    .await
+    .unwrap();
+
+  assert_eq!(
+    repo.channel_settings(design.clone()).await.unwrap(),
+    vec![
+      setting(&design, "commented", None, Some(false)),
+      setting(&design, "merged", Some("tada"), Some(true)),
+    ]
+  );
+
+  assert_eq!(
+    repo.delete_channel_settings(design.clone()).await.unwrap(),
+    2
+  );
+  assert_eq!(repo.channel_settings(design).await.unwrap(), vec![]);
+  assert_eq!(repo.channel_settings(platform).await.unwrap().len(), 1);
+}
+
+pub async fn counts_comments<R: PrRepository>(repo: &R) {
+  assert_eq!(repo.comments(pr(267)).await.unwrap(), None);
+
+  assert_eq!(
+    repo
+      .count_comment(pr(267), Some(1795), now() - Duration::days(100))
+      .await
+      .unwrap(),
+    1
+  );
+  // More inline comments of the same review, even with another comment in between
+  assert_eq!(
+    repo
+      .count_comment(pr(267), Some(1795), now() - Duration::days(99))
+      .await
+      .unwrap(),
+    1
+  );
+  assert_eq!(
+    repo
+      .count_comment(pr(267), None, now() - Duration::days(98))
+      .await
+      .unwrap(),
+    2
+  );
+  assert_eq!(
+    repo
+      .count_comment(pr(267), Some(1795), now())
+      .await
+      .unwrap(),
+    2
+  );
+  assert_eq!(
+    repo
+      .count_comment(pr(268), Some(1796), now() - Duration::days(100))
+      .await
+      .unwrap(),
+    1
+  );
+  repo.set_reacted_comments(pr(267), 2).await.unwrap();
+
+  assert_eq!(
+    repo.comments(pr(267)).await.unwrap(),
+    Some(PrComments {
+      url: pr(267),
+      comments: 2,
+      reacted_comments: 2,
+      last_review_id: Some(1795),
+      updated_at: now(),
+    })
+  );
+
+  assert_eq!(
+    repo
+      .delete_comments_updated_before(now() - Duration::days(90))
+      .await
+      .unwrap(),
+    1
+  );
+  assert_eq!(repo.comments(pr(268)).await.unwrap(), None);
+  assert!(repo.comments(pr(267)).await.unwrap().is_some());
+}
+
+fn event(url: PrUrl, event_type: &str, hours_ago: i64) -> RecordedEvent {
+  RecordedEvent {
+    id: EventId(uuid::Uuid::new_v4()),
+    delivery_id: Some(format!("delivery-{hours_ago}")),
+    pr_url: url,
+    event_type: event_type.to_string(),
+    actor: "NavidJalali".to_string(),
+    received_at: now() - Duration::hours(hours_ago),
+  }
+}
+
+pub async fn records_history<E: EventRepository>(events: &E) {
+  let approved = event(pr(1), "approved", 2);
+  let merged = event(pr(1), "merged", 1);
+  events.record_event(merged.clone()).await.unwrap();
+  events.record_event(approved.clone()).await.unwrap();
+  events
+    .record_event(event(pr(2), "approved", 1))
+    .await
+    .unwrap();
+
+  for (event, emoji) in [(&merged, "shipit"), (&approved, "white_check_mark")] {
+    events
+      .record_slack_action(SlackAction {
+        event_id: event.id.clone(),
+        kind: SlackActionKind::AddReaction,
+        channel: Channel("C05UBF6AJH3".to_string()),
+        timestamp: Timestamp("1696367451.886309".to_string()),
+        emoji: emoji.to_string(),
+        succeeded: true,
+        error: None,
+        performed_at: event.received_at,
+      })
+      .await
+      .unwrap();
+  }
+
+  let history = events
+    .history(pr(1))
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|entry| {
+      (
+        entry.event.event_type,
+        entry.event.received_at,
+        entry
+          .actions
+          .into_iter()
+          .map(|action| action.emoji)
+          .collect::<Vec<_>>(),
+      )
+    })
+    .collect::<Vec<_>>();
+
+  assert_eq!(
+    history,
+    vec![
+      (
+        "approved".to_string(),
+        approved.received_at,
+        vec!["white_check_mark".to_string()]
+      ),
+      (
+        "merged".to_string(),
+        merged.received_at,
+        vec!["shipit".to_string()]
+      ),
+    ]
+  );
+}
+
+pub async fn derives_states<E: EventRepository>(events: &E) {
+  events
+    .record_event(event(pr(1), "closed", 3))
+    .await
+    .unwrap();
+  events
+    .record_event(event(pr(1), "merged", 2))
+    .await
+    .unwrap();
+  events
+    .record_event(event(pr(2), "closed", 1))
+    .await
+    .unwrap();
+  events
+    .record_event(event(pr(3), "approved", 1))
+    .await
+    .unwrap();
+  events
+    .record_event(event(pr(4), "closed", 2))
+    .await
+    .unwrap();
+  events
+    .record_event(event(pr(4), "reopened", 1))
+    .await
+    .unwrap();
+
+  let states = events
+    .states(vec![pr(1), pr(2), pr(3), pr(4)])
+    .await
+    .unwrap();
+  assert_eq!(states.len(), 3);
+  assert_eq!(states.get(&pr(1)), Some(&PrState::Merged));
+  assert_eq!(states.get(&pr(2)), Some(&PrState::Closed));
+  assert_eq!(states.get(&pr(4)), Some(&PrState::Open));
+}
+
+pub async fn finds_the_latest_of_some_events<E: EventRepository>(events: &E) {
+  let conflicted = event(pr(1), "conflicted", 3);
+  for event in [
+    event(pr(1), "conflict_resolved", 4),
+    conflicted.clone(),
+    event(pr(1), "approved", 2),
+    event(pr(2), "conflict_resolved", 1),
+  ] {
+    events.record_event(event).await.unwrap();
+  }
+
+  let latest = events
+    .latest_of(pr(1), &["conflicted", "conflict_resolved"])
+    .await
+    .unwrap();
+  assert_eq!(latest.map(|event| event.id), Some(conflicted.id));
+  assert!(events
+    .latest_of(pr(3), &["conflicted"])
+    .await
+    .unwrap()
+    .is_none());
+}
+
+// The in-memory backends keep PRs and events apart, so only the databases can tell tracked PRs
+pub async fn finds_finished_prs_that_are_tracked<P: PrRepository, E: EventRepository>(
+  prs: &P,
+  events: &E,
+) {
+  for n in [1, 2, 3, 5] {
+    prs
+      .insert_all(ToInsert::new(
+        vec![pr(n)],
+        Channel("C05UBF6AJH3".to_string()),
+        Timestamp(format!("1696367451.00000{n}")),
+        now(),
+      ))
+      .await
+      .unwrap();
+  }
+  events
+    .record_event(event(pr(1), "closed", 3))
+    .await
+    .unwrap();
+  events
+    .record_event(event(pr(1), "merged", 2))
+    .await
+    .unwrap();
+  events
+    .record_event(event(pr(2), "closed", 1))
+    .await
+    .unwrap();
+  events
+    .record_event(event(pr(3), "approved", 3))
+    .await
+    .unwrap();
+  // Purged long ago
+  events
+    .record_event(event(pr(4), "merged", 48))
+    .await
+    .unwrap();
+  // Open again
+  events
+    .record_event(event(pr(5), "closed", 3))
+    .await
+    .unwrap();
+  events
+    .record_event(event(pr(5), "reopened", 2))
+    .await
+    .unwrap();
+
+  let finished = events
+    .finished_before(now() - Duration::minutes(90))
+    .await
+    .unwrap();
+  assert_eq!(finished, vec![pr(1)]);
+}
+
+pub async fn purges_old_events<E: EventRepository>(events: &E) {
+  let old = event(pr(1), "approved", 48);
+  events.record_event(old.clone()).await.unwrap();
+  events
+    .record_event(event(pr(1), "merged", 1))
+    .await
+    .unwrap();
+  events
+    .record_slack_action(SlackAction {
+      event_id: old.id.clone(),
+      kind: SlackActionKind::AddReaction,
+      channel: Channel("C05UBF6AJH3".to_string()),
+      timestamp: Timestamp("1696367451.886309".to_string()),
+      emoji: "white_check_mark".to_string(),
+      succeeded: true,
+      error: None,
+      performed_at: old.received_at,
+    })
+    .await
+    .unwrap();
+
+  let deleted = events
+    .delete_received_before(now() - Duration::hours(24))
+    .await
+    .unwrap();
+
+  assert_eq!(deleted, 1);
+  let history = events.history(pr(1)).await.unwrap();
+  assert_eq!(history.len(), 1);
+  assert_eq!(history[0].event.event_type, "merged");
+  assert!(history[0].actions.is_empty());
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use sqlx::Postgres;
+use tracing::error;
+
+use crate::metrics;
+use crate::models::{EventHistoryEntry, EventId, PrState, PrUrl, RecordedEvent, SlackAction};
+use crate::persistence::{
+  models::{EventTable, SlackActionTable},
+  RepositoryError,
+};
+
+#[async_trait::async_trait]
+pub trait EventRepository {
+  async fn record_event(&self, event: RecordedEvent) -> Result<(), RepositoryError>;
+  async fn record_slack_action(&self, action: SlackAction) -> Result<(), RepositoryError>;
+  async fn history(&self, url: PrUrl) -> Result<Vec<EventHistoryEntry>, RepositoryError>;
+  async fn states(&self, urls: Vec<PrUrl>) -> Result<HashMap<PrUrl, PrState>, RepositoryError>;
+  // The last event of the PR with one of the event types
+  async fn latest_of(
+    &self,
+    url: PrUrl,
+    event_types: &[&str],
+  ) -> Result<Option<RecordedEvent>, RepositoryError>;
+  // Tracked PRs that were last merged or closed before the cutoff
+  async fn finished_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<PrUrl>, RepositoryError>;
+  // Removes events received before the cutoff together with their Slack actions, returns the number of events
+  async fn delete_received_before(&self, cutoff: DateTime<Utc>) -> Result<u64, RepositoryError>;
+}
+
+#[derive(Clone)]
+pub struct LiveEventRepository {
+  pub pool: Arc<sqlx::Pool<Postgres>>,
+}
+
+impl LiveEventRepository {
+  pub fn new(pool: Arc<sqlx::Pool<Postgres>>) -> Self {
+    Self { pool }
+  }
+}
+
+#[async_trait::async_trait]
+impl EventRepository for LiveEventRepository {
+  #[tracing::instrument(skip_all, fields(pr_url = %event.pr_url.0, event_type = %event.event_type))]
+  async fn record_event(&self, event: RecordedEvent) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["record_event"])
+      .start_timer();
+
+    sqlx::query(
+      r"insert into events (id, delivery_id, pr_url, event_type, actor, received_at) values ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(event.id.0)
+    .bind(event.delivery_id)
+    .bind(event.pr_url.0)
+    .bind(event.event_type)
+    .bind(event.actor)
+    .bind(event.received_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %action.channel.0, timestamp = %action.timestamp.0))]
+  async fn record_slack_action(&self, action: SlackAction) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["record_slack_action"])
+      .start_timer();
+
+    sqlx::query(
+      r"insert into slack_actions (id, event_id, kind, channel, timestamp, emoji, succeeded, error, performed_at) values ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind(uuid::Uuid::new_v4())
+    .bind(action.event_id.0)
+    .bind(action.kind.name())
+    .bind(action.channel.0)
+    .bind(action.timestamp.0)
+    .bind(action.emoji)
+    .bind(action.succeeded)
+    .bind(action.error)
+    .bind(action.performed_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn history(&self, url: PrUrl) -> Result<Vec<EventHistoryEntry>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["history"])
+      .start_timer();
+
+    let events = sqlx::query_as::<_, EventTable>(
+      "select * from events where pr_url = $1 order by received_at",
+    )
+    .bind(&url.0)
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    let actions = sqlx::query_as::<_, SlackActionTable>(
+      "select slack_actions.* from slack_actions join events on events.id = slack_actions.event_id where events.pr_url = $1 order by slack_actions.performed_at",
+    )
+    .bind(&url.0)
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    Ok(history_from_rows(events, actions))
+  }
+
+  #[tracing::instrument(skip_all)]
+  async fn states(&self, urls: Vec<PrUrl>) -> Result<HashMap<PrUrl, PrState>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["states"])
+      .start_timer();
+
+    let rows = sqlx::query_as::<_, (String, String)>(
+      r"select distinct on (pr_url) pr_url, event_type from events
+        where pr_url = any($1) and event_type in ('merged', 'closed', 'reopened')
+        order by pr_url, received_at desc",
+    )
+    .bind(urls.into_iter().map(|url| url.0).collect::<Vec<_>>())
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .filter_map(|(url, event_type)| {
+          PrState::after_event(&event_type).map(|state| (PrUrl(url), state))
+        })
+        .collect(),
+    )
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn latest_of(
+    &self,
+    url: PrUrl,
+    event_types: &[&str],
+  ) -> Result<Option<RecordedEvent>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["latest_of"])
+      .start_timer();
+
+    let event = sqlx::query_as::<_, EventTable>(
+      r"select * from events where pr_url = $1 and event_type = any($2)
+        order by received_at desc limit 1",
+    )
+    .bind(url.0)
+    .bind(event_types)
+    .fetch_optional(self.pool.as_ref())
+    .await?;
+
+    Ok(event.map(|event| event.into()))
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn finished_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<PrUrl>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["finished_before"])
+      .start_timer();
+
+    let urls = sqlx::query_scalar::<_, String>(
+      r"select pr_url from (
+          select distinct on (pr_url) pr_url, event_type, received_at from events
+            where event_type in ('merged', 'closed', 'reopened')
+              and pr_url in (select url from pull_requests)
+            order by pr_url, received_at desc
+        ) latest
+        where event_type in ('merged', 'closed') and received_at < $1",
+    )
+    .bind(cutoff)
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    Ok(urls.into_iter().map(PrUrl).collect())
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn delete_received_before(&self, cutoff: DateTime<Utc>) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_events_received_before"])
+      .start_timer();
+
+    // Slack actions go with their events
+    let result = sqlx::query("delete from events where received_at < $1")
+      .bind(cutoff)
+      .execute(self.pool.as_ref())
+      .await?;
+
+    Ok(result.rows_affected())
+  }
+}
+
+pub fn history_from_rows(
+  events: Vec<EventTable>,
+  actions: Vec<SlackActionTable>,
+) -> Vec<EventHistoryEntry> {
+  group_history(
+    events.into_iter().map(|event| event.into()).collect(),
+    actions
+      .into_iter()
+      .filter_map(|action| match SlackAction::try_from(action) {
+        Ok(action) => Some(action),
+        Err(e) => {
+          error!("Skipping slack action: {}", e);
+          None
+        }
+      })
+      .collect(),
+  )
+}
+
+// Attaches every action to the event that caused it, keeping the events in order
+pub fn group_history(
+  events: Vec<RecordedEvent>,
+  actions: Vec<SlackAction>,
+) -> Vec<EventHistoryEntry> {
+  let mut actions_by_event: HashMap<EventId, Vec<SlackAction>> = HashMap::new();
+  for action in actions {
+    actions_by_event
+      .entry(action.event_id.clone())
+      .or_default()
+      .push(action);
+  }
+
+  events
+    .into_iter()
+    .map(|event| EventHistoryEntry {
+      actions: actions_by_event.remove(&event.id).unwrap_or_default(),
+      event,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::SlackActionKind;
+  use crate::persistence::{conformance, pr_repository::LivePrRepository};
+  use crate::slack::models::{Channel, Timestamp};
+  use chrono::{DateTime, Utc};
+
+  fn event(n: u128, received_at: DateTime<Utc>) -> RecordedEvent {
+    RecordedEvent {
+      id: EventId(uuid::Uuid::from_u128(n)),
+      delivery_id: Some(format!("delivery-{n}")),
+      pr_url: "https://github.com/NavidJalali/prmoji-testing/pull/1".into(),
+      event_type: "merged".to_string(),
+      actor: "NavidJalali".to_string(),
+      received_at,
+    }
+  }
+
+  fn action(event_id: u128, emoji: &str, performed_at: DateTime<Utc>) -> SlackAction {
+    SlackAction {
+      event_id: EventId(uuid::Uuid::from_u128(event_id)),
+      kind: SlackActionKind::AddReaction,
+      channel: Channel("C05UBF6AJH3".to_string()),
+      timestamp: Timestamp("1696367451.886309".to_string()),
+      emoji: emoji.to_string(),
+      succeeded: true,
+      error: None,
+      performed_at,
+    }
+  }
+
+  #[test]
+  fn groups_actions_by_event() {
+    let now = Utc::now();
+    let history = group_history(
+      vec![event(1, now), event(2, now)],
+      vec![
+        action(2, "shipit", now),
+        action(1, "white_check_mark", now),
+        action(2, "tada", now),
+      ],
+    );
+
+    let emojis = history
+      .iter()
+      .map(|entry| {
+        (
+          entry.event.id.clone(),
+          entry
+            .actions
+            .iter()
+            .map(|action| action.emoji.as_str())
+            .collect::<Vec<_>>(),
+        )
+      })
+      .collect::<Vec<_>>();
+
+    assert_eq!(
+      emojis,
+      vec![
+        (EventId(uuid::Uuid::from_u128(1)), vec!["white_check_mark"]),
+        (EventId(uuid::Uuid::from_u128(2)), vec!["shipit", "tada"]),
+      ]
+    );
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn records_history(pool: sqlx::PgPool) {
+    conformance::records_history(&LiveEventRepository::new(Arc::new(pool))).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn derives_states(pool: sqlx::PgPool) {
+    conformance::derives_states(&LiveEventRepository::new(Arc::new(pool))).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn finds_the_latest_of_some_events(pool: sqlx::PgPool) {
+    conformance::finds_the_latest_of_some_events(&LiveEventRepository::new(Arc::new(pool))).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn finds_finished_prs_that_are_tracked(pool: sqlx::PgPool) {
+    let pool = Arc::new(pool);
+    conformance::finds_finished_prs_that_are_tracked(
+      &LivePrRepository { pool: pool.clone() },
+      &LiveEventRepository::new(pool),
+    )
+    .await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn purges_old_events(pool: sqlx::PgPool) {
+    conformance::purges_old_events(&LiveEventRepository::new(Arc::new(pool))).await;
+  }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+
+use crate::models::{EventHistoryEntry, PrState, PrUrl, RecordedEvent, SlackAction};
+use crate::persistence::{
+  event_repository::{group_history, EventRepository},
+  RepositoryError,
+};
+
+// Keeps the audit log in memory, for tests and demo mode
+#[derive(Clone, Default)]
+pub struct InMemoryEventRepository {
+  events: Arc<Mutex<Vec<RecordedEvent>>>,
+  actions: Arc<Mutex<Vec<SlackAction>>>,
+}
+
+impl InMemoryEventRepository {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // The latest merged, closed or reopened event of every PR
+  fn finishing_events(&self) -> HashMap<PrUrl, RecordedEvent> {
+    let mut latest: HashMap<PrUrl, RecordedEvent> = HashMap::new();
+    for event in self.events.lock().unwrap().iter() {
+      if PrState::after_event(&event.event_type).is_none() {
+        continue;
+      }
+      let newer = latest
+        .get(&event.pr_url)
+        .is_none_or(|current| event.received_at > current.received_at);
+      if newer {
+        latest.insert(event.pr_url.clone(), event.clone());
+      }
+    }
+    latest
+  }
+}
+
+#[async_trait::async_trait]
+impl EventRepository for InMemoryEventRepository {
+  async fn record_event(&self, event: RecordedEvent) -> Result<(), RepositoryError> {
+    self.events.lock().unwrap().push(event);
+    Ok(())
+  }
+
+  async fn record_slack_action(&self, action: SlackAction) -> Result<(), RepositoryError> {
+    self.actions.lock().unwrap().push(action);
+    Ok(())
+  }
+
+  async fn history(&self, url: PrUrl) -> Result<Vec<EventHistoryEntry>, RepositoryError> {
+    let mut events = self
+      .events
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|event| event.pr_url == url)
+      .cloned()
+      .collect::<Vec<_>>();
+    events.sort_by_key(|event| event.received_at);
+
+    let mut actions = self
+      .actions
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|action| events.iter().any(|event| event.id == action.event_id))
+      .cloned()
+      .collect::<Vec<_>>();
+    actions.sort_by_key(|action| action.performed_at);
+
+    Ok(group_history(events, actions))
+  }
+
+  async fn states(&self, urls: Vec<PrUrl>) -> Result<HashMap<PrUrl, PrState>, RepositoryError> {
+    Ok(
+      self
+        .finishing_events()
+        .into_iter()
+        .filter(|(url, _)| urls.contains(url))
+        .filter_map(|(url, event)| {
+          PrState::after_event(&event.event_type).map(|state| (url, state))
+        })
+        .collect(),
+    )
+  }
+
+  async fn latest_of(
+    &self,
+    url: PrUrl,
+    event_types: &[&str],
+  ) -> Result<Option<RecordedEvent>, RepositoryError> {
+    Ok(
+      self
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|event| event.pr_url == url && event_types.contains(&event.event_type.as_str()))
+        .max_by_key(|event| event.received_at)
+        .cloned(),
+    )
+  }
+
+  async fn finished_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<PrUrl>, RepositoryError> {
+    Ok(
+      self
+        .finishing_events()
+        .into_iter()
+        .filter(|(_, event)| {
+          PrState::after_event(&event.event_type) != Some(PrState::Open)
+            && event.received_at < cutoff
+        })
+        .map(|(url, _)| url)
+        .collect(),
+    )
+  }
+
+  async fn delete_received_before(&self, cutoff: DateTime<Utc>) -> Result<u64, RepositoryError> {
+    let mut events = self.events.lock().unwrap();
+    let before = events.len();
+    events.retain(|event| event.received_at >= cutoff);
+    let ids = events
+      .iter()
+      .map(|event| event.id.clone())
+      .collect::<Vec<_>>();
+    self
+      .actions
+      .lock()
+      .unwrap()
+      .retain(|action| ids.contains(&action.event_id));
+    Ok((before - events.len()) as u64)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::persistence::conformance;
+
+  #[tokio::test]
+  async fn records_history() {
+    conformance::records_history(&InMemoryEventRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn derives_states() {
+    conformance::derives_states(&InMemoryEventRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn finds_the_latest_of_some_events() {
+    conformance::finds_the_latest_of_some_events(&InMemoryEventRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn purges_old_events() {
+    conformance::purges_old_events(&InMemoryEventRepository::new()).await;
+  }
+}
@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+
+use crate::models::{
+  ChannelSetting, PrComments, PrFilter, PrId, PrMetadata, PrUrl, ToDelete, ToInsert, PR,
+};
+use crate::persistence::pr_repository::{PoolStatus, PrRepository};
+use crate::persistence::RepositoryError;
+use crate::slack::models::Channel;
+
+// Keeps the tracked messages in memory, for tests and demo mode
+#[derive(Clone, Default)]
+pub struct InMemoryPrRepository {
+  prs: Arc<Mutex<Vec<PR>>>,
+  metadata: Arc<Mutex<HashMap<PrUrl, PrMetadata>>>,
+  // Installation ids by repository
+  installations: Arc<Mutex<HashMap<String, u64>>>,
+  channel_settings: Arc<Mutex<Vec<ChannelSetting>>>,
+  comments: Arc<Mutex<HashMap<PrUrl, PrComments>>>,
+  unavailable: Arc<AtomicBool>,
+}
+
+impl InMemoryPrRepository {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // Makes every call fail as if the database could not be reached
+  #[allow(dead_code)]
+  pub fn set_unavailable(&self, unavailable: bool) {
+    self.unavailable.store(unavailable, Ordering::SeqCst);
+  }
+
+  fn prs(&self) -> Result<std::sync::MutexGuard<'_, Vec<PR>>, RepositoryError> {
+    if self.unavailable.load(Ordering::SeqCst) {
+      Err(RepositoryError::Database(sqlx::Error::PoolTimedOut))
+    } else {
+      Ok(self.prs.lock().unwrap())
+    }
+  }
+
+  fn stored_metadata(
+    &self,
+  ) -> Result<std::sync::MutexGuard<'_, HashMap<PrUrl, PrMetadata>>, RepositoryError> {
+    if self.unavailable.load(Ordering::SeqCst) {
+      Err(RepositoryError::Database(sqlx::Error::PoolTimedOut))
+    } else {
+      Ok(self.metadata.lock().unwrap())
+    }
+  }
+
+  fn installations(
+    &self,
+  ) -> Result<std::sync::MutexGuard<'_, HashMap<String, u64>>, RepositoryError> {
+    if self.unavailable.load(Ordering::SeqCst) {
+      Err(RepositoryError::Database(sqlx::Error::PoolTimedOut))
+    } else {
+      Ok(self.installations.lock().unwrap())
+    }
+  }
+
+  fn stored_channel_settings(
+    &self,
+  ) -> Result<std::sync::MutexGuard<'_, Vec<ChannelSetting>>, RepositoryError> {
+    if self.unavailable.load(Ordering::SeqCst) {
+      Err(RepositoryError::Database(sqlx::Error::PoolTimedOut))
+    } else {
+      Ok(self.channel_settings.lock().unwrap())
+    }
+  }
+
+  fn stored_comments(
+    &self,
+  ) -> Result<std::sync::MutexGuard<'_, HashMap<PrUrl, PrComments>>, RepositoryError> {
+    if self.unavailable.load(Ordering::SeqCst) {
+      Err(RepositoryError::Database(sqlx::Error::PoolTimedOut))
+    } else {
+      Ok(self.comments.lock().unwrap())
+    }
+  }
+}
+
+fn insert(prs: &mut Vec<PR>, to_insert: ToInsert) {
+  for url in to_insert.urls {
+    let tracked = prs.iter().any(|pr| {
+      pr.url == url && pr.channel == to_insert.channel && pr.timestamp == to_insert.timestamp
+    });
+
+    if !tracked {
+      prs.push(PR {
+        id: PrId(uuid::Uuid::new_v4()),
+        url,
+        inserted_at: to_insert.inserted_at,
+        channel: to_insert.channel.clone(),
+        timestamp: to_insert.timestamp.clone(),
+      });
+    }
+  }
+}
+
+fn delete(prs: &mut Vec<PR>, to_delete: ToDelete) {
+  prs.retain(|pr| {
+    pr.channel != to_delete.channel
+      || pr.timestamp != to_delete.timestamp
+      || !to_delete.urls.contains(&pr.url)
+  });
+}
+
+#[async_trait::async_trait]
+impl PrRepository for InMemoryPrRepository {
+  async fn is_healthy(&self) -> bool {
+    self.prs().is_ok()
+  }
+
+  fn pool_status(&self) -> Option<PoolStatus> {
+    None
+  }
+
+  async fn list(&self, filter: PrFilter) -> Result<Vec<PR>, RepositoryError> {
+    let prefix = filter.url_prefix();
+    let mut prs = self
+      .prs()?
+      .iter()
+      .filter(|pr| {
+        filter
+          .channel
+          .as_ref()
+          .is_none_or(|channel| pr.channel == *channel)
+      })
+      .filter(|pr| {
+        prefix
+          .as_ref()
+          .is_none_or(|prefix| pr.url.0.starts_with(prefix))
+      })
+      .filter(|pr| {
+        filter
+          .inserted_after
+          .is_none_or(|after| pr.inserted_at >= after)
+      })
+      .filter(|pr| {
+        filter
+          .inserted_before
+          .is_none_or(|before| pr.inserted_at <= before)
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+    prs.sort_by_key(|pr| pr.inserted_at);
+    Ok(prs)
+  }
+
+  async fn get_by_url(&self, url: PrUrl) -> Result<Vec<PR>, RepositoryError> {
+    Ok(
+      self
+        .prs()?
+        .iter()
+        .filter(|pr| pr.url == url)
+        .cloned()
+        .collect(),
+    )
+  }
+
+  async fn delete_by_url(&self, url: PrUrl) -> Result<u64, RepositoryError> {
+    self.delete_by_urls(vec![url]).await
+  }
+
+  async fn delete_by_urls(&self, urls: Vec<PrUrl>) -> Result<u64, RepositoryError> {
+    let mut prs = self.prs()?;
+    let before = prs.len();
+    prs.retain(|pr| !urls.contains(&pr.url));
+    Ok((before - prs.len()) as u64)
+  }
+
+  async fn delete_inserted_before(&self, cutoff: DateTime<Utc>) -> Result<u64, RepositoryError> {
+    let mut prs = self.prs()?;
+    let before = prs.len();
+    prs.retain(|pr| pr.inserted_at >= cutoff);
+    Ok((before - prs.len()) as u64)
+  }
+
+  async fn insert_all(&self, to_insert: ToInsert) -> Result<(), RepositoryError> {
+    insert(&mut *self.prs()?, to_insert);
+    Ok(())
+  }
+
+  async fn delete_all(&self, to_delete: ToDelete) -> Result<(), RepositoryError> {
+    delete(&mut *self.prs()?, to_delete);
+    Ok(())
+  }
+
+  async fn update(&self, to_insert: ToInsert, to_delete: ToDelete) -> Result<(), RepositoryError> {
+    let mut prs = self.prs()?;
+    delete(&mut prs, to_delete);
+    insert(&mut prs, to_insert);
+    Ok(())
+  }
+  async fn upsert_metadata(&self, metadata: PrMetadata) -> Result<(), RepositoryError> {
+    let mut stored = self.stored_metadata()?;
+    let merge_commit_sha = metadata.merge_commit_sha.clone().or_else(|| {
+      stored
+        .get(&metadata.url)
+        .and_then(|previous| previous.merge_commit_sha.clone())
+    });
+    stored.insert(
+      metadata.url.clone(),
+      PrMetadata {
+        merge_commit_sha,
+        ..metadata
+      },
+    );
+    Ok(())
+  }
+
+  async fn metadata(
+    &self,
+    urls: Vec<PrUrl>,
+  ) -> Result<HashMap<PrUrl, PrMetadata>, RepositoryError> {
+    let metadata = self.stored_metadata()?;
+    Ok(
+      urls
+        .into_iter()
+        .filter_map(|url| metadata.get(&url).map(|found| (url, found.clone())))
+        .collect(),
+    )
+  }
+
+  async fn delete_metadata_updated_before(
+    &self,
+    cutoff: DateTime<Utc>,
+  ) -> Result<u64, RepositoryError> {
+    let mut metadata = self.stored_metadata()?;
+    let before = metadata.len();
+    metadata.retain(|_, metadata| metadata.updated_at >= cutoff);
+    Ok((before - metadata.len()) as u64)
+  }
+
+  async fn merged_pr(
+    &self,
+    repository: &str,
+    merge_commit_sha: &str,
+  ) -> Result<Option<PrUrl>, RepositoryError> {
+    Ok(
+      self
+        .stored_metadata()?
+        .values()
+        .find(|metadata| {
+          metadata.repository == repository
+            && metadata.merge_commit_sha.as_deref() == Some(merge_commit_sha)
+        })
+        .map(|metadata| metadata.url.clone()),
+    )
+  }
+
+  async fn save_installation(
+    &self,
+    installation_id: u64,
+    repositories: Vec<String>,
+    _updated_at: DateTime<Utc>,
+  ) -> Result<(), RepositoryError> {
+    let mut installations = self.installations()?;
+    for repository in repositories {
+      installations.insert(repository, installation_id);
+    }
+    Ok(())
+  }
+
+  async fn delete_installation(&self, installation_id: u64) -> Result<u64, RepositoryError> {
+    let mut installations = self.installations()?;
+    let before = installations.len();
+    installations.retain(|_, id| *id != installation_id);
+    Ok((before - installations.len()) as u64)
+  }
+
+  async fn delete_installation_repositories(
+    &self,
+    installation_id: u64,
+    repositories: Vec<String>,
+  ) -> Result<u64, RepositoryError> {
+    let mut installations = self.installations()?;
+    let before = installations.len();
+    installations
+      .retain(|repository, id| *id != installation_id || !repositories.contains(repository));
+    Ok((before - installations.len()) as u64)
+  }
+
+  async fn installation(&self, repository: &str) -> Result<Option<u64>, RepositoryError> {
+    Ok(self.installations()?.get(repository).copied())
+  }
+
+  async fn channel_settings(
+    &self,
+    channel: Channel,
+  ) -> Result<Vec<ChannelSetting>, RepositoryError> {
+    let mut settings = self
+      .stored_channel_settings()?
+      .iter()
+      .filter(|setting| setting.channel == channel)
+      .cloned()
+      .collect::<Vec<_>>();
+    settings.sort_by(|a, b| a.event_type.cmp(&b.event_type));
+    Ok(settings)
+  }
+
+  async fn save_channel_setting(&self, setting: ChannelSetting) -> Result<(), RepositoryError> {
+    let mut settings = self.stored_channel_settings()?;
+    settings.retain(|existing| {
+      existing.channel != setting.channel || existing.event_type != setting.event_type
+    });
+    settings.push(setting);
+    Ok(())
+  }
+
+  async fn delete_channel_settings(&self, channel: Channel) -> Result<u64, RepositoryError> {
+    let mut settings = self.stored_channel_settings()?;
+    let before = settings.len();
+    settings.retain(|setting| setting.channel != channel);
+    Ok((before - settings.len()) as u64)
+  }
+
+  async fn count_comment(
+    &self,
+    url: PrUrl,
+    review_id: Option<u64>,
+    received_at: DateTime<Utc>,
+  ) -> Result<u32, RepositoryError> {
+    let mut comments = self.stored_comments()?;
+    let entry = comments.entry(url.clone()).or_insert(PrComments {
+      url,
+      comments: 0,
+      reacted_comments: 0,
+      last_review_id: None,
+      updated_at: received_at,
+    });
+    if review_id.is_none() || review_id != entry.last_review_id {
+      entry.comments += 1;
+    }
+    entry.last_review_id = review_id.or(entry.last_review_id);
+    entry.updated_at = received_at;
+    Ok(entry.comments)
+  }
+
+  async fn comments(&self, url: PrUrl) -> Result<Option<PrComments>, RepositoryError> {
+    Ok(self.stored_comments()?.get(&url).cloned())
+  }
+
+  async fn set_reacted_comments(&self, url: PrUrl, reacted: u32) -> Result<(), RepositoryError> {
+    if let Some(comments) = self.stored_comments()?.get_mut(&url) {
+      comments.reacted_comments = reacted;
+    }
+    Ok(())
+  }
+
+  async fn delete_comments_updated_before(
+    &self,
+    cutoff: DateTime<Utc>,
+  ) -> Result<u64, RepositoryError> {
+    let mut comments = self.stored_comments()?;
+    let before = comments.len();
+    comments.retain(|_, comments| comments.updated_at >= cutoff);
+    Ok((before - comments.len()) as u64)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::persistence::conformance;
+
+  #[tokio::test]
+  async fn create_tracks_every_url() {
+    conformance::create_tracks_every_url(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn edit_replaces_every_url() {
+    conformance::edit_replaces_every_url(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn delete_removes_every_url() {
+    conformance::delete_removes_every_url(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn delete_leaves_other_messages_alone() {
+    conformance::delete_leaves_other_messages_alone(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn retried_creates_are_tracked_once() {
+    conformance::retried_creates_are_tracked_once(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn repeated_urls_are_tracked_once() {
+    conformance::repeated_urls_are_tracked_once(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn list_applies_filters() {
+    conformance::list_applies_filters(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn purges_by_url_and_age() {
+    conformance::purges_by_url_and_age(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn keeps_the_latest_metadata() {
+    conformance::keeps_the_latest_metadata(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn finds_merged_prs_by_merge_commit() {
+    conformance::finds_merged_prs_by_merge_commit(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn tracks_installations() {
+    conformance::tracks_installations(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn keeps_one_setting_per_channel_and_event() {
+    conformance::keeps_one_setting_per_channel_and_event(&InMemoryPrRepository::new()).await;
+  }
+
+  #[tokio::test]
+  async fn counts_comments() {
+    conformance::counts_comments(&InMemoryPrRepository::new()).await;
+  }
+}
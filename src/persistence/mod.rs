@@ -1,2 +1,28 @@
+#[cfg(test)]
+mod conformance;
+pub mod event_repository;
+pub mod in_memory_event_repository;
+pub mod in_memory_pr_repository;
 pub mod models;
 pub mod pr_repository;
+pub mod sqlite_event_repository;
+pub mod sqlite_pr_repository;
+
+#[derive(Debug)]
+pub enum RepositoryError {
+  Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for RepositoryError {
+  fn from(error: sqlx::Error) -> Self {
+    RepositoryError::Database(error)
+  }
+}
+
+impl std::fmt::Display for RepositoryError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RepositoryError::Database(error) => write!(f, "Database error: {}", error),
+    }
+  }
+}
@@ -9,3 +9,54 @@ pub struct PullRequestTable {
   pub channel: String,
   pub timestamp: String,
 }
+
+#[derive(sqlx::FromRow)]
+pub struct EventTable {
+  pub id: Uuid,
+  pub delivery_id: Option<String>,
+  pub pr_url: String,
+  pub event_type: String,
+  pub actor: String,
+  pub received_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct SlackActionTable {
+  pub event_id: Uuid,
+  pub kind: String,
+  pub channel: String,
+  pub timestamp: String,
+  pub emoji: String,
+  pub succeeded: bool,
+  pub error: Option<String>,
+  pub performed_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct PullRequestMetadataTable {
+  pub url: String,
+  pub repository: String,
+  pub number: i64,
+  pub title: String,
+  pub author: Option<String>,
+  pub merge_commit_sha: Option<String>,
+  pub updated_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct ChannelSettingTable {
+  pub channel: String,
+  pub event_type: String,
+  pub emoji: Option<String>,
+  pub enabled: Option<bool>,
+  pub updated_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct PullRequestCommentsTable {
+  pub url: String,
+  pub comments: i64,
+  pub reacted_comments: i64,
+  pub last_review_id: Option<i64>,
+  pub updated_at: DateTime<Utc>,
+}
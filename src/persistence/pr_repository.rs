@@ -1,20 +1,93 @@
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use sqlx::Postgres;
 use tracing::error;
 
-use crate::models::{PrUrl, ToDelete, ToInsert, PR};
+use crate::metrics;
+use crate::models::{
+  ChannelSetting, PrComments, PrFilter, PrMetadata, PrUrl, ToDelete, ToInsert, PR,
+};
+use crate::slack::models::Channel;
 
-use crate::persistence::models::PullRequestTable;
+use crate::persistence::{
+  models::{
+    ChannelSettingTable, PullRequestCommentsTable, PullRequestMetadataTable, PullRequestTable,
+  },
+  RepositoryError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+  pub max: u32,
+  pub open: u32,
+  pub idle: u32,
+}
 
 #[async_trait::async_trait]
 pub trait PrRepository {
-  async fn list(&self) -> Vec<PR>;
-  async fn get_by_url(&self, url: PrUrl) -> Vec<PR>;
-  async fn insert_all(&self, to_insert: ToInsert) -> ();
-  async fn delete_all(&self, to_delete: ToDelete) -> ();
-  async fn update(&self, to_insert: ToInsert, to_delete: ToDelete) -> ();
+  async fn is_healthy(&self) -> bool;
+  // Connection pool utilisation, if the repository is backed by one
+  fn pool_status(&self) -> Option<PoolStatus>;
+  async fn list(&self, filter: PrFilter) -> Result<Vec<PR>, RepositoryError>;
+  async fn get_by_url(&self, url: PrUrl) -> Result<Vec<PR>, RepositoryError>;
+  async fn delete_by_url(&self, url: PrUrl) -> Result<u64, RepositoryError>;
+  async fn delete_by_urls(&self, urls: Vec<PrUrl>) -> Result<u64, RepositoryError>;
+  async fn delete_inserted_before(&self, cutoff: DateTime<Utc>) -> Result<u64, RepositoryError>;
+  async fn insert_all(&self, to_insert: ToInsert) -> Result<(), RepositoryError>;
+  async fn delete_all(&self, to_delete: ToDelete) -> Result<(), RepositoryError>;
+  async fn update(&self, to_insert: ToInsert, to_delete: ToDelete) -> Result<(), RepositoryError>;
+  // Title, author and repository of a PR, replaced by whatever the latest webhook says
+  async fn upsert_metadata(&self, metadata: PrMetadata) -> Result<(), RepositoryError>;
+  async fn metadata(&self, urls: Vec<PrUrl>)
+    -> Result<HashMap<PrUrl, PrMetadata>, RepositoryError>;
+  async fn delete_metadata_updated_before(
+    &self,
+    cutoff: DateTime<Utc>,
+  ) -> Result<u64, RepositoryError>;
+  // The merged PR of the repository, by `owner/name`, whose merge commit this is
+  async fn merged_pr(
+    &self,
+    repository: &str,
+    merge_commit_sha: &str,
+  ) -> Result<Option<PrUrl>, RepositoryError>;
+  // The GitHub App installation that can read each repository, by `owner/name`
+  async fn save_installation(
+    &self,
+    installation_id: u64,
+    repositories: Vec<String>,
+    updated_at: DateTime<Utc>,
+  ) -> Result<(), RepositoryError>;
+  async fn delete_installation(&self, installation_id: u64) -> Result<u64, RepositoryError>;
+  async fn delete_installation_repositories(
+    &self,
+    installation_id: u64,
+    repositories: Vec<String>,
+  ) -> Result<u64, RepositoryError>;
+  async fn installation(&self, repository: &str) -> Result<Option<u64>, RepositoryError>;
+  // Overrides made with `/prmoji config`, at most one per event type and channel
+  async fn channel_settings(
+    &self,
+    channel: Channel,
+  ) -> Result<Vec<ChannelSetting>, RepositoryError>;
+  async fn save_channel_setting(&self, setting: ChannelSetting) -> Result<(), RepositoryError>;
+  async fn delete_channel_settings(&self, channel: Channel) -> Result<u64, RepositoryError>;
+  // Comments are counted per PR as they arrive, reactions catch up with the count later.
+  // Another comment of the review that was counted last isn't counted again
+  async fn count_comment(
+    &self,
+    url: PrUrl,
+    review_id: Option<u64>,
+    received_at: DateTime<Utc>,
+  ) -> Result<u32, RepositoryError>;
+  async fn comments(&self, url: PrUrl) -> Result<Option<PrComments>, RepositoryError>;
+  async fn set_reacted_comments(&self, url: PrUrl, reacted: u32) -> Result<(), RepositoryError>;
+  async fn delete_comments_updated_before(
+    &self,
+    cutoff: DateTime<Utc>,
+  ) -> Result<u64, RepositoryError>;
 }
 
 #[derive(Clone)]
@@ -32,37 +105,125 @@ impl LivePrRepository {
         .expect("Failed to connect to Postgres"),
     );
 
+    sqlx::migrate!("./migrations")
+      .run(pool.as_ref())
+      .await
+      .expect("Failed to migrate the Postgres database");
+
     Self { pool }
   }
 }
 
 #[async_trait::async_trait]
 impl PrRepository for LivePrRepository {
-  async fn list(&self) -> Vec<PR> {
-    let query = sqlx::query_as::<_, PullRequestTable>("select * from pull_requests");
-    let prs = query.fetch_all(self.pool.as_ref()).await.unwrap();
-    prs.into_iter().map(|pr| pr.into()).collect()
+  #[tracing::instrument(skip_all)]
+  async fn is_healthy(&self) -> bool {
+    match sqlx::query("select 1").execute(self.pool.as_ref()).await {
+      Ok(_) => true,
+      Err(e) => {
+        error!("Database is not reachable: {}", e);
+        false
+      }
+    }
   }
 
-  async fn get_by_url(&self, url: PrUrl) -> Vec<PR> {
-    let query = sqlx::query_as::<_, PullRequestTable>("select * from pull_requests where url = $1");
+  fn pool_status(&self) -> Option<PoolStatus> {
+    Some(PoolStatus {
+      max: self.pool.options().get_max_connections(),
+      open: self.pool.size(),
+      idle: self.pool.num_idle() as u32,
+    })
+  }
+
+  #[tracing::instrument(skip_all)]
+  async fn list(&self, filter: PrFilter) -> Result<Vec<PR>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["list"])
+      .start_timer();
+
+    let query = sqlx::query_as::<_, PullRequestTable>(
+      r"select * from pull_requests
+        where ($1::varchar is null or channel = $1)
+          and ($2::text is null or starts_with(url, $2))
+          and ($3::timestamptz is null or inserted_at >= $3)
+          and ($4::timestamptz is null or inserted_at <= $4)
+        order by inserted_at",
+    );
     let prs = query
-      .bind(url.0)
+      .bind(filter.channel.as_ref().map(|channel| channel.0.clone()))
+      .bind(filter.url_prefix())
+      .bind(filter.inserted_after)
+      .bind(filter.inserted_before)
       .fetch_all(self.pool.as_ref())
-      .await
-      .unwrap();
-    prs.into_iter().map(|pr| pr.into()).collect()
+      .await?;
+    Ok(prs.into_iter().map(|pr| pr.into()).collect())
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn get_by_url(&self, url: PrUrl) -> Result<Vec<PR>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["get_by_url"])
+      .start_timer();
+
+    let query = sqlx::query_as::<_, PullRequestTable>("select * from pull_requests where url = $1");
+    let prs = query.bind(url.0).fetch_all(self.pool.as_ref()).await?;
+    Ok(prs.into_iter().map(|pr| pr.into()).collect())
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn delete_by_url(&self, url: PrUrl) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_by_url"])
+      .start_timer();
+
+    let result = sqlx::query("delete from pull_requests where url = $1")
+      .bind(url.0)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
   }
 
-  async fn insert_all(&self, to_insert: ToInsert) -> () {
-    let mut txn = self.pool.begin().await.unwrap();
+  #[tracing::instrument(skip_all, fields(count = urls.len()))]
+  async fn delete_by_urls(&self, urls: Vec<PrUrl>) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_by_urls"])
+      .start_timer();
+
+    let result = sqlx::query("delete from pull_requests where url = any($1)")
+      .bind(urls.into_iter().map(|url| url.0).collect::<Vec<_>>())
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn delete_inserted_before(&self, cutoff: DateTime<Utc>) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_inserted_before"])
+      .start_timer();
+
+    let result = sqlx::query("delete from pull_requests where inserted_at < $1")
+      .bind(cutoff)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %to_insert.channel.0, timestamp = %to_insert.timestamp.0))]
+  async fn insert_all(&self, to_insert: ToInsert) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["insert_all"])
+      .start_timer();
+
+    let mut txn = self.pool.begin().await?;
     let ToInsert {
       channel,
       timestamp,
       urls,
       inserted_at,
     } = to_insert;
-    let prepared = r"insert into pull_requests (id, url, inserted_at, channel, timestamp) values ($1, $2, $3, $4, $5)";
+    let prepared = r"insert into pull_requests (id, url, inserted_at, channel, timestamp) values ($1, $2, $3, $4, $5)
+        on conflict (url, channel, timestamp) do nothing";
 
     for url in urls {
       let result = sqlx::query(prepared)
@@ -74,22 +235,25 @@ impl PrRepository for LivePrRepository {
         .execute(&mut *txn)
         .await;
 
-      match result {
-        Ok(_) => (),
-        Err(e) => {
-          error!("Failed to insert: {}", e);
-          txn.rollback().await.unwrap();
-          return;
-        }
-      };
+      if let Err(e) = result {
+        error!("Failed to insert: {}", e);
+        txn.rollback().await?;
+        return Err(e.into());
+      }
     }
 
-    txn.commit().await.unwrap();
+    txn.commit().await?;
+    Ok(())
   }
 
-  async fn delete_all(&self, to_delete: ToDelete) -> () {
+  #[tracing::instrument(skip_all, fields(channel = %to_delete.channel.0, timestamp = %to_delete.timestamp.0))]
+  async fn delete_all(&self, to_delete: ToDelete) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_all"])
+      .start_timer();
+
     if to_delete.urls.is_empty() {
-      return ();
+      Ok(())
     } else {
       let ToDelete {
         urls,
@@ -98,25 +262,24 @@ impl PrRepository for LivePrRepository {
       } = to_delete;
 
       sqlx::query(
-        "delete from pull_requests where channel = $1 and timestamp = $2 and url in ($3)",
+        "delete from pull_requests where channel = $1 and timestamp = $2 and url = any($3)",
       )
       .bind(channel.0)
       .bind(timestamp.0)
-      .bind(
-        urls
-          .into_iter()
-          .map(|url| url.0)
-          .collect::<Vec<_>>()
-          .join(","),
-      )
+      .bind(urls.into_iter().map(|url| url.0).collect::<Vec<_>>())
       .execute(self.pool.deref())
-      .await
-      .expect("Failed to delete");
-    };
+      .await?;
+      Ok(())
+    }
   }
 
-  async fn update(&self, to_insert: ToInsert, to_delete: ToDelete) -> () {
-    let mut txn = self.pool.begin().await.unwrap();
+  #[tracing::instrument(skip_all, fields(channel = %to_insert.channel.0, timestamp = %to_insert.timestamp.0))]
+  async fn update(&self, to_insert: ToInsert, to_delete: ToDelete) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["update"])
+      .start_timer();
+
+    let mut txn = self.pool.begin().await?;
 
     if !to_delete.urls.is_empty() {
       let ToDelete {
@@ -126,28 +289,19 @@ impl PrRepository for LivePrRepository {
       } = to_delete;
 
       let result = sqlx::query(
-        "delete from pull_requests where channel = $1 and timestamp = $2 and url in ($3)",
+        "delete from pull_requests where channel = $1 and timestamp = $2 and url = any($3)",
       )
       .bind(channel.0)
       .bind(timestamp.0)
-      .bind(
-        urls
-          .into_iter()
-          .map(|url| url.0)
-          .collect::<Vec<_>>()
-          .join(","),
-      )
+      .bind(urls.into_iter().map(|url| url.0).collect::<Vec<_>>())
       .execute(&mut *txn)
       .await;
 
-      match result {
-        Ok(_) => (),
-        Err(e) => {
-          error!("Failed to delete: {}", e);
-          txn.rollback().await.unwrap();
-          return;
-        }
-      };
+      if let Err(e) = result {
+        error!("Failed to delete: {}", e);
+        txn.rollback().await?;
+        return Err(e.into());
+      }
     }
 
     if !to_insert.urls.is_empty() {
@@ -157,7 +311,8 @@ impl PrRepository for LivePrRepository {
         urls,
         inserted_at,
       } = to_insert;
-      let prepared = r"insert into pull_requests (id, url, inserted_at, channel, timestamp) values ($1, $2, $3, $4, $5)";
+      let prepared = r"insert into pull_requests (id, url, inserted_at, channel, timestamp) values ($1, $2, $3, $4, $5)
+        on conflict (url, channel, timestamp) do nothing";
 
       for url in urls {
         let result = sqlx::query(prepared)
@@ -169,17 +324,445 @@ impl PrRepository for LivePrRepository {
           .execute(&mut *txn)
           .await;
 
-        match result {
-          Ok(_) => (),
-          Err(e) => {
-            error!("Failed to insert: {}", e);
-            txn.rollback().await.unwrap();
-            return;
-          }
-        };
+        if let Err(e) = result {
+          error!("Failed to insert: {}", e);
+          txn.rollback().await?;
+          return Err(e.into());
+        }
       }
     }
 
-    txn.commit().await.unwrap();
+    txn.commit().await?;
+    Ok(())
+  }
+  #[tracing::instrument(skip_all, fields(pr_url = %metadata.url.0))]
+  async fn upsert_metadata(&self, metadata: PrMetadata) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["upsert_metadata"])
+      .start_timer();
+
+    sqlx::query(
+      r"insert into pull_request_metadata (url, repository, number, title, author, merge_commit_sha, updated_at) values ($1, $2, $3, $4, $5, $6, $7)
+        on conflict (url) do update set
+          repository = excluded.repository,
+          number = excluded.number,
+          title = excluded.title,
+          author = excluded.author,
+          merge_commit_sha = coalesce(excluded.merge_commit_sha, pull_request_metadata.merge_commit_sha),
+          updated_at = excluded.updated_at",
+    )
+    .bind(metadata.url.0)
+    .bind(metadata.repository)
+    .bind(metadata.number as i64)
+    .bind(metadata.title)
+    .bind(metadata.author)
+    .bind(metadata.merge_commit_sha)
+    .bind(metadata.updated_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(count = urls.len()))]
+  async fn metadata(
+    &self,
+    urls: Vec<PrUrl>,
+  ) -> Result<HashMap<PrUrl, PrMetadata>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["metadata"])
+      .start_timer();
+
+    let rows = sqlx::query_as::<_, PullRequestMetadataTable>(
+      "select * from pull_request_metadata where url = any($1)",
+    )
+    .bind(urls.into_iter().map(|url| url.0).collect::<Vec<_>>())
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| (PrUrl(row.url.clone()), row.into()))
+        .collect(),
+    )
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn delete_metadata_updated_before(
+    &self,
+    cutoff: DateTime<Utc>,
+  ) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_metadata_updated_before"])
+      .start_timer();
+
+    let result = sqlx::query("delete from pull_request_metadata where updated_at < $1")
+      .bind(cutoff)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(repository = %repository, merge_commit_sha = %merge_commit_sha))]
+  async fn merged_pr(
+    &self,
+    repository: &str,
+    merge_commit_sha: &str,
+  ) -> Result<Option<PrUrl>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["merged_pr"])
+      .start_timer();
+
+    let url = sqlx::query_scalar::<_, String>(
+      "select url from pull_request_metadata where repository = $1 and merge_commit_sha = $2",
+    )
+    .bind(repository)
+    .bind(merge_commit_sha)
+    .fetch_optional(self.pool.as_ref())
+    .await?;
+    Ok(url.map(PrUrl))
+  }
+
+  #[tracing::instrument(skip_all, fields(installation_id = installation_id, count = repositories.len()))]
+  async fn save_installation(
+    &self,
+    installation_id: u64,
+    repositories: Vec<String>,
+    updated_at: DateTime<Utc>,
+  ) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["save_installation"])
+      .start_timer();
+
+    sqlx::query(
+      r"insert into github_installations (repository, installation_id, updated_at)
+        select unnest($1::text[]), $2, $3
+        on conflict (repository) do update set
+          installation_id = excluded.installation_id,
+          updated_at = excluded.updated_at",
+    )
+    .bind(repositories)
+    .bind(installation_id as i64)
+    .bind(updated_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(installation_id = installation_id))]
+  async fn delete_installation(&self, installation_id: u64) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_installation"])
+      .start_timer();
+
+    let result = sqlx::query("delete from github_installations where installation_id = $1")
+      .bind(installation_id as i64)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(installation_id = installation_id, count = repositories.len()))]
+  async fn delete_installation_repositories(
+    &self,
+    installation_id: u64,
+    repositories: Vec<String>,
+  ) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_installation_repositories"])
+      .start_timer();
+
+    let result = sqlx::query(
+      "delete from github_installations where installation_id = $1 and repository = any($2)",
+    )
+    .bind(installation_id as i64)
+    .bind(repositories)
+    .execute(self.pool.as_ref())
+    .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(repository = %repository))]
+  async fn installation(&self, repository: &str) -> Result<Option<u64>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["installation"])
+      .start_timer();
+
+    let installation_id = sqlx::query_scalar::<_, i64>(
+      "select installation_id from github_installations where repository = $1",
+    )
+    .bind(repository)
+    .fetch_optional(self.pool.as_ref())
+    .await?;
+    Ok(installation_id.map(|id| id as u64))
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %channel.0))]
+  async fn channel_settings(
+    &self,
+    channel: Channel,
+  ) -> Result<Vec<ChannelSetting>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["channel_settings"])
+      .start_timer();
+
+    let rows = sqlx::query_as::<_, ChannelSettingTable>(
+      "select * from channel_settings where channel = $1 order by event_type",
+    )
+    .bind(channel.0)
+    .fetch_all(self.pool.as_ref())
+    .await?;
+    Ok(rows.into_iter().map(ChannelSetting::from).collect())
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %setting.channel.0, event_type = %setting.event_type))]
+  async fn save_channel_setting(&self, setting: ChannelSetting) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["save_channel_setting"])
+      .start_timer();
+
+    sqlx::query(
+      r"insert into channel_settings (channel, event_type, emoji, enabled, updated_at) values ($1, $2, $3, $4, $5)
+        on conflict (channel, event_type) do update set
+          emoji = excluded.emoji,
+          enabled = excluded.enabled,
+          updated_at = excluded.updated_at",
+    )
+    .bind(setting.channel.0)
+    .bind(setting.event_type)
+    .bind(setting.emoji)
+    .bind(setting.enabled)
+    .bind(setting.updated_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %channel.0))]
+  async fn delete_channel_settings(&self, channel: Channel) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_channel_settings"])
+      .start_timer();
+
+    let result = sqlx::query("delete from channel_settings where channel = $1")
+      .bind(channel.0)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn count_comment(
+    &self,
+    url: PrUrl,
+    review_id: Option<u64>,
+    received_at: DateTime<Utc>,
+  ) -> Result<u32, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["count_comment"])
+      .start_timer();
+
+    let comments = sqlx::query_scalar::<_, i64>(
+      r"insert into pull_request_comments (url, comments, reacted_comments, last_review_id, updated_at) values ($1, 1, 0, $2, $3)
+        on conflict (url) do update set
+          comments = pull_request_comments.comments
+            + case when excluded.last_review_id = pull_request_comments.last_review_id then 0 else 1 end,
+          last_review_id = coalesce(excluded.last_review_id, pull_request_comments.last_review_id),
+          updated_at = excluded.updated_at
+        returning comments",
+    )
+    .bind(url.0)
+    .bind(review_id.map(|id| id as i64))
+    .bind(received_at)
+    .fetch_one(self.pool.as_ref())
+    .await?;
+    Ok(comments as u32)
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn comments(&self, url: PrUrl) -> Result<Option<PrComments>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["comments"])
+      .start_timer();
+
+    let row = sqlx::query_as::<_, PullRequestCommentsTable>(
+      "select * from pull_request_comments where url = $1",
+    )
+    .bind(url.0)
+    .fetch_optional(self.pool.as_ref())
+    .await?;
+    Ok(row.map(PrComments::from))
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0, reacted = reacted))]
+  async fn set_reacted_comments(&self, url: PrUrl, reacted: u32) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["set_reacted_comments"])
+      .start_timer();
+
+    sqlx::query("update pull_request_comments set reacted_comments = $2 where url = $1")
+      .bind(url.0)
+      .bind(reacted as i64)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn delete_comments_updated_before(
+    &self,
+    cutoff: DateTime<Utc>,
+  ) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_comments_updated_before"])
+      .start_timer();
+
+    let result = sqlx::query("delete from pull_request_comments where updated_at < $1")
+      .bind(cutoff)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
+  }
+}
+
+/*
+ * These run against a real Postgres: every test gets a fresh database with the migrations applied.
+ * DATABASE_URL=postgres://postgres@localhost:5432 cargo test -- --ignored
+ */
+#[cfg(test)]
+mod tests {
+  use chrono::Utc;
+  use sqlx::PgPool;
+
+  use super::*;
+  use crate::persistence::conformance::{self, created, pr};
+
+  fn repository(pool: PgPool) -> LivePrRepository {
+    LivePrRepository {
+      pool: Arc::new(pool),
+    }
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn create_tracks_every_url(pool: PgPool) {
+    conformance::create_tracks_every_url(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn edit_replaces_every_url(pool: PgPool) {
+    conformance::edit_replaces_every_url(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn delete_removes_every_url(pool: PgPool) {
+    conformance::delete_removes_every_url(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn delete_leaves_other_messages_alone(pool: PgPool) {
+    conformance::delete_leaves_other_messages_alone(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn retried_creates_are_tracked_once(pool: PgPool) {
+    conformance::retried_creates_are_tracked_once(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn repeated_urls_are_tracked_once(pool: PgPool) {
+    conformance::repeated_urls_are_tracked_once(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn list_applies_filters(pool: PgPool) {
+    conformance::list_applies_filters(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn purges_by_url_and_age(pool: PgPool) {
+    conformance::purges_by_url_and_age(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn keeps_the_latest_metadata(pool: PgPool) {
+    conformance::keeps_the_latest_metadata(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn finds_merged_prs_by_merge_commit(pool: PgPool) {
+    conformance::finds_merged_prs_by_merge_commit(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn tracks_installations(pool: PgPool) {
+    conformance::tracks_installations(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn keeps_one_setting_per_channel_and_event(pool: PgPool) {
+    conformance::keeps_one_setting_per_channel_and_event(&repository(pool)).await;
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn counts_comments(pool: PgPool) {
+    conformance::counts_comments(&repository(pool)).await;
+  }
+
+  #[sqlx::test(migrations = false)]
+  #[ignore = "needs a Postgres database"]
+  async fn migrations_remove_existing_duplicates(pool: PgPool) {
+    let mut migrator = sqlx::migrate!("./migrations");
+    let all = migrator.migrations.clone();
+    migrator.migrations = all
+      .iter()
+      .filter(|migration| migration.version < 3)
+      .cloned()
+      .collect::<Vec<_>>()
+      .into();
+    migrator.run(&pool).await.unwrap();
+
+    let message = created();
+    for inserted_at in ["2023-10-03T20:20:51Z", "2023-10-03T20:20:53Z"] {
+      sqlx::query(
+        "insert into pull_requests (id, url, inserted_at, channel, timestamp) values ($1, $2, $3, $4, $5)",
+      )
+      .bind(uuid::Uuid::new_v4())
+      .bind(pr(267).0)
+      .bind(inserted_at.parse::<chrono::DateTime<Utc>>().unwrap())
+      .bind(message.channel.0.clone())
+      .bind(message.event_ts.0.clone())
+      .execute(&pool)
+      .await
+      .unwrap();
+    }
+
+    migrator.migrations = all;
+    migrator.run(&pool).await.unwrap();
+
+    let repo = repository(pool);
+    let prs = repo.get_by_url(pr(267)).await.unwrap();
+    assert_eq!(prs.len(), 1);
+    assert_eq!(
+      prs[0].inserted_at,
+      "2023-10-03T20:20:51Z"
+        .parse::<chrono::DateTime<Utc>>()
+        .unwrap()
+    );
   }
 }
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use sqlx::Sqlite;
+
+use crate::metrics;
+use crate::models::{EventHistoryEntry, PrState, PrUrl, RecordedEvent, SlackAction};
+use crate::persistence::{
+  event_repository::{history_from_rows, EventRepository},
+  models::{EventTable, SlackActionTable},
+  sqlite_pr_repository::json_array,
+  RepositoryError,
+};
+
+#[derive(Clone)]
+pub struct SqliteEventRepository {
+  pub pool: Arc<sqlx::Pool<Sqlite>>,
+}
+
+impl SqliteEventRepository {
+  pub fn new(pool: Arc<sqlx::Pool<Sqlite>>) -> Self {
+    Self { pool }
+  }
+}
+
+#[async_trait::async_trait]
+impl EventRepository for SqliteEventRepository {
+  #[tracing::instrument(skip_all, fields(pr_url = %event.pr_url.0, event_type = %event.event_type))]
+  async fn record_event(&self, event: RecordedEvent) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["record_event"])
+      .start_timer();
+
+    sqlx::query(
+      r"insert into events (id, delivery_id, pr_url, event_type, actor, received_at) values (?1, ?2, ?3, ?4, ?5, ?6)",
+    )
+    .bind(event.id.0)
+    .bind(event.delivery_id)
+    .bind(event.pr_url.0)
+    .bind(event.event_type)
+    .bind(event.actor)
+    .bind(event.received_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %action.channel.0, timestamp = %action.timestamp.0))]
+  async fn record_slack_action(&self, action: SlackAction) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["record_slack_action"])
+      .start_timer();
+
+    sqlx::query(
+      r"insert into slack_actions (id, event_id, kind, channel, timestamp, emoji, succeeded, error, performed_at) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )
+    .bind(uuid::Uuid::new_v4())
+    .bind(action.event_id.0)
+    .bind(action.kind.name())
+    .bind(action.channel.0)
+    .bind(action.timestamp.0)
+    .bind(action.emoji)
+    .bind(action.succeeded)
+    .bind(action.error)
+    .bind(action.performed_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn history(&self, url: PrUrl) -> Result<Vec<EventHistoryEntry>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["history"])
+      .start_timer();
+
+    let events = sqlx::query_as::<_, EventTable>(
+      "select * from events where pr_url = ?1 order by julianday(received_at)",
+    )
+    .bind(&url.0)
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    let actions = sqlx::query_as::<_, SlackActionTable>(
+      "select slack_actions.* from slack_actions join events on events.id = slack_actions.event_id where events.pr_url = ?1 order by julianday(slack_actions.performed_at)",
+    )
+    .bind(&url.0)
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    Ok(history_from_rows(events, actions))
+  }
+
+  // SQLite takes the bare columns from the row holding the max
+  #[tracing::instrument(skip_all)]
+  async fn states(&self, urls: Vec<PrUrl>) -> Result<HashMap<PrUrl, PrState>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["states"])
+      .start_timer();
+
+    let rows = sqlx::query_as::<_, (String, String, f64)>(
+      r"select pr_url, event_type, max(julianday(received_at)) from events
+        where pr_url in (select value from json_each(?1))
+          and event_type in ('merged', 'closed', 'reopened')
+        group by pr_url",
+    )
+    .bind(json_array(urls))
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .filter_map(|(url, event_type, _)| {
+          PrState::after_event(&event_type).map(|state| (PrUrl(url), state))
+        })
+        .collect(),
+    )
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn latest_of(
+    &self,
+    url: PrUrl,
+    event_types: &[&str],
+  ) -> Result<Option<RecordedEvent>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["latest_of"])
+      .start_timer();
+
+    let event = sqlx::query_as::<_, EventTable>(
+      r"select * from events
+        where pr_url = ?1 and event_type in (select value from json_each(?2))
+        order by julianday(received_at) desc limit 1",
+    )
+    .bind(url.0)
+    .bind(serde_json::to_string(event_types).expect("A list of strings is valid JSON"))
+    .fetch_optional(self.pool.as_ref())
+    .await?;
+
+    Ok(event.map(|event| event.into()))
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn finished_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<PrUrl>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["finished_before"])
+      .start_timer();
+
+    let urls = sqlx::query_scalar::<_, String>(
+      r"select pr_url from (
+          select pr_url, event_type, max(julianday(received_at)) as received_at from events
+            where event_type in ('merged', 'closed', 'reopened')
+              and pr_url in (select url from pull_requests)
+            group by pr_url
+        )
+        where event_type in ('merged', 'closed') and received_at < julianday(?1)",
+    )
+    .bind(cutoff)
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    Ok(urls.into_iter().map(PrUrl).collect())
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn delete_received_before(&self, cutoff: DateTime<Utc>) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_events_received_before"])
+      .start_timer();
+
+    let mut tx = self.pool.begin().await?;
+    sqlx::query(
+      r"delete from slack_actions where event_id in
+        (select id from events where julianday(received_at) < julianday(?1))",
+    )
+    .bind(cutoff)
+    .execute(&mut *tx)
+    .await?;
+    let result = sqlx::query("delete from events where julianday(received_at) < julianday(?1)")
+      .bind(cutoff)
+      .execute(&mut *tx)
+      .await?;
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::persistence::{conformance, sqlite_pr_repository::SqlitePrRepository};
+
+  async fn repository() -> SqliteEventRepository {
+    SqliteEventRepository::new(SqlitePrRepository::in_memory().await.pool)
+  }
+
+  #[tokio::test]
+  async fn records_history() {
+    conformance::records_history(&repository().await).await;
+  }
+
+  #[tokio::test]
+  async fn derives_states() {
+    conformance::derives_states(&repository().await).await;
+  }
+
+  #[tokio::test]
+  async fn finds_the_latest_of_some_events() {
+    conformance::finds_the_latest_of_some_events(&repository().await).await;
+  }
+
+  #[tokio::test]
+  async fn finds_finished_prs_that_are_tracked() {
+    let prs = SqlitePrRepository::in_memory().await;
+    let events = SqliteEventRepository::new(prs.pool.clone());
+    conformance::finds_finished_prs_that_are_tracked(&prs, &events).await;
+  }
+
+  #[tokio::test]
+  async fn purges_old_events() {
+    conformance::purges_old_events(&repository().await).await;
+  }
+}
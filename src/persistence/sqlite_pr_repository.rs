@@ -0,0 +1,665 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+use sqlx::Sqlite;
+use tracing::error;
+
+use crate::metrics;
+use crate::models::{
+  ChannelSetting, PrComments, PrFilter, PrMetadata, PrUrl, ToDelete, ToInsert, PR,
+};
+use crate::persistence::pr_repository::{PoolStatus, PrRepository};
+use crate::persistence::{
+  models::{
+    ChannelSettingTable, PullRequestCommentsTable, PullRequestMetadataTable, PullRequestTable,
+  },
+  RepositoryError,
+};
+use crate::slack::models::Channel;
+
+/*
+ * SQLite has no arrays, lists of URLs are bound as a JSON array and unpacked with `json_each`.
+ * Timestamps are stored as RFC 3339 text, so they are compared with `julianday` rather than as strings.
+ */
+#[derive(Clone)]
+pub struct SqlitePrRepository {
+  pub pool: Arc<sqlx::Pool<Sqlite>>,
+}
+
+impl SqlitePrRepository {
+  pub async fn new(config: &crate::config::Database) -> Self {
+    let options = SqliteConnectOptions::new()
+      .filename(&config.path)
+      .create_if_missing(true)
+      .journal_mode(SqliteJournalMode::Wal);
+
+    let pool = SqlitePoolOptions::new()
+      .max_connections(config.pool_size)
+      .connect_with(options)
+      .await
+      .expect("Failed to open the SQLite database");
+
+    Self::migrated(pool).await
+  }
+
+  // A private database that lives as long as the repository
+  #[cfg(test)]
+  pub async fn in_memory() -> Self {
+    let pool = SqlitePoolOptions::new()
+      .max_connections(1)
+      .connect("sqlite::memory:")
+      .await
+      .expect("Failed to open an in-memory SQLite database");
+
+    Self::migrated(pool).await
+  }
+
+  async fn migrated(pool: sqlx::Pool<Sqlite>) -> Self {
+    sqlx::migrate!("./migrations/sqlite")
+      .run(&pool)
+      .await
+      .expect("Failed to migrate the SQLite database");
+
+    Self {
+      pool: Arc::new(pool),
+    }
+  }
+}
+
+pub fn json_array(urls: Vec<PrUrl>) -> String {
+  serde_json::to_string(&urls.into_iter().map(|url| url.0).collect::<Vec<_>>())
+    .expect("A list of strings is valid JSON")
+}
+
+#[async_trait::async_trait]
+impl PrRepository for SqlitePrRepository {
+  #[tracing::instrument(skip_all)]
+  async fn is_healthy(&self) -> bool {
+    match sqlx::query("select 1").execute(self.pool.as_ref()).await {
+      Ok(_) => true,
+      Err(e) => {
+        error!("Database is not reachable: {}", e);
+        false
+      }
+    }
+  }
+
+  fn pool_status(&self) -> Option<PoolStatus> {
+    Some(PoolStatus {
+      max: self.pool.options().get_max_connections(),
+      open: self.pool.size(),
+      idle: self.pool.num_idle() as u32,
+    })
+  }
+
+  #[tracing::instrument(skip_all)]
+  async fn list(&self, filter: PrFilter) -> Result<Vec<PR>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["list"])
+      .start_timer();
+
+    let query = sqlx::query_as::<_, PullRequestTable>(
+      r"select * from pull_requests
+        where (?1 is null or channel = ?1)
+          and (?2 is null or substr(url, 1, length(?2)) = ?2)
+          and (?3 is null or julianday(inserted_at) >= julianday(?3))
+          and (?4 is null or julianday(inserted_at) <= julianday(?4))
+        order by julianday(inserted_at)",
+    );
+    let prs = query
+      .bind(filter.channel.as_ref().map(|channel| channel.0.clone()))
+      .bind(filter.url_prefix())
+      .bind(filter.inserted_after)
+      .bind(filter.inserted_before)
+      .fetch_all(self.pool.as_ref())
+      .await?;
+    Ok(prs.into_iter().map(|pr| pr.into()).collect())
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn get_by_url(&self, url: PrUrl) -> Result<Vec<PR>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["get_by_url"])
+      .start_timer();
+
+    let query = sqlx::query_as::<_, PullRequestTable>("select * from pull_requests where url = ?1");
+    let prs = query.bind(url.0).fetch_all(self.pool.as_ref()).await?;
+    Ok(prs.into_iter().map(|pr| pr.into()).collect())
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn delete_by_url(&self, url: PrUrl) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_by_url"])
+      .start_timer();
+
+    let result = sqlx::query("delete from pull_requests where url = ?1")
+      .bind(url.0)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(count = urls.len()))]
+  async fn delete_by_urls(&self, urls: Vec<PrUrl>) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_by_urls"])
+      .start_timer();
+
+    let result =
+      sqlx::query("delete from pull_requests where url in (select value from json_each(?1))")
+        .bind(json_array(urls))
+        .execute(self.pool.as_ref())
+        .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn delete_inserted_before(&self, cutoff: DateTime<Utc>) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_inserted_before"])
+      .start_timer();
+
+    let result =
+      sqlx::query("delete from pull_requests where julianday(inserted_at) < julianday(?1)")
+        .bind(cutoff)
+        .execute(self.pool.as_ref())
+        .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %to_insert.channel.0, timestamp = %to_insert.timestamp.0))]
+  async fn insert_all(&self, to_insert: ToInsert) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["insert_all"])
+      .start_timer();
+
+    let mut txn = self.pool.begin().await?;
+    let ToInsert {
+      channel,
+      timestamp,
+      urls,
+      inserted_at,
+    } = to_insert;
+    let prepared = r"insert into pull_requests (id, url, inserted_at, channel, timestamp) values (?1, ?2, ?3, ?4, ?5)
+        on conflict (url, channel, timestamp) do nothing";
+
+    for url in urls {
+      let result = sqlx::query(prepared)
+        .bind(uuid::Uuid::new_v4())
+        .bind(url.0)
+        .bind(inserted_at)
+        .bind(channel.0.clone())
+        .bind(timestamp.0.clone())
+        .execute(&mut *txn)
+        .await;
+
+      if let Err(e) = result {
+        error!("Failed to insert: {}", e);
+        txn.rollback().await?;
+        return Err(e.into());
+      }
+    }
+
+    txn.commit().await?;
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %to_delete.channel.0, timestamp = %to_delete.timestamp.0))]
+  async fn delete_all(&self, to_delete: ToDelete) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_all"])
+      .start_timer();
+
+    if to_delete.urls.is_empty() {
+      Ok(())
+    } else {
+      let ToDelete {
+        urls,
+        channel,
+        timestamp,
+      } = to_delete;
+
+      sqlx::query(
+        "delete from pull_requests where channel = ?1 and timestamp = ?2 and url in (select value from json_each(?3))",
+      )
+      .bind(channel.0)
+      .bind(timestamp.0)
+      .bind(json_array(urls))
+      .execute(self.pool.as_ref())
+      .await?;
+      Ok(())
+    }
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %to_insert.channel.0, timestamp = %to_insert.timestamp.0))]
+  async fn update(&self, to_insert: ToInsert, to_delete: ToDelete) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["update"])
+      .start_timer();
+
+    let mut txn = self.pool.begin().await?;
+
+    if !to_delete.urls.is_empty() {
+      let ToDelete {
+        urls,
+        channel,
+        timestamp,
+      } = to_delete;
+
+      let result = sqlx::query(
+        "delete from pull_requests where channel = ?1 and timestamp = ?2 and url in (select value from json_each(?3))",
+      )
+      .bind(channel.0)
+      .bind(timestamp.0)
+      .bind(json_array(urls))
+      .execute(&mut *txn)
+      .await;
+
+      if let Err(e) = result {
+        error!("Failed to delete: {}", e);
+        txn.rollback().await?;
+        return Err(e.into());
+      }
+    }
+
+    if !to_insert.urls.is_empty() {
+      let ToInsert {
+        channel,
+        timestamp,
+        urls,
+        inserted_at,
+      } = to_insert;
+      let prepared = r"insert into pull_requests (id, url, inserted_at, channel, timestamp) values (?1, ?2, ?3, ?4, ?5)
+        on conflict (url, channel, timestamp) do nothing";
+
+      for url in urls {
+        let result = sqlx::query(prepared)
+          .bind(uuid::Uuid::new_v4())
+          .bind(url.0)
+          .bind(inserted_at)
+          .bind(channel.0.clone())
+          .bind(timestamp.0.clone())
+          .execute(&mut *txn)
+          .await;
+
+        if let Err(e) = result {
+          error!("Failed to insert: {}", e);
+          txn.rollback().await?;
+          return Err(e.into());
+        }
+      }
+    }
+
+    txn.commit().await?;
+    Ok(())
+  }
+  #[tracing::instrument(skip_all, fields(pr_url = %metadata.url.0))]
+  async fn upsert_metadata(&self, metadata: PrMetadata) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["upsert_metadata"])
+      .start_timer();
+
+    sqlx::query(
+      r"insert into pull_request_metadata (url, repository, number, title, author, merge_commit_sha, updated_at) values (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        on conflict (url) do update set
+          repository = excluded.repository,
+          number = excluded.number,
+          title = excluded.title,
+          author = excluded.author,
+          merge_commit_sha = coalesce(excluded.merge_commit_sha, pull_request_metadata.merge_commit_sha),
+          updated_at = excluded.updated_at",
+    )
+    .bind(metadata.url.0)
+    .bind(metadata.repository)
+    .bind(metadata.number as i64)
+    .bind(metadata.title)
+    .bind(metadata.author)
+    .bind(metadata.merge_commit_sha)
+    .bind(metadata.updated_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(count = urls.len()))]
+  async fn metadata(
+    &self,
+    urls: Vec<PrUrl>,
+  ) -> Result<HashMap<PrUrl, PrMetadata>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["metadata"])
+      .start_timer();
+
+    let rows = sqlx::query_as::<_, PullRequestMetadataTable>(
+      "select * from pull_request_metadata where url in (select value from json_each(?1))",
+    )
+    .bind(json_array(urls))
+    .fetch_all(self.pool.as_ref())
+    .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| (PrUrl(row.url.clone()), row.into()))
+        .collect(),
+    )
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn delete_metadata_updated_before(
+    &self,
+    cutoff: DateTime<Utc>,
+  ) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_metadata_updated_before"])
+      .start_timer();
+
+    let result =
+      sqlx::query("delete from pull_request_metadata where julianday(updated_at) < julianday(?1)")
+        .bind(cutoff)
+        .execute(self.pool.as_ref())
+        .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(repository = %repository, merge_commit_sha = %merge_commit_sha))]
+  async fn merged_pr(
+    &self,
+    repository: &str,
+    merge_commit_sha: &str,
+  ) -> Result<Option<PrUrl>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["merged_pr"])
+      .start_timer();
+
+    let url = sqlx::query_scalar::<_, String>(
+      "select url from pull_request_metadata where repository = ?1 and merge_commit_sha = ?2",
+    )
+    .bind(repository)
+    .bind(merge_commit_sha)
+    .fetch_optional(self.pool.as_ref())
+    .await?;
+    Ok(url.map(PrUrl))
+  }
+
+  #[tracing::instrument(skip_all, fields(installation_id = installation_id, count = repositories.len()))]
+  async fn save_installation(
+    &self,
+    installation_id: u64,
+    repositories: Vec<String>,
+    updated_at: DateTime<Utc>,
+  ) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["save_installation"])
+      .start_timer();
+
+    // `where true` tells the upsert apart from a join constraint
+    sqlx::query(
+      r"insert into github_installations (repository, installation_id, updated_at)
+        select value, ?2, ?3 from json_each(?1) where true
+        on conflict (repository) do update set
+          installation_id = excluded.installation_id,
+          updated_at = excluded.updated_at",
+    )
+    .bind(serde_json::to_string(&repositories).expect("A list of strings is valid JSON"))
+    .bind(installation_id as i64)
+    .bind(updated_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(installation_id = installation_id))]
+  async fn delete_installation(&self, installation_id: u64) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_installation"])
+      .start_timer();
+
+    let result = sqlx::query("delete from github_installations where installation_id = ?1")
+      .bind(installation_id as i64)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(installation_id = installation_id, count = repositories.len()))]
+  async fn delete_installation_repositories(
+    &self,
+    installation_id: u64,
+    repositories: Vec<String>,
+  ) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_installation_repositories"])
+      .start_timer();
+
+    let result = sqlx::query(
+      "delete from github_installations where installation_id = ?1 and repository in (select value from json_each(?2))",
+    )
+    .bind(installation_id as i64)
+    .bind(serde_json::to_string(&repositories).expect("A list of strings is valid JSON"))
+    .execute(self.pool.as_ref())
+    .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(repository = %repository))]
+  async fn installation(&self, repository: &str) -> Result<Option<u64>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["installation"])
+      .start_timer();
+
+    let installation_id = sqlx::query_scalar::<_, i64>(
+      "select installation_id from github_installations where repository = ?1",
+    )
+    .bind(repository)
+    .fetch_optional(self.pool.as_ref())
+    .await?;
+    Ok(installation_id.map(|id| id as u64))
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %channel.0))]
+  async fn channel_settings(
+    &self,
+    channel: Channel,
+  ) -> Result<Vec<ChannelSetting>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["channel_settings"])
+      .start_timer();
+
+    let rows = sqlx::query_as::<_, ChannelSettingTable>(
+      "select * from channel_settings where channel = ?1 order by event_type",
+    )
+    .bind(channel.0)
+    .fetch_all(self.pool.as_ref())
+    .await?;
+    Ok(rows.into_iter().map(ChannelSetting::from).collect())
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %setting.channel.0, event_type = %setting.event_type))]
+  async fn save_channel_setting(&self, setting: ChannelSetting) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["save_channel_setting"])
+      .start_timer();
+
+    sqlx::query(
+      r"insert into channel_settings (channel, event_type, emoji, enabled, updated_at) values (?1, ?2, ?3, ?4, ?5)
+        on conflict (channel, event_type) do update set
+          emoji = excluded.emoji,
+          enabled = excluded.enabled,
+          updated_at = excluded.updated_at",
+    )
+    .bind(setting.channel.0)
+    .bind(setting.event_type)
+    .bind(setting.emoji)
+    .bind(setting.enabled)
+    .bind(setting.updated_at)
+    .execute(self.pool.as_ref())
+    .await?;
+
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(channel = %channel.0))]
+  async fn delete_channel_settings(&self, channel: Channel) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_channel_settings"])
+      .start_timer();
+
+    let result = sqlx::query("delete from channel_settings where channel = ?1")
+      .bind(channel.0)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(result.rows_affected())
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn count_comment(
+    &self,
+    url: PrUrl,
+    review_id: Option<u64>,
+    received_at: DateTime<Utc>,
+  ) -> Result<u32, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["count_comment"])
+      .start_timer();
+
+    let comments = sqlx::query_scalar::<_, i64>(
+      r"insert into pull_request_comments (url, comments, reacted_comments, last_review_id, updated_at) values (?1, 1, 0, ?2, ?3)
+        on conflict (url) do update set
+          comments = pull_request_comments.comments
+            + case when excluded.last_review_id = pull_request_comments.last_review_id then 0 else 1 end,
+          last_review_id = coalesce(excluded.last_review_id, pull_request_comments.last_review_id),
+          updated_at = excluded.updated_at
+        returning comments",
+    )
+    .bind(url.0)
+    .bind(review_id.map(|id| id as i64))
+    .bind(received_at)
+    .fetch_one(self.pool.as_ref())
+    .await?;
+    Ok(comments as u32)
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0))]
+  async fn comments(&self, url: PrUrl) -> Result<Option<PrComments>, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["comments"])
+      .start_timer();
+
+    let row = sqlx::query_as::<_, PullRequestCommentsTable>(
+      "select * from pull_request_comments where url = ?1",
+    )
+    .bind(url.0)
+    .fetch_optional(self.pool.as_ref())
+    .await?;
+    Ok(row.map(PrComments::from))
+  }
+
+  #[tracing::instrument(skip_all, fields(pr_url = %url.0, reacted = reacted))]
+  async fn set_reacted_comments(&self, url: PrUrl, reacted: u32) -> Result<(), RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["set_reacted_comments"])
+      .start_timer();
+
+    sqlx::query("update pull_request_comments set reacted_comments = ?2 where url = ?1")
+      .bind(url.0)
+      .bind(reacted as i64)
+      .execute(self.pool.as_ref())
+      .await?;
+    Ok(())
+  }
+
+  #[tracing::instrument(skip_all, fields(cutoff = %cutoff))]
+  async fn delete_comments_updated_before(
+    &self,
+    cutoff: DateTime<Utc>,
+  ) -> Result<u64, RepositoryError> {
+    let _timer = metrics::DB_QUERY_DURATION
+      .with_label_values(&["delete_comments_updated_before"])
+      .start_timer();
+
+    let result =
+      sqlx::query("delete from pull_request_comments where julianday(updated_at) < julianday(?1)")
+        .bind(cutoff)
+        .execute(self.pool.as_ref())
+        .await?;
+    Ok(result.rows_affected())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::persistence::conformance;
+
+  #[tokio::test]
+  async fn create_tracks_every_url() {
+    conformance::create_tracks_every_url(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn edit_replaces_every_url() {
+    conformance::edit_replaces_every_url(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn delete_removes_every_url() {
+    conformance::delete_removes_every_url(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn delete_leaves_other_messages_alone() {
+    conformance::delete_leaves_other_messages_alone(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn retried_creates_are_tracked_once() {
+    conformance::retried_creates_are_tracked_once(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn repeated_urls_are_tracked_once() {
+    conformance::repeated_urls_are_tracked_once(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn list_applies_filters() {
+    conformance::list_applies_filters(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn purges_by_url_and_age() {
+    conformance::purges_by_url_and_age(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn keeps_the_latest_metadata() {
+    conformance::keeps_the_latest_metadata(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn finds_merged_prs_by_merge_commit() {
+    conformance::finds_merged_prs_by_merge_commit(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn tracks_installations() {
+    conformance::tracks_installations(&SqlitePrRepository::in_memory().await).await;
+  }
+
+  #[tokio::test]
+  async fn keeps_one_setting_per_channel_and_event() {
+    conformance::keeps_one_setting_per_channel_and_event(&SqlitePrRepository::in_memory().await)
+      .await;
+  }
+
+  #[tokio::test]
+  async fn counts_comments() {
+    conformance::counts_comments(&SqlitePrRepository::in_memory().await).await;
+  }
+}
@@ -0,0 +1,293 @@
+use tracing::{info, warn};
+
+use crate::{
+  app_state::AppState,
+  channel_settings,
+  clock::Clock,
+  metrics,
+  models::{RecordedEvent, SlackAction, SlackActionKind, PR},
+  persistence::{event_repository::EventRepository, pr_repository::PrRepository},
+  repository_rules::repository_of,
+  slack::{
+    models::{AddReactionRequest, Channel, Emoji, RemoveReactionRequest},
+    SlackClient,
+  },
+};
+
+// Adds the reaction to every tracked message and records the outcome against the event
+pub async fn add_reactions<S: AppState>(
+  state: &S,
+  event: &RecordedEvent,
+  prs: Vec<PR>,
+  emoji: Emoji,
+) -> Vec<SlackAction> {
+  let reactions = resolve_emojis(state, event, prs, emoji).await;
+  perform(state, event, SlackActionKind::AddReaction, reactions).await
+}
+
+// Removes the reaction from every tracked message, in channels that would have added it
+pub async fn remove_reactions<S: AppState>(
+  state: &S,
+  event: &RecordedEvent,
+  prs: Vec<PR>,
+  emoji: Emoji,
+) -> Vec<SlackAction> {
+  let reactions = resolve_emojis(state, event, prs, emoji).await;
+  perform(state, event, SlackActionKind::RemoveReaction, reactions).await
+}
+
+// The messages that want a reaction for the emoji, with the emoji their channel uses for it
+pub async fn resolve_emojis<S: AppState>(
+  state: &S,
+  event: &RecordedEvent,
+  prs: Vec<PR>,
+  emoji: Emoji,
+) -> Vec<(PR, String)> {
+  let repository = repository_of(&event.pr_url);
+
+  let mut settings = Vec::new();
+  let mut channels: Vec<Channel> = Vec::new();
+  for pr in &prs {
+    if !channels.contains(&pr.channel) {
+      channels.push(pr.channel.clone());
+    }
+  }
+  for channel in channels {
+    match state.pr_repository().channel_settings(channel).await {
+      Ok(channel_settings) => settings.extend(channel_settings),
+      Err(err) => warn!(
+        "Failed to read channel settings, using the configuration: {}",
+        err
+      ),
+    }
+  }
+
+  // Messages in channels that turned the event off are left alone
+  prs
+    .into_iter()
+    .filter_map(|pr| {
+      let name = channel_settings::resolve(
+        state.config(),
+        state.repository_rules(),
+        &settings,
+        &pr.channel,
+        repository.as_deref(),
+        emoji.clone(),
+      )?;
+      Some((pr, name))
+    })
+    .collect()
+}
+
+/*
+ * Adds or removes the named emojis and records the outcome against the event. A reaction that is
+ * already there, or already gone, is what we wanted, so Slack's error for it isn't a failure.
+ */
+pub async fn perform<S: AppState>(
+  state: &S,
+  event: &RecordedEvent,
+  kind: SlackActionKind,
+  reactions: Vec<(PR, String)>,
+) -> Vec<SlackAction> {
+  let slack = state.slack_client();
+
+  let calls = reactions.into_iter().map(|(pr, name)| async {
+    let result = match kind {
+      SlackActionKind::AddReaction => {
+        slack
+          .add_reaction(AddReactionRequest {
+            channel: pr.channel.clone(),
+            name: name.clone(),
+            timestamp: pr.timestamp.clone(),
+          })
+          .await
+      }
+      SlackActionKind::RemoveReaction => {
+        slack
+          .remove_reaction(RemoveReactionRequest {
+            channel: pr.channel.clone(),
+            name: name.clone(),
+            timestamp: pr.timestamp.clone(),
+          })
+          .await
+      }
+    };
+    (pr, name, result)
+  });
+
+  let results = futures::future::join_all(calls).await;
+
+  let mut actions = Vec::with_capacity(results.len());
+
+  for (pr, name, result) in results {
+    let error = match result {
+      Ok(_) => {
+        info!("Successfully performed {}", kind.name());
+        match kind {
+          SlackActionKind::AddReaction => metrics::REACTIONS_ADDED.inc(),
+          SlackActionKind::RemoveReaction => metrics::REACTIONS_REMOVED.inc(),
+        }
+        None
+      }
+      Err(err)
+        if matches!(
+          (kind, err.code().as_str()),
+          (SlackActionKind::AddReaction, "already_reacted")
+            | (SlackActionKind::RemoveReaction, "no_reaction")
+        ) =>
+      {
+        info!("Nothing to do for {}: {}", kind.name(), err.code());
+        None
+      }
+      Err(err) => {
+        warn!("Failed to perform {}: {:?}", kind.name(), err);
+        let code = err.code();
+        metrics::REACTIONS_FAILED.with_label_values(&[&code]).inc();
+        Some(code)
+      }
+    };
+
+    let action = SlackAction {
+      event_id: event.id.clone(),
+      kind,
+      channel: pr.channel,
+      timestamp: pr.timestamp,
+      emoji: name,
+      succeeded: error.is_none(),
+      error,
+      performed_at: state.clock().now(),
+    };
+
+    if let Err(err) = state
+      .event_repository()
+      .record_slack_action(action.clone())
+      .await
+    {
+      warn!("Failed to record slack action: {}", err);
+    }
+
+    actions.push(action);
+  }
+
+  actions
+}
+
+// The reactions a pull request should have given its recorded events, in order of first occurrence.
+// Pushes only get a reaction once the pull request was approved or had changes requested, and the
+// approval is only taken back once every approving review was dismissed
+pub fn reactions_for_history(events: &[RecordedEvent]) -> Vec<Emoji> {
+  let mut emojis: Vec<Emoji> = Vec::new();
+  let mut reviewed = false;
+  let mut approvers: Vec<&str> = Vec::new();
+  for event in events {
+    match event.event_type.as_str() {
+      "approved" => approvers.push(&event.actor),
+      "changes_requested" => approvers.retain(|approver| *approver != event.actor),
+      "review_dismissed" => {
+        let dismissed_approval = approvers.contains(&event.actor.as_str());
+        approvers.retain(|approver| *approver != event.actor);
+        if dismissed_approval && approvers.is_empty() {
+          emojis.retain(|emoji| *emoji != Emoji::Approved);
+        }
+      }
+      event_type => {
+        if let Some(removed) = Emoji::removed_by(event_type) {
+          emojis.retain(|emoji| *emoji != removed);
+        }
+      }
+    }
+    reviewed |= matches!(event.event_type.as_str(), "approved" | "changes_requested");
+    if let Some(emoji) = Emoji::for_event(&event.event_type) {
+      if emoji == Emoji::Updated && !reviewed {
+        continue;
+      }
+      if !emojis.contains(&emoji) {
+        emojis.push(emoji);
+      }
+    }
+  }
+  emojis
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::EventId;
+
+  fn event(event_type: &str) -> RecordedEvent {
+    event_by(event_type, "NavidJalali")
+  }
+
+  fn event_by(event_type: &str, actor: &str) -> RecordedEvent {
+    RecordedEvent {
+      id: EventId(uuid::Uuid::new_v4()),
+      delivery_id: None,
+      pr_url: "https://github.com/NavidJalali/prmoji-testing/pull/1".into(),
+      event_type: event_type.to_string(),
+      actor: actor.to_string(),
+      received_at: chrono::Utc::now(),
+    }
+  }
+
+  #[test]
+  fn replays_history_into_unique_reactions() {
+    let history = vec![
+      event("commented"),
+      event("approved"),
+      event("resync"),
+      event("commented"),
+      event("merged"),
+    ];
+
+    assert_eq!(
+      reactions_for_history(&history),
+      vec![Emoji::Comment, Emoji::Approved, Emoji::Merged]
+    );
+  }
+
+  #[test]
+  fn replays_reactions_that_were_taken_back() {
+    let history = vec![
+      event("auto_merge_enabled"),
+      event("merge_queued"),
+      event("merge_dequeued"),
+      event("auto_merge_disabled"),
+      event("auto_merge_enabled"),
+    ];
+
+    assert_eq!(reactions_for_history(&history), vec![Emoji::AutoMerge]);
+  }
+
+  #[test]
+  fn replays_pushes_after_the_first_review_only() {
+    let history = vec![
+      event("updated"),
+      event("approved"),
+      event("updated"),
+      event("review_dismissed"),
+    ];
+
+    assert_eq!(reactions_for_history(&history), vec![Emoji::Updated]);
+  }
+
+  #[test]
+  fn replays_approvals_until_the_last_one_is_dismissed() {
+    let history = vec![
+      event_by("approved", "rhalm"),
+      event_by("approved", "NavidJalali"),
+      event_by("review_dismissed", "rhalm"),
+    ];
+    assert_eq!(reactions_for_history(&history), vec![Emoji::Approved]);
+
+    let mut history = history;
+    history.push(event_by("changes_requested", "rhalm"));
+    history.push(event_by("review_dismissed", "rhalm"));
+    assert_eq!(
+      reactions_for_history(&history),
+      vec![Emoji::Approved, Emoji::ChangeRequest]
+    );
+
+    history.push(event_by("review_dismissed", "NavidJalali"));
+    assert_eq!(reactions_for_history(&history), vec![Emoji::ChangeRequest]);
+  }
+}
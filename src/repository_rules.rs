@@ -0,0 +1,231 @@
+use globset::{GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+
+use crate::{config, github::client::pull_request_path, models::PrUrl, slack::models::EVENT_TYPES};
+
+#[derive(Debug)]
+pub enum RepositoryRulesError {
+  InvalidPattern(globset::Error),
+  UnknownEvent(String),
+}
+
+impl From<globset::Error> for RepositoryRulesError {
+  fn from(error: globset::Error) -> Self {
+    RepositoryRulesError::InvalidPattern(error)
+  }
+}
+
+impl std::fmt::Display for RepositoryRulesError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RepositoryRulesError::InvalidPattern(error) => write!(f, "Invalid pattern: {}", error),
+      RepositoryRulesError::UnknownEvent(event) => write!(f, "Unknown event type: {}", event),
+    }
+  }
+}
+
+// The compiled `[repositories]` configuration, repository names are matched case insensitively like on GitHub
+#[derive(Debug, Clone)]
+pub struct RepositoryRules {
+  // Every repository is included if None
+  include: Option<GlobSet>,
+  exclude: GlobSet,
+  rules: Vec<(GlobMatcher, config::RepositoryRule)>,
+}
+
+fn glob(pattern: &str) -> Result<globset::Glob, globset::Error> {
+  GlobBuilder::new(pattern).case_insensitive(true).build()
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+  let mut builder = GlobSetBuilder::new();
+  for pattern in patterns {
+    builder.add(glob(pattern)?);
+  }
+  builder.build()
+}
+
+// `owner/name` of the repository a pull request belongs to
+pub fn repository_of(url: &PrUrl) -> Option<String> {
+  pull_request_path(url).map(|(owner, name, _)| format!("{owner}/{name}"))
+}
+
+impl RepositoryRules {
+  pub fn new(config: &config::Repositories) -> Result<Self, RepositoryRulesError> {
+    let include = match config.include.is_empty() {
+      true => None,
+      false => Some(glob_set(&config.include)?),
+    };
+
+    let mut rules = Vec::new();
+    for rule in &config.rules {
+      if let Some(event) = rule
+        .events
+        .iter()
+        .flatten()
+        .chain(rule.emojis.keys())
+        .find(|event| !EVENT_TYPES.contains(&event.as_str()))
+      {
+        return Err(RepositoryRulesError::UnknownEvent(event.clone()));
+      }
+      rules.push((glob(&rule.pattern)?.compile_matcher(), rule.clone()));
+    }
+
+    Ok(Self {
+      include,
+      exclude: glob_set(&config.exclude)?,
+      rules,
+    })
+  }
+
+  // Whether posted PRs and webhooks of a repository are processed at all
+  pub fn tracks(&self, repository: &str) -> bool {
+    self
+      .include
+      .as_ref()
+      .is_none_or(|include| include.is_match(repository))
+      && !self.exclude.is_match(repository)
+  }
+
+  fn rule(&self, repository: &str) -> Option<&config::RepositoryRule> {
+    self
+      .rules
+      .iter()
+      .find(|(pattern, _)| pattern.is_match(repository))
+      .map(|(_, rule)| rule)
+  }
+
+  // Whether events of this type add a reaction to PRs of the repository
+  pub fn reacts_to(&self, repository: &str, event_type: &str) -> bool {
+    self
+      .rule(repository)
+      .and_then(|rule| rule.events.as_ref())
+      .is_none_or(|events| events.iter().any(|event| event == event_type))
+  }
+
+  // The emoji the repository uses for events of this type instead of the global one
+  pub fn emoji(&self, repository: &str, event_type: &str) -> Option<&str> {
+    self
+      .rule(repository)
+      .and_then(|rule| {
+        rule
+          .emojis
+          .iter()
+          .find(|(name, _)| name.eq_ignore_ascii_case(event_type))
+      })
+      .map(|(_, emoji)| emoji.as_str())
+  }
+
+  pub fn tracks_pr(&self, url: &PrUrl) -> bool {
+    repository_of(url).is_none_or(|repository| self.tracks(&repository))
+  }
+
+  pub fn reacts_to_pr(&self, url: &PrUrl, event_type: &str) -> bool {
+    repository_of(url).is_none_or(|repository| self.reacts_to(&repository, event_type))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rules(include: &[&str], exclude: &[&str], rules: &[(&str, &[&str])]) -> RepositoryRules {
+    let strings = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+    RepositoryRules::new(&config::Repositories {
+      include: strings(include),
+      exclude: strings(exclude),
+      rules: rules
+        .iter()
+        .map(|(pattern, events)| config::RepositoryRule {
+          pattern: pattern.to_string(),
+          events: Some(strings(events)),
+          emojis: Default::default(),
+        })
+        .collect(),
+    })
+    .unwrap()
+  }
+
+  #[test]
+  fn tracks_everything_by_default() {
+    let rules = rules(&[], &[], &[]);
+    assert!(rules.tracks("fancy-org/cool-repo"));
+    assert!(rules.reacts_to("fancy-org/cool-repo", "commented"));
+  }
+
+  #[test]
+  fn excludes_win_over_includes() {
+    let rules = rules(&["fancy-org/*"], &["fancy-org/legacy-*"], &[]);
+    assert!(rules.tracks("fancy-org/cool-repo"));
+    assert!(rules.tracks("Fancy-Org/Cool-Repo"));
+    assert!(!rules.tracks("fancy-org/legacy-api"));
+    assert!(!rules.tracks("other-org/cool-repo"));
+    assert!(rules.tracks_pr(&PrUrl(
+      "https://github.com/fancy-org/cool-repo/pull/267".to_string()
+    )));
+    assert!(!rules.tracks_pr(&PrUrl(
+      "https://github.com/other-org/cool-repo/pull/267".to_string()
+    )));
+  }
+
+  #[test]
+  fn the_first_matching_rule_decides_the_reactions() {
+    let rules = rules(
+      &[],
+      &[],
+      &[
+        ("fancy-org/noisy", &["merged"]),
+        ("fancy-org/*", &["merged", "closed", "approved"]),
+      ],
+    );
+    assert!(rules.reacts_to("fancy-org/noisy", "merged"));
+    assert!(!rules.reacts_to("fancy-org/noisy", "approved"));
+    assert!(rules.reacts_to("fancy-org/cool-repo", "approved"));
+    assert!(!rules.reacts_to("fancy-org/cool-repo", "commented"));
+    assert!(rules.reacts_to("other-org/cool-repo", "commented"));
+  }
+
+  #[test]
+  fn rules_can_override_emojis_only() {
+    let rules = RepositoryRules::new(&config::Repositories {
+      rules: vec![config::RepositoryRule {
+        pattern: "fancy-org/design-*".to_string(),
+        events: None,
+        emojis: [("approved".to_string(), "heart".to_string())].into(),
+      }],
+      ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(
+      rules.emoji("fancy-org/design-system", "approved"),
+      Some("heart")
+    );
+    assert_eq!(rules.emoji("fancy-org/design-system", "merged"), None);
+    assert_eq!(rules.emoji("fancy-org/cool-repo", "approved"), None);
+    assert!(rules.reacts_to("fancy-org/design-system", "commented"));
+  }
+
+  #[test]
+  fn rejects_invalid_rules() {
+    let invalid = |repositories: config::Repositories| RepositoryRules::new(&repositories).is_err();
+    assert!(invalid(config::Repositories {
+      include: vec!["fancy-org/[".to_string()],
+      ..Default::default()
+    }));
+    assert!(invalid(config::Repositories {
+      rules: vec![config::RepositoryRule {
+        pattern: "fancy-org/*".to_string(),
+        events: None,
+        emojis: [("released".to_string(), "rocket".to_string())].into(),
+      }],
+      ..Default::default()
+    }));
+    assert!(invalid(config::Repositories {
+      rules: vec![config::RepositoryRule {
+        pattern: "fancy-org/*".to_string(),
+        events: Some(vec!["released".to_string()]),
+        emojis: Default::default(),
+      }],
+      ..Default::default()
+    }));
+  }
+}
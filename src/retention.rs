@@ -0,0 +1,282 @@
+use chrono::{DateTime, Duration, Utc};
+use tracing::{error, info};
+
+use crate::{
+  app_state::AppState,
+  clock::Clock,
+  config::Retention,
+  metrics,
+  persistence::{event_repository::EventRepository, pr_repository::PrRepository, RepositoryError},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cutoffs {
+  // PRs merged or closed before this are purged
+  pub finished: DateTime<Utc>,
+  // Rows inserted before this are purged
+  pub inserted: DateTime<Utc>,
+  // Events received before this are purged from the audit log
+  pub received: DateTime<Utc>,
+}
+
+impl Cutoffs {
+  pub fn new(config: &Retention, now: DateTime<Utc>) -> Self {
+    // Tracked PRs need their events for their state and for resyncs
+    let audit_log_days = config.audit_log_days.max(config.max_age_days);
+    Self {
+      finished: now - Duration::hours(config.grace_period_hours),
+      inserted: now - Duration::days(config.max_age_days),
+      received: now - Duration::days(audit_log_days),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Purged {
+  pub finished: u64,
+  pub expired: u64,
+  // Metadata of PRs GitHub hasn't mentioned since the maximum age
+  pub metadata: u64,
+  // Comment counts of PRs that haven't been commented on since the maximum age
+  pub comments: u64,
+  // Events that left the audit log, with their Slack actions
+  pub events: u64,
+}
+
+#[tracing::instrument(name = "retention", skip_all)]
+pub async fn purge<P: PrRepository, E: EventRepository, C: Clock>(
+  prs: &P,
+  events: &E,
+  clock: &C,
+  config: &Retention,
+) -> Result<Purged, RepositoryError> {
+  let cutoffs = Cutoffs::new(config, clock.now());
+
+  let finished_urls = events.finished_before(cutoffs.finished).await?;
+  let finished = if finished_urls.is_empty() {
+    0
+  } else {
+    prs.delete_by_urls(finished_urls).await?
+  };
+
+  let expired = prs.delete_inserted_before(cutoffs.inserted).await?;
+  let metadata = prs.delete_metadata_updated_before(cutoffs.inserted).await?;
+  let comments = prs.delete_comments_updated_before(cutoffs.inserted).await?;
+  let purged_events = events.delete_received_before(cutoffs.received).await?;
+
+  metrics::PRS_PURGED
+    .with_label_values(&["finished"])
+    .inc_by(finished);
+  metrics::PRS_PURGED
+    .with_label_values(&["expired"])
+    .inc_by(expired);
+
+  info!(
+    "Purged {} rows of merged or closed PRs, {} expired rows, metadata of {} PRs, comment counts of {} PRs and {} events",
+    finished, expired, metadata, comments, purged_events
+  );
+
+  Ok(Purged {
+    finished,
+    expired,
+    metadata,
+    comments,
+    events: purged_events,
+  })
+}
+
+pub fn spawn<S: AppState>(state: S) {
+  let config = state.config().retention.clone();
+  if !config.enabled {
+    info!("Retention job is disabled");
+    return;
+  }
+
+  let period = std::time::Duration::from_secs(config.interval_minutes.max(1) * 60);
+  info!("Purging old PRs every {} minute(s)", period.as_secs() / 60);
+
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(period);
+    loop {
+      interval.tick().await;
+      let result = purge(
+        state.pr_repository(),
+        state.event_repository(),
+        state.clock(),
+        &config,
+      )
+      .await;
+
+      if let Err(err) = result {
+        error!("Failed to purge old PRs: {}", err);
+      }
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use sqlx::PgPool;
+
+  use super::*;
+  use crate::{
+    clock::FrozenClock,
+    models::{EventId, PrFilter, PrMetadata, PrUrl, RecordedEvent, ToInsert},
+    persistence::{
+      event_repository::LiveEventRepository, pr_repository::LivePrRepository,
+      sqlite_event_repository::SqliteEventRepository, sqlite_pr_repository::SqlitePrRepository,
+    },
+    slack::models::{Channel, Timestamp},
+  };
+
+  fn config() -> Retention {
+    Retention {
+      enabled: true,
+      interval_minutes: 60,
+      grace_period_hours: 72,
+      max_age_days: 90,
+      audit_log_days: 180,
+    }
+  }
+
+  fn now() -> DateTime<Utc> {
+    "2023-10-03T20:20:51Z".parse().unwrap()
+  }
+
+  fn pr(n: i32) -> PrUrl {
+    PrUrl(format!("https://github.com/fancy-org/cool-repo/pull/{n}"))
+  }
+
+  #[test]
+  fn cutoffs_follow_the_clock() {
+    let clock = FrozenClock::new(now());
+
+    assert_eq!(
+      Cutoffs::new(&config(), clock.now()),
+      Cutoffs {
+        finished: "2023-09-30T20:20:51Z".parse().unwrap(),
+        inserted: "2023-07-05T20:20:51Z".parse().unwrap(),
+        received: "2023-04-06T20:20:51Z".parse().unwrap(),
+      }
+    );
+  }
+
+  #[sqlx::test]
+  #[ignore = "needs a Postgres database"]
+  async fn purges_finished_and_expired_prs_from_postgres(pool: PgPool) {
+    let pool = Arc::new(pool);
+    purges_finished_and_expired_prs(
+      &LivePrRepository { pool: pool.clone() },
+      &LiveEventRepository::new(pool),
+    )
+    .await;
+  }
+
+  #[tokio::test]
+  async fn purges_finished_and_expired_prs_from_sqlite() {
+    let prs = SqlitePrRepository::in_memory().await;
+    let events = SqliteEventRepository::new(prs.pool.clone());
+    purges_finished_and_expired_prs(&prs, &events).await;
+  }
+
+  async fn purges_finished_and_expired_prs<P: PrRepository, E: EventRepository>(
+    prs: &P,
+    events: &E,
+  ) {
+    let track = |url: PrUrl, days_ago: i64| {
+      ToInsert::new(
+        vec![url],
+        Channel("C05UBF6AJH3".to_string()),
+        Timestamp(format!("1696367451.{days_ago}")),
+        now() - Duration::days(days_ago),
+      )
+    };
+    let finish = |url: PrUrl, event_type: &str, hours_ago: i64| RecordedEvent {
+      id: EventId(uuid::Uuid::new_v4()),
+      delivery_id: None,
+      pr_url: url,
+      event_type: event_type.to_string(),
+      actor: "NavidJalali".to_string(),
+      received_at: now() - Duration::hours(hours_ago),
+    };
+
+    // Merged a week ago, closed an hour ago, approved a week ago, open for a hundred days.
+    // PR 5 was merged and purged long ago, only its events are left. PR 6 was closed a week ago
+    // and reopened four days ago
+    prs.insert_all(track(pr(6), 10)).await.unwrap();
+    prs.insert_all(track(pr(1), 10)).await.unwrap();
+    prs.insert_all(track(pr(2), 10)).await.unwrap();
+    prs.insert_all(track(pr(3), 10)).await.unwrap();
+    prs.insert_all(track(pr(4), 100)).await.unwrap();
+    events
+      .record_event(finish(pr(1), "merged", 168))
+      .await
+      .unwrap();
+    events
+      .record_event(finish(pr(2), "closed", 1))
+      .await
+      .unwrap();
+    events
+      .record_event(finish(pr(3), "approved", 168))
+      .await
+      .unwrap();
+    events
+      .record_event(finish(pr(5), "merged", 200 * 24))
+      .await
+      .unwrap();
+    events
+      .record_event(finish(pr(6), "closed", 168))
+      .await
+      .unwrap();
+    events
+      .record_event(finish(pr(6), "reopened", 96))
+      .await
+      .unwrap();
+    // Last heard of a hundred days ago, and yesterday
+    for (n, days_ago) in [(4, 100), (3, 1)] {
+      prs
+        .upsert_metadata(PrMetadata {
+          url: pr(n),
+          repository: "fancy-org/cool-repo".to_string(),
+          number: n as u32,
+          title: "Update README.md".to_string(),
+          author: Some("NavidJalali".to_string()),
+          merge_commit_sha: None,
+          updated_at: now() - Duration::days(days_ago),
+        })
+        .await
+        .unwrap();
+    }
+    prs
+      .count_comment(pr(4), None, now() - Duration::days(100))
+      .await
+      .unwrap();
+
+    let purged = purge(prs, events, &FrozenClock::new(now()), &config())
+      .await
+      .unwrap();
+
+    assert_eq!(
+      purged,
+      Purged {
+        finished: 1,
+        expired: 1,
+        metadata: 1,
+        comments: 1,
+        events: 1,
+      }
+    );
+
+    let mut remaining = prs
+      .list(PrFilter::default())
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|pr| pr.url)
+      .collect::<Vec<_>>();
+    remaining.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(remaining, vec![pr(2), pr(3), pr(6)]);
+  }
+}
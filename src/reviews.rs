@@ -0,0 +1,72 @@
+use tracing::{info, warn};
+
+use crate::{
+  app_state::AppState,
+  backfill,
+  github::client::GitHubClient,
+  models::RecordedEvent,
+  persistence::{event_repository::EventRepository, pr_repository::PrRepository, RepositoryError},
+  reactions,
+  slack::models::Emoji,
+};
+
+/*
+ * Reviews get dismissed by hand, or by branch protection once a PR is pushed to after its approval.
+ * Only the latter takes the approval back, and only when it was the last approval left: the
+ * history says what the dismissed review was, GitHub whether pushes to the base branch dismiss
+ * approvals. Without GitHub credentials approvals are kept.
+ */
+pub async fn review_dismissed<S: AppState>(
+  state: &S,
+  event: RecordedEvent,
+  base_branch: Option<&str>,
+) -> Result<(), RepositoryError> {
+  let history = state
+    .event_repository()
+    .history(event.pr_url.clone())
+    .await?
+    .into_iter()
+    .map(|entry| entry.event)
+    .collect::<Vec<_>>();
+  let before = history
+    .iter()
+    .filter(|recorded| recorded.id != event.id)
+    .cloned()
+    .collect::<Vec<_>>();
+  if !reactions::reactions_for_history(&before).contains(&Emoji::Approved)
+    || reactions::reactions_for_history(&history).contains(&Emoji::Approved)
+  {
+    info!("Approval of {:?} still stands", event.pr_url);
+    return Ok(());
+  }
+
+  let (github, branch) = match (state.github_client(), base_branch) {
+    (Some(github), Some(branch)) => (github, branch),
+    _ => return Ok(()),
+  };
+  let installation_id = backfill::installation_of(state, &event.pr_url).await?;
+  match github
+    .dismisses_stale_reviews(&event.pr_url, branch, installation_id)
+    .await
+  {
+    Ok(true) => {}
+    Ok(false) => {
+      info!("Pushes to {} don't dismiss approvals", branch);
+      return Ok(());
+    }
+    Err(err) => {
+      warn!(
+        "Failed to look up the branch protection on GitHub: {}",
+        err.code()
+      );
+      return Ok(());
+    }
+  }
+
+  let prs = state
+    .pr_repository()
+    .get_by_url(event.pr_url.clone())
+    .await?;
+  reactions::remove_reactions(state, &event, prs, Emoji::Approved).await;
+  Ok(())
+}
@@ -0,0 +1,137 @@
+use std::{
+  collections::HashSet,
+  sync::{Arc, Mutex},
+};
+
+use tracing::info;
+
+use super::{models::*, SlackClient, SlackClientError};
+
+// Only the latest requests are kept, demo mode runs for as long as the server does
+const RECORDED_LIMIT: usize = 1000;
+
+// Records reactions instead of sending them, for tests and demo mode. Like Slack it refuses to add
+// a reaction a message already has, or to remove one it doesn't have
+#[derive(Clone, Default)]
+pub struct InMemorySlackClient {
+  reactions: Arc<Mutex<Vec<AddReactionRequest>>>,
+  removed_reactions: Arc<Mutex<Vec<RemoveReactionRequest>>>,
+  // Channel, timestamp and emoji of the reactions messages have now
+  present: Arc<Mutex<HashSet<(String, String, String)>>>,
+}
+
+fn refused(error: &str) -> SlackClientError {
+  SlackClientError::UnexpectedResponse(
+    reqwest::StatusCode::OK,
+    SlackResponse {
+      ok: false,
+      error: Some(error.to_string()),
+    },
+  )
+}
+
+fn record<T>(log: &Mutex<Vec<T>>, request: T) {
+  let mut log = log.lock().unwrap();
+  if log.len() >= RECORDED_LIMIT {
+    log.remove(0);
+  }
+  log.push(request);
+}
+
+impl InMemorySlackClient {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  // The latest reactions added, in order
+  #[cfg(test)]
+  pub fn reactions(&self) -> Vec<AddReactionRequest> {
+    self.reactions.lock().unwrap().clone()
+  }
+
+  // The latest reactions removed, in order
+  #[cfg(test)]
+  pub fn removed_reactions(&self) -> Vec<RemoveReactionRequest> {
+    self.removed_reactions.lock().unwrap().clone()
+  }
+}
+
+#[async_trait::async_trait]
+impl SlackClient for InMemorySlackClient {
+  async fn add_reaction(
+    &self,
+    payload: AddReactionRequest,
+  ) -> Result<SlackResponse, SlackClientError> {
+    info!(
+      "Adding :{}: to message {} in {}",
+      payload.name, payload.timestamp.0, payload.channel.0
+    );
+    let key = (
+      payload.channel.0.clone(),
+      payload.timestamp.0.clone(),
+      payload.name.clone(),
+    );
+    if !self.present.lock().unwrap().insert(key) {
+      return Err(refused("already_reacted"));
+    }
+    record(&self.reactions, payload);
+    Ok(SlackResponse {
+      ok: true,
+      error: None,
+    })
+  }
+
+  async fn remove_reaction(
+    &self,
+    payload: RemoveReactionRequest,
+  ) -> Result<SlackResponse, SlackClientError> {
+    info!(
+      "Removing :{}: from message {} in {}",
+      payload.name, payload.timestamp.0, payload.channel.0
+    );
+    let key = (
+      payload.channel.0.clone(),
+      payload.timestamp.0.clone(),
+      payload.name.clone(),
+    );
+    if !self.present.lock().unwrap().remove(&key) {
+      return Err(refused("no_reaction"));
+    }
+    record(&self.removed_reactions, payload);
+    Ok(SlackResponse {
+      ok: true,
+      error: None,
+    })
+  }
+
+  async fn auth_test(&self) -> Result<SlackResponse, SlackClientError> {
+    Ok(SlackResponse {
+      ok: true,
+      error: None,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn keeps_only_the_latest_reactions() {
+    let client = InMemorySlackClient::new();
+    for n in 0..=RECORDED_LIMIT {
+      client
+        .add_reaction(AddReactionRequest {
+          channel: Channel("C05UBF6AJH3".to_string()),
+          timestamp: Timestamp(format!("1696367451.{n:06}")),
+          name: "white_check_mark".to_string(),
+        })
+        .await
+        .unwrap();
+    }
+
+    let reactions = client.reactions();
+    assert_eq!(reactions.len(), RECORDED_LIMIT);
+    assert_eq!(reactions[0].timestamp.0, "1696367451.000001");
+  }
+}
@@ -1,11 +1,11 @@
+pub mod in_memory;
 pub mod models;
+pub mod queue;
 
 use std::sync::Arc;
 
 use models::*;
 
-use crate::config::Emojis;
-
 #[derive(Debug)]
 pub enum SlackClientError {
   ClientSendError(reqwest::Error),
@@ -13,37 +13,73 @@ pub enum SlackClientError {
   UnexpectedResponse(reqwest::StatusCode, SlackResponse),
 }
 
+impl SlackClientError {
+  // The error code reported by Slack, e.g. `already_reacted`
+  pub fn code(&self) -> String {
+    match self {
+      SlackClientError::ClientSendError(_) => "client_send_error".to_string(),
+      SlackClientError::CannotReadBody(_) => "cannot_read_body".to_string(),
+      SlackClientError::UnexpectedResponse(status, response) => response
+        .error
+        .clone()
+        .unwrap_or_else(|| format!("http_{}", status.as_u16())),
+    }
+  }
+}
+
 #[async_trait::async_trait]
 pub trait SlackClient {
   async fn add_reaction(
     &self,
     payload: AddReactionRequest,
   ) -> Result<SlackResponse, SlackClientError>;
+  async fn remove_reaction(
+    &self,
+    payload: RemoveReactionRequest,
+  ) -> Result<SlackResponse, SlackClientError>;
+  async fn auth_test(&self) -> Result<SlackResponse, SlackClientError>;
   //async fn send_message(&self, channel: &Channel, text: &Text) -> ();
 }
 
 #[derive(Clone)]
 pub struct LiveSlackClient {
   credentials: Credentials,
-  emojis: Emojis,
   http_client: Arc<reqwest::Client>,
 }
 
 impl LiveSlackClient {
   pub fn new(config: &crate::config::Configuration) -> Self {
     let credentials = Credentials::from_config(&config.slack);
-    let emojis = config.emojis.clone();
     let http_client = Arc::new(reqwest::Client::new());
     Self {
       credentials,
-      emojis,
       http_client,
     }
   }
+
+  async fn read_response(response: reqwest::Response) -> Result<SlackResponse, SlackClientError> {
+    let status = response.status();
+
+    let body = response
+      .json::<SlackResponse>()
+      .await
+      .map_err(SlackClientError::CannotReadBody)?;
+
+    if status.is_success() && body.ok {
+      Ok(body)
+    } else {
+      Err(SlackClientError::UnexpectedResponse(status, body))
+    }
+  }
 }
 
 #[async_trait::async_trait]
 impl SlackClient for LiveSlackClient {
+  #[tracing::instrument(
+    name = "slack.reactions.add",
+    skip_all,
+    fields(channel = %payload.channel.0, timestamp = %payload.timestamp.0, emoji = %payload.name)
+  )]
   async fn add_reaction(
     &self,
     payload: AddReactionRequest,
@@ -51,23 +87,46 @@ impl SlackClient for LiveSlackClient {
     let response = self
       .http_client
       .post("https://slack.com/api/reactions.add")
-      .json(&payload.as_json(&self.emojis))
-      .bearer_auth(&self.credentials.api_token())
+      .json(&payload.into_json())
+      .bearer_auth(self.credentials.api_token())
       .send()
       .await
       .map_err(SlackClientError::ClientSendError)?;
 
-    let status = response.status();
+    Self::read_response(response).await
+  }
 
-    let body = response
-      .json::<SlackResponse>()
+  #[tracing::instrument(
+    name = "slack.reactions.remove",
+    skip_all,
+    fields(channel = %payload.channel.0, timestamp = %payload.timestamp.0, emoji = %payload.name)
+  )]
+  async fn remove_reaction(
+    &self,
+    payload: RemoveReactionRequest,
+  ) -> Result<SlackResponse, SlackClientError> {
+    let response = self
+      .http_client
+      .post("https://slack.com/api/reactions.remove")
+      .json(&payload.into_json())
+      .bearer_auth(self.credentials.api_token())
+      .send()
       .await
-      .map_err(SlackClientError::CannotReadBody)?;
+      .map_err(SlackClientError::ClientSendError)?;
 
-    if status.is_success() && body.ok {
-      Ok(body)
-    } else {
-      Err(SlackClientError::UnexpectedResponse(status, body))
-    }
+    Self::read_response(response).await
+  }
+
+  #[tracing::instrument(name = "slack.auth.test", skip_all)]
+  async fn auth_test(&self) -> Result<SlackResponse, SlackClientError> {
+    let response = self
+      .http_client
+      .post("https://slack.com/api/auth.test")
+      .bearer_auth(self.credentials.api_token())
+      .send()
+      .await
+      .map_err(SlackClientError::ClientSendError)?;
+
+    Self::read_response(response).await
   }
 }
@@ -32,6 +32,8 @@ pub struct Message {
 pub struct AlteredMessage {
   pub text: Text,
   pub user: String,
+  // The timestamp of the original message, which is what identifies it
+  pub ts: Timestamp,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -41,10 +43,21 @@ pub enum Event {
   Update(MessageUpdate),
 }
 
+impl Event {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Event::Create(_) => "message",
+      Event::Update(MessageUpdate::MessageChanged { .. }) => "message_changed",
+      Event::Update(MessageUpdate::MessageDeleted { .. }) => "message_deleted",
+    }
+  }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "subtype")]
 pub enum MessageUpdate {
+  // `event_ts` is the time of the edit, the edited message is `message.ts`
   MessageChanged {
     channel: Channel,
     channel_type: ChannelType,
@@ -56,6 +69,7 @@ pub enum MessageUpdate {
     channel: Channel,
     channel_type: ChannelType,
     event_ts: Timestamp,
+    deleted_ts: Timestamp,
     previous_message: AlteredMessage,
   },
 }
@@ -82,20 +96,118 @@ pub enum Emoji {
   Approved,
   Comment,
   ChangeRequest,
+  AutoMerge,
+  MergeQueue,
+  // New commits since the PR was reviewed
+  Updated,
+  Conflict,
+  // By environment, e.g. `production`
+  Deployed(String),
 }
 
+impl Emoji {
+  // The reaction for a GitHub event, by `GitHubEventType::name`
+  pub fn for_event(event_type: &str) -> Option<Self> {
+    match event_type {
+      "closed" => Some(Emoji::Deleted),
+      "merged" => Some(Emoji::Merged),
+      "commented" => Some(Emoji::Comment),
+      "changes_requested" => Some(Emoji::ChangeRequest),
+      "approved" => Some(Emoji::Approved),
+      "auto_merge_enabled" => Some(Emoji::AutoMerge),
+      "merge_queued" => Some(Emoji::MergeQueue),
+      "updated" => Some(Emoji::Updated),
+      "conflicted" => Some(Emoji::Conflict),
+      _ => event_type
+        .strip_prefix("deployed:")
+        .map(|environment| Emoji::Deployed(environment.to_string())),
+    }
+  }
+
+  // The reaction an event takes back, e.g. auto-merge being disabled again
+  pub fn removed_by(event_type: &str) -> Option<Self> {
+    match event_type {
+      "auto_merge_disabled" => Some(Emoji::AutoMerge),
+      "merge_dequeued" => Some(Emoji::MergeQueue),
+      "conflict_resolved" => Some(Emoji::Conflict),
+      "review_dismissed" => Some(Emoji::Approved),
+      _ => None,
+    }
+  }
+
+  pub fn event_type(&self) -> &'static str {
+    match self {
+      Emoji::Deleted => "closed",
+      Emoji::Merged => "merged",
+      Emoji::Comment => "commented",
+      Emoji::ChangeRequest => "changes_requested",
+      Emoji::Approved => "approved",
+      Emoji::AutoMerge => "auto_merge_enabled",
+      Emoji::MergeQueue => "merge_queued",
+      Emoji::Updated => "updated",
+      Emoji::Conflict => "conflicted",
+      Emoji::Deployed(_) => "deployed",
+    }
+  }
+
+  // The keys the settings of the reaction are looked up by, most specific first. Deployments can
+  // be set up for one environment, e.g. `deployed:production`, or for all of them
+  pub fn setting_keys(&self) -> Vec<String> {
+    match self {
+      Emoji::Deployed(environment) => vec![
+        format!("deployed:{}", environment.to_lowercase()),
+        "deployed".to_string(),
+      ],
+      emoji => vec![emoji.event_type().to_string()],
+    }
+  }
+}
+
+// Every event type that can get a reaction, in the order they are listed to users
+pub const EVENT_TYPES: [&str; 10] = [
+  "merged",
+  "closed",
+  "approved",
+  "changes_requested",
+  "commented",
+  "auto_merge_enabled",
+  "merge_queued",
+  "updated",
+  "conflicted",
+  "deployed",
+];
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AddReactionRequest {
   pub channel: Channel,
-  pub name: Emoji,
+  // The emoji without colons, e.g. `shipit`
+  pub name: String,
   pub timestamp: Timestamp,
 }
 
 impl AddReactionRequest {
-  pub fn as_json(self, config: &crate::config::Emojis) -> HashMap<&'static str, String> {
+  pub fn into_json(self) -> HashMap<&'static str, String> {
+    let mut map = HashMap::new();
+    map.insert("channel", self.channel.0);
+    map.insert("name", self.name);
+    map.insert("timestamp", self.timestamp.0);
+    map
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoveReactionRequest {
+  pub channel: Channel,
+  // The emoji without colons, e.g. `speech_balloon`
+  pub name: String,
+  pub timestamp: Timestamp,
+}
+
+impl RemoveReactionRequest {
+  pub fn into_json(self) -> HashMap<&'static str, String> {
     let mut map = HashMap::new();
     map.insert("channel", self.channel.0);
-    map.insert("name", config.get(self.name));
+    map.insert("name", self.name);
     map.insert("timestamp", self.timestamp.0);
     map
   }
@@ -163,9 +275,11 @@ mod tests {
           channel: Channel("C05UBF6AJH3".to_string()),
           channel_type: ChannelType::Group,
           event_ts: Timestamp("1696364748.000800".to_string()),
+          deleted_ts: Timestamp("1696364550.183599".to_string()),
           previous_message: AlteredMessage {
             text: Text("Hello world".to_string()),
             user: "U05TYH6U1K9".to_string(),
+            ts: Timestamp("1696364550.183599".to_string()),
           }
         })
       }
@@ -205,10 +319,12 @@ mod tests {
           message: AlteredMessage {
             text: Text("Scala is awesome!".to_string()),
             user: "U05TYH6U1K9".to_string(),
+            ts: Timestamp("1696367451.886309".to_string()),
           },
           previous_message: AlteredMessage {
             text: Text("Hello World!".to_string()),
             user: "U05TYH6U1K9".to_string(),
+            ts: Timestamp("1696367451.886309".to_string()),
           }
         })
       }
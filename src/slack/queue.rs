@@ -0,0 +1,231 @@
+use std::{
+  sync::{
+    atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, error, info, warn, Instrument, Span};
+
+use crate::{api, app_state::AppState, clock::Clock};
+
+use super::models::Event;
+
+/*
+ * Slack expects a reply within 3 seconds, otherwise it retries the event.
+ * The webhook handler therefore only verifies and parses the event and hands
+ * it to this queue. A fixed number of workers drain the queue in the
+ * background and do the actual (database) work.
+ */
+
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub struct QueuedEvent {
+  pub event: Event,
+  pub enqueued_at: DateTime<Utc>,
+  // The webhook span, so that processing is traced as part of the same request
+  pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum QueueError {
+  Full,
+  Closed,
+}
+
+#[derive(Debug, Default)]
+struct QueueStats {
+  in_flight: AtomicUsize,
+  processed: AtomicU64,
+  failed: AtomicU64,
+  last_lag_ms: AtomicI64,
+  max_lag_ms: AtomicI64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueueSnapshot {
+  pub depth: usize,
+  pub capacity: usize,
+  pub in_flight: usize,
+  pub processed: u64,
+  pub failed: u64,
+  pub last_lag_ms: i64,
+  pub max_lag_ms: i64,
+}
+
+#[derive(Clone)]
+pub struct SlackEventQueue {
+  sender: mpsc::Sender<QueuedEvent>,
+  receiver: Arc<Mutex<mpsc::Receiver<QueuedEvent>>>,
+  stats: Arc<QueueStats>,
+}
+
+impl SlackEventQueue {
+  pub fn new(capacity: usize) -> Self {
+    let (sender, receiver) = mpsc::channel(capacity);
+    Self {
+      sender,
+      receiver: Arc::new(Mutex::new(receiver)),
+      stats: Arc::new(QueueStats::default()),
+    }
+  }
+
+  pub fn enqueue(&self, event: Event, enqueued_at: DateTime<Utc>) -> Result<(), QueueError> {
+    self
+      .sender
+      .try_send(QueuedEvent {
+        event,
+        enqueued_at,
+        span: Span::current(),
+      })
+      .map_err(|err| match err {
+        mpsc::error::TrySendError::Full(_) => QueueError::Full,
+        mpsc::error::TrySendError::Closed(_) => QueueError::Closed,
+      })
+  }
+
+  // Number of events waiting to be picked up by a worker
+  pub fn depth(&self) -> usize {
+    self.sender.max_capacity() - self.sender.capacity()
+  }
+
+  pub fn snapshot(&self) -> QueueSnapshot {
+    QueueSnapshot {
+      depth: self.depth(),
+      capacity: self.sender.max_capacity(),
+      in_flight: self.stats.in_flight.load(Ordering::Relaxed),
+      processed: self.stats.processed.load(Ordering::Relaxed),
+      failed: self.stats.failed.load(Ordering::Relaxed),
+      last_lag_ms: self.stats.last_lag_ms.load(Ordering::Relaxed),
+      max_lag_ms: self.stats.max_lag_ms.load(Ordering::Relaxed),
+    }
+  }
+
+  async fn next(&self) -> Option<QueuedEvent> {
+    let event = self.receiver.lock().await.recv().await;
+    if event.is_some() {
+      self.stats.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+    event
+  }
+
+  fn record_lag(&self, enqueued_at: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    let lag_ms = (now - enqueued_at).num_milliseconds().max(0);
+    self.stats.last_lag_ms.store(lag_ms, Ordering::Relaxed);
+    self.stats.max_lag_ms.fetch_max(lag_ms, Ordering::Relaxed);
+    lag_ms
+  }
+
+  fn done(&self, succeeded: bool) {
+    self.stats.in_flight.fetch_sub(1, Ordering::Relaxed);
+    self.stats.processed.fetch_add(1, Ordering::Relaxed);
+    if !succeeded {
+      self.stats.failed.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+
+  // Slack will not redeliver an event it got a reply for, so failures are retried here
+  async fn process<S: AppState>(state: &S, event: Event, span: Span) -> bool {
+    let mut attempt = 1;
+    loop {
+      let result = api::process_slack_event(state, event.clone())
+        .instrument(span.clone())
+        .await;
+
+      match result {
+        Ok(()) => return true,
+        Err(err) if attempt < MAX_ATTEMPTS => {
+          warn!(parent: &span, "Failed to process Slack event (attempt {}): {}", attempt, err);
+          tokio::time::sleep(RETRY_DELAY * attempt).await;
+          attempt += 1;
+        }
+        Err(err) => {
+          error!(parent: &span, "Giving up on Slack event after {} attempts: {}", attempt, err);
+          return false;
+        }
+      }
+    }
+  }
+
+  pub fn spawn_workers<S: AppState>(&self, state: S, workers: usize) {
+    info!("Starting {} Slack event worker(s)", workers);
+    for _ in 0..workers {
+      let queue = self.clone();
+      let state = state.clone();
+      tokio::spawn(async move {
+        while let Some(QueuedEvent {
+          event,
+          enqueued_at,
+          span,
+        }) = queue.next().await
+        {
+          let lag_ms = queue.record_lag(enqueued_at, state.clock().now());
+          debug!(parent: &span, "Processing Slack event after {}ms in queue", lag_ms);
+          let succeeded = Self::process(&state, event, span).await;
+          queue.done(succeeded);
+        }
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::slack::models::{Channel, ChannelType, Message, Text, Timestamp};
+
+  fn event() -> Event {
+    Event::Create(Message {
+      channel: Channel("C05UBF6AJH3".to_string()),
+      channel_type: ChannelType::Group,
+      text: Text("Hello World!".to_string()),
+      event_ts: Timestamp("1696367451.886309".to_string()),
+    })
+  }
+
+  #[test]
+  fn rejects_events_when_full() {
+    let queue = SlackEventQueue::new(2);
+    let now = Utc::now();
+
+    assert!(queue.enqueue(event(), now).is_ok());
+    assert!(queue.enqueue(event(), now).is_ok());
+    assert!(matches!(queue.enqueue(event(), now), Err(QueueError::Full)));
+    assert_eq!(queue.depth(), 2);
+  }
+
+  #[tokio::test]
+  async fn tracks_depth_and_lag() {
+    let queue = SlackEventQueue::new(8);
+    let enqueued_at = Utc::now();
+
+    queue.enqueue(event(), enqueued_at).unwrap();
+    queue.enqueue(event(), enqueued_at).unwrap();
+
+    let queued = queue.next().await.unwrap();
+    queue.record_lag(
+      queued.enqueued_at,
+      enqueued_at + chrono::Duration::milliseconds(1500),
+    );
+    queue.done(true);
+
+    assert_eq!(
+      queue.snapshot(),
+      QueueSnapshot {
+        depth: 1,
+        capacity: 8,
+        in_flight: 0,
+        processed: 1,
+        failed: 0,
+        last_lag_ms: 1500,
+        max_lag_ms: 1500,
+      }
+    );
+  }
+}
@@ -0,0 +1,52 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::{LogFormat, Telemetry};
+
+/*
+ * Logs are always written to stdout, either as text or as JSON.
+ * If an OTLP endpoint is configured, spans are also exported to it.
+ * The returned provider has to be shut down on exit to flush pending spans.
+ */
+pub fn init(config: &Telemetry) -> Option<SdkTracerProvider> {
+  let filter = EnvFilter::try_new(&config.level).unwrap_or_else(|err| {
+    eprintln!("Invalid log level {:?}, using info: {}", config.level, err);
+    EnvFilter::new("info")
+  });
+
+  let fmt = match config.format {
+    LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+    LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+  };
+
+  let provider = config.otlp_endpoint.as_ref().map(|endpoint| {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+      .with_http()
+      .with_endpoint(endpoint)
+      .build()
+      .expect("Failed to build the OTLP span exporter");
+
+    SdkTracerProvider::builder()
+      .with_batch_exporter(exporter)
+      .with_resource(
+        Resource::builder()
+          .with_service_name(config.service_name.clone())
+          .build(),
+      )
+      .build()
+  });
+
+  let otel = provider.as_ref().map(|provider| {
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(config.service_name.clone()))
+  });
+
+  tracing_subscriber::registry()
+    .with(filter)
+    .with(fmt)
+    .with(otel)
+    .init();
+
+  provider
+}
@@ -0,0 +1,41 @@
+// Setup shared by the tests that follow a tracked PR and look at its reactions
+use crate::{
+  app_state::{AppState, TestState},
+  clock::Clock,
+  models::{PrUrl, ToInsert},
+  persistence::pr_repository::PrRepository,
+  slack::models::{Channel, Timestamp},
+};
+
+// Tracks the PR as if it was posted in a channel
+pub async fn track(state: &TestState, url: &PrUrl) {
+  state
+    .pr_repository()
+    .insert_all(ToInsert::new(
+      vec![url.clone()],
+      Channel("C05UBF6AJH3".to_string()),
+      Timestamp("1696367451.886309".to_string()),
+      state.clock().now(),
+    ))
+    .await
+    .unwrap();
+}
+
+// The names of the reactions added so far, in order
+pub fn added_names(state: &TestState) -> Vec<String> {
+  state
+    .slack_client
+    .reactions()
+    .into_iter()
+    .map(|reaction| reaction.name)
+    .collect()
+}
+
+pub fn removed_names(state: &TestState) -> Vec<String> {
+  state
+    .slack_client
+    .removed_reactions()
+    .into_iter()
+    .map(|reaction| reaction.name)
+    .collect()
+}
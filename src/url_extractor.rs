@@ -7,7 +7,7 @@ static PR_REGEX: Lazy<Regex> = Lazy::new(|| {
   Regex::new(r"https:\/\/github\.com\/[A-Za-z0-9_.-]+\/[A-Za-z0-9_.-]+\/pull\/\d+").unwrap()
 });
 
-pub fn extract_pr_urls(message: &String) -> Vec<PrUrl> {
+pub fn extract_pr_urls(message: &str) -> Vec<PrUrl> {
   PR_REGEX
     .captures_iter(message)
     .map(|cap| PrUrl(cap[0].to_string()))
@@ -24,7 +24,7 @@ mod tests {
 
   #[test]
   fn find_single_pr_url() {
-    let message = format!("Hello please take a look at this pr {}", pr(69).to_string());
+    let message = format!("Hello please take a look at this pr {}", pr(69));
     let urls = extract_pr_urls(&message);
     assert_eq!(urls, vec![PrUrl(pr(69))]);
   }
@@ -44,7 +44,7 @@ mod tests {
     let urls = extract_pr_urls(&message.to_string());
     assert_eq!(
       urls,
-      vec![pr(267), pr(268), pr(269)]
+      [pr(267), pr(268), pr(269)]
         .iter()
         .map(|s| PrUrl(s.to_string()))
         .collect::<Vec<PrUrl>>()
@@ -54,7 +54,7 @@ mod tests {
   #[test]
   fn find_no_pr_urls() {
     let message = "Hello please take a look at these nuts lmao";
-    let urls = extract_pr_urls(&message.to_string());
+    let urls = extract_pr_urls(message);
     assert_eq!(urls, Vec::<PrUrl>::new());
   }
 }